@@ -23,9 +23,10 @@ impl Apmlify for PredictedLabels {
 
             let amplified_values = t.to_owned() * Array1::from_vec(normalized_coeffs.clone());
             let amplified_sum = amplified_values.iter().sum::<f32>();
-            assert!(amplified_sum > 0.0);
-
-            t.assign(&(amplified_values / amplified_sum));
+            // Classes a model never predicts can't be amplified, e.g. talk of AO.
+            if amplified_sum > 0.0 {
+                t.assign(&(amplified_values / amplified_sum));
+            }
         }
         self
     }
@@ -41,4 +42,11 @@ mod tests {
         let sut = array![[0.1, 0.7, 0.2]].amplified(&[0.33, 1.00, 0.10]);
         assert_eq!(sut, array![[0.043_824_706, 0.929_614_9, 0.026_560_428]]);
     }
+
+    #[test]
+    fn test_amplified_unpredicted_class() {
+        // AO predicts no talk, amplifying talk only leaves the prediction as is.
+        let sut = array![[0.3, 0.7, 0.0]].amplified(&[0.0, 0.0, 1.0]);
+        assert_eq!(sut, array![[0.3, 0.7, 0.0]]);
+    }
 }
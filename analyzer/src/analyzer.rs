@@ -7,22 +7,28 @@ use std::{
     time::Duration,
 };
 
-use classifier::{Classify, ClassifyModel};
+use classifier::Classify;
 use enumflags2::BitFlags;
 use flume::TryRecvError;
 use ndarray_stats::QuantileExt;
 
 use codec::{resample_16k_mono_s16_frames, AudioFrame, FrameDuration, Timestamp};
 
-use crate::{amplify::Apmlify, rate::Rate, AnalyzerOpts, ContentKind, LabelSmoother};
+use crate::{
+    amplify::Apmlify, rate::Rate, Amplification, AnalyzerConfig, AnalyzerOpts, ContentKind,
+    LabelSmoother,
+};
 
 pub struct BufferedAnalyzer {
     frame_sender: flume::Sender<AudioFrame>,
-    processed_receiver: flume::Receiver<Vec<(ContentKind, AudioFrame)>>,
+    // Boundary hints, as number of frames pushed before the hint.
+    boundary_sender: flume::Sender<usize>,
+    pushed: usize,
+    processed_receiver: flume::Receiver<Vec<(ContentKind, f32, AudioFrame)>>,
     worker_stats_receiver: flume::Receiver<(Duration, String)>,
     stats_sender: flume::Sender<Stats>,
     last_kind: ContentKind,
-    output_queue: VecDeque<(ContentKind, AudioFrame)>,
+    output_queue: VecDeque<(ContentKind, f32, AudioFrame)>,
     ads_duration: Duration,
     ads_counter: usize,
     processing_flag: Arc<AtomicBool>,
@@ -31,36 +37,41 @@ pub struct BufferedAnalyzer {
 
 pub const DRAIN_DURATION: Duration = Duration::from_millis(200);
 const PROCESSING_DURATION: Duration = Duration::from_millis(950);
-// Duplicate samples N times to increase prediction accuracy.
-const REPEAT_SAMPLE: usize = 4;
-
-const MODEL: ClassifyModel = ClassifyModel::AMT;
-const AMPLIFICATION: [f32; 3] = [1., 5., 5.];
-
 impl BufferedAnalyzer {
     #[must_use]
-    pub fn new(smoother: LabelSmoother, opts: BitFlags<AnalyzerOpts>) -> Self {
+    pub fn new(
+        classifier: Arc<dyn Classify>,
+        config: &AnalyzerConfig,
+        smoother: LabelSmoother,
+        opts: BitFlags<AnalyzerOpts>,
+    ) -> Self {
         // Send frame processing stats to printer thread.
         let (stats_sender, stats_receiver) = flume::unbounded();
         std::thread::spawn(move || stats_worker(&stats_receiver, opts));
 
         // Send frame to processing thread.
         let (frame_sender, frame_receiver) = flume::unbounded();
+        // Send boundary hints to processing thread.
+        let (boundary_sender, boundary_receiver) = flume::unbounded();
         // Receive processed frames from processing thread.
         let (processed_sender, processed_receiver) = flume::unbounded();
         // Receive processing stats.
         let (worker_stats_sender, worker_stats_receiver) = flume::unbounded();
 
-        let classifier = classifier::create("./models", MODEL).expect("Initialized classifier");
-
         let processing_flag = Arc::new(AtomicBool::new(false));
         let flag = processing_flag.clone();
 
+        let amplification = config.amplification;
+        let repeat_sample = config.repeat_sample;
+
         std::thread::spawn(move || {
             processing_worker(
                 classifier.as_ref(),
+                amplification,
+                repeat_sample,
                 smoother,
                 &frame_receiver,
+                &boundary_receiver,
                 &processed_sender,
                 &worker_stats_sender,
                 &flag,
@@ -69,6 +80,8 @@ impl BufferedAnalyzer {
 
         Self {
             frame_sender,
+            boundary_sender,
+            pushed: 0,
             processed_receiver,
             worker_stats_receiver,
             stats_sender,
@@ -91,11 +104,28 @@ impl BufferedAnalyzer {
 
     pub fn push(&mut self, frame: AudioFrame) -> anyhow::Result<()> {
         self.frame_sender.send(frame)?;
+        self.pushed += 1;
+
+        Ok(())
+    }
+
+    /// Hints that a content boundary is likely before the next pushed frame.
+    pub fn hint_boundary(&mut self) -> anyhow::Result<()> {
+        self.boundary_sender.send(self.pushed)?;
 
         Ok(())
     }
 
     pub fn pop(&mut self) -> anyhow::Result<Vec<(ContentKind, AudioFrame)>> {
+        Ok(self
+            .pop_with_confidence()?
+            .into_iter()
+            .map(|(kind, _, frame)| (kind, frame))
+            .collect())
+    }
+
+    /// Same as `pop`, but also returns smoothed confidence of the detected kind.
+    pub fn pop_with_confidence(&mut self) -> anyhow::Result<Vec<(ContentKind, f32, AudioFrame)>> {
         match self.processed_receiver.try_recv() {
             Ok(processed_frames) => {
                 self.output_queue.extend(processed_frames);
@@ -115,7 +145,7 @@ impl BufferedAnalyzer {
             }
         };
 
-        if let Some((kind, frame)) = self.output_queue.front() {
+        if let Some((kind, _, frame)) = self.output_queue.front() {
             let kind = *kind;
 
             if kind == ContentKind::Advertisement {
@@ -189,17 +219,24 @@ fn stats_worker(receiver: &flume::Receiver<Stats>, opts: BitFlags<AnalyzerOpts>)
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn processing_worker(
     classifier: &dyn Classify,
+    amplification: Amplification,
+    repeat_sample: usize,
     mut smoother: LabelSmoother,
     frame_receiver: &flume::Receiver<AudioFrame>,
-    processed_sender: &flume::Sender<Vec<(ContentKind, AudioFrame)>>,
+    boundary_receiver: &flume::Receiver<usize>,
+    processed_sender: &flume::Sender<Vec<(ContentKind, f32, AudioFrame)>>,
     worker_stats_sender: &flume::Sender<(Duration, String)>,
     processing_flag: &Arc<AtomicBool>,
 ) -> anyhow::Result<()> {
     let mut rate = Rate::new();
     let mut input_queue = VecDeque::<AudioFrame>::new();
     let mut output_queue = VecDeque::<AudioFrame>::new();
+    let mut boundaries = VecDeque::<usize>::new();
+    // Frames moved to the output queue so far.
+    let mut drained = 0;
 
     while !frame_receiver.is_disconnected() {
         processing_flag.store(true, atomic::Ordering::SeqCst);
@@ -251,6 +288,7 @@ fn processing_worker(
                 (DRAIN_DURATION.as_secs_f64() / frame_duration_secs).floor() as usize;
             let frames_to_drain = frames_to_drain.min(frames_to_process.len());
             output_queue.extend(input_queue.drain(..frames_to_drain));
+            drained += frames_to_drain;
 
             let samples = match resample_16k_mono_s16_frames(frames_to_process.clone()) {
                 Ok(samples) => samples.into_iter().map(f32::from).collect::<Vec<_>>(),
@@ -259,28 +297,49 @@ fn processing_worker(
                     anyhow::bail!(err);
                 }
             };
-            let samples = samples.repeat(REPEAT_SAMPLE);
+            let samples = samples.repeat(repeat_sample);
 
             let data = classifier::Data::from_shape_vec((samples.len(),), samples)?;
             // Normalize data to [-1., 1.]
             let data = data / 32768.0;
 
-            let prediction = classifier.classify(&data)?.amplified(&AMPLIFICATION);
+            let prediction = classifier.classify(&data)?.amplified(&amplification.0);
             if let Some(smoothed) = smoother.push(&prediction)? {
-                let kind = match smoothed.argmax()? {
+                let detected = match smoothed.argmax()? {
                     0 => ContentKind::Advertisement,
                     1 => ContentKind::Music,
                     2 => ContentKind::Talk,
                     x => unreachable!("Unexpected label {x}"),
                 };
 
+                boundaries.extend(boundary_receiver.try_iter());
+                if boundaries.front().is_some_and(|frame| *frame < drained) {
+                    boundaries.retain(|frame| *frame >= drained);
+                    smoother.hint_boundary();
+                }
+
+                let kind = smoother.settle(
+                    detected,
+                    output_queue.iter().map(FrameDuration::duration).sum(),
+                );
+                let confidence = match kind {
+                    ContentKind::Advertisement => smoothed[0],
+                    ContentKind::Music => smoothed[1],
+                    ContentKind::Talk => smoothed[2],
+                    ContentKind::Unknown => 0.0,
+                };
+
                 let _elapsed = rate.stop();
 
                 // println!("{}ms", elapsed.as_millis() / output_queue.len() as u128);
 
                 #[allow(clippy::iter_with_drain)]
-                processed_sender
-                    .send(output_queue.drain(..).map(|frame| (kind, frame)).collect())?;
+                processed_sender.send(
+                    output_queue
+                        .drain(..)
+                        .map(|frame| (kind, confidence, frame))
+                        .collect(),
+                )?;
 
                 worker_stats_sender.send((rate.average(), smoother.get_buffer_content()))?;
             }
@@ -291,7 +350,7 @@ fn processing_worker(
     processed_sender.send(
         input_queue
             .into_iter()
-            .map(|frame| (ContentKind::Unknown, frame))
+            .map(|frame| (ContentKind::Unknown, 0.0, frame))
             .collect(),
     )?;
 
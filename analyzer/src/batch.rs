@@ -0,0 +1,134 @@
+use classifier::{Classify, Data, PredictedLabels};
+
+struct Request {
+    data: Data,
+    reply: flume::Sender<anyhow::Result<PredictedLabels>>,
+}
+
+/// Shares one classifier between many analyzers.
+///
+/// Requests are served by a single worker thread,
+/// all requests queued while the worker is busy go to the classifier as one batch.
+#[derive(Clone)]
+pub struct BatchClassifier {
+    sender: flume::Sender<Request>,
+}
+
+impl BatchClassifier {
+    #[must_use]
+    pub fn new(classifier: Box<dyn Classify>) -> Self {
+        let (sender, receiver) = flume::unbounded();
+        std::thread::spawn(move || batch_worker(classifier.as_ref(), &receiver));
+        Self { sender }
+    }
+}
+
+impl Classify for BatchClassifier {
+    fn classify(&self, data: &Data) -> anyhow::Result<PredictedLabels> {
+        let (reply, response) = flume::bounded(1);
+        self.sender.send(Request {
+            data: data.clone(),
+            reply,
+        })?;
+        response.recv()?
+    }
+}
+
+fn batch_worker(classifier: &dyn Classify, receiver: &flume::Receiver<Request>) {
+    while let Ok(request) = receiver.recv() {
+        let requests = std::iter::once(request)
+            .chain(receiver.try_iter())
+            .collect::<Vec<_>>();
+
+        let data = requests
+            .iter()
+            .map(|request| request.data.clone())
+            .collect::<Vec<_>>();
+
+        match classifier.classify_batch(&data) {
+            Ok(labels) => {
+                for (request, labels) in requests.into_iter().zip(labels) {
+                    _ = request.reply.send(Ok(labels));
+                }
+            }
+            Err(err) => {
+                log::error!("Batch classification failed: {err:#}");
+                for request in requests {
+                    _ = request.reply.send(Err(anyhow::anyhow!("{err:#}")));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Barrier, Mutex};
+
+    use super::*;
+
+    // Labels every input with its first value, records batch sizes.
+    struct FakeClassifier {
+        batches: Arc<Mutex<Vec<usize>>>,
+        entered: Arc<Barrier>,
+        release: Arc<Barrier>,
+    }
+
+    impl Classify for FakeClassifier {
+        fn classify(&self, data: &Data) -> anyhow::Result<PredictedLabels> {
+            Ok(PredictedLabels::from_elem((1, 3), data[0]))
+        }
+
+        fn classify_batch(&self, data: &[Data]) -> anyhow::Result<Vec<PredictedLabels>> {
+            // Hold the first batch until other requests are queued.
+            if self.batches.lock().unwrap().is_empty() {
+                self.entered.wait();
+                self.release.wait();
+            }
+            self.batches.lock().unwrap().push(data.len());
+            data.iter().map(|data| self.classify(data)).collect()
+        }
+    }
+
+    #[test]
+    fn test_batching() {
+        const CLIENTS: usize = 4;
+
+        let batches = Arc::new(Mutex::new(vec![]));
+        let entered = Arc::new(Barrier::new(2));
+        let release = Arc::new(Barrier::new(2));
+
+        let sut = BatchClassifier::new(Box::new(FakeClassifier {
+            batches: batches.clone(),
+            entered: entered.clone(),
+            release: release.clone(),
+        }));
+
+        let first = {
+            let sut = sut.clone();
+            std::thread::spawn(move || sut.classify(&Data::from_elem(1, 0.0)))
+        };
+
+        // The first request is in the classifier, others get queued.
+        entered.wait();
+
+        let clients = (1..=CLIENTS)
+            .map(|n| {
+                let sut = sut.clone();
+                std::thread::spawn(move || sut.classify(&Data::from_elem(1, n as f32)))
+            })
+            .collect::<Vec<_>>();
+
+        while sut.sender.len() < CLIENTS {
+            std::thread::yield_now();
+        }
+        release.wait();
+
+        assert_eq!(first.join().unwrap().unwrap()[[0, 0]] as usize, 0);
+        for (n, client) in clients.into_iter().enumerate() {
+            assert_eq!(client.join().unwrap().unwrap()[[0, 0]] as usize, n + 1);
+        }
+
+        assert_eq!(*batches.lock().unwrap(), vec![1, CLIENTS]);
+    }
+}
@@ -1,14 +1,73 @@
 // use std::io::Write;
-use std::time::Duration;
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
+use clap::{Parser, Subcommand};
 use enumflags2::BitFlags;
 use kdam::{tqdm, BarExt};
 use log::LevelFilter;
 
-use analyzer::{BufferedAnalyzer, ContentKind, LabelSmoother};
-use codec::Decoder;
+use analyzer::{
+    parse_labels, write_report, AnalyzerConfig, BufferedAnalyzer, ContentKind, Evaluation,
+    LabelSmoother, ReportFormat, Segment, SegmentCollector,
+};
+use codec::{Decoder, FrameDuration, StreamSelector};
+
+#[derive(Debug, Parser)]
+#[command(subcommand_negates_reqs = true)]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Without a command, the input is analyzed.
+    #[command(flatten)]
+    analyze: Option<AnalyzeArgs>,
+
+    #[command(flatten)]
+    config: AnalyzerConfig,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Detect content kinds in the audio file.
+    Analyze(AnalyzeArgs),
+    /// Compare detected content kinds with the ground truth.
+    Eval {
+        /// Audio file to analyze.
+        input: PathBuf,
+
+        /// Ground truth, Audacity labels or CSV with start,end,kind columns.
+        labels: PathBuf,
+    },
+}
+
+#[derive(Debug, clap::Args)]
+struct AnalyzeArgs {
+    /// Audio file to analyze.
+    input: PathBuf,
+
+    /// Write detected segments to the file.
+    #[arg(long)]
+    report: Option<PathBuf>,
+
+    /// Segment report format.
+    #[arg(long, value_enum, default_value_t = ReportFormat::Json)]
+    format: ReportFormat,
+
+    /// Start analysis at this position, in milliseconds.
+    #[arg(long, default_value_t = 0)]
+    start: u64,
+
+    /// Analyze this many milliseconds only.
+    #[arg(long)]
+    length: Option<u64>,
+}
 
 fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+
     stderrlog::new()
         .show_module_names(true)
         .show_level(true)
@@ -17,12 +76,61 @@ fn main() -> anyhow::Result<()> {
         .init()
         .unwrap();
 
-    let file = std::env::args().nth(1).expect("Expects path");
-    let input = std::fs::File::open(file).expect("Valid file path");
+    let command = args
+        .command
+        .or_else(|| args.analyze.map(Command::Analyze))
+        .ok_or_else(|| anyhow::anyhow!("Expected an audio file or a command, see --help"))?;
+
+    match command {
+        Command::Analyze(AnalyzeArgs {
+            input,
+            report,
+            format,
+            start,
+            length,
+        }) => {
+            let window = Window {
+                start: Duration::from_millis(start),
+                length: length.map(Duration::from_millis),
+            };
+            let segments = analyze(&input, &args.config, &window)?;
+
+            if let Some(path) = report {
+                write_report(&segments, format, std::fs::File::create(&path)?)?;
+                println!("Report: {} segments in {}", segments.len(), path.display());
+            }
+        }
+        Command::Eval { input, labels } => {
+            let truth = parse_labels(&std::fs::read_to_string(labels)?)?;
+            let segments = analyze(&input, &args.config, &Window::default())?;
+
+            println!("\n{}", Evaluation::new(&truth, &segments));
+        }
+    }
+
+    Ok(())
+}
+
+/// Part of the input to analyze.
+#[derive(Debug, Default)]
+struct Window {
+    start: Duration,
+    length: Option<Duration>,
+}
+
+fn analyze(input: &Path, config: &AnalyzerConfig, window: &Window) -> anyhow::Result<Vec<Segment>> {
+    let input = std::fs::File::open(input).expect("Valid file path");
+
+    let mut decoder = Decoder::try_from_seekable(input, StreamSelector::Best)?;
+    if !window.start.is_zero() {
+        decoder.seek(window.start)?;
+    }
 
-    let decoder = Decoder::try_from(input)?;
+    let classifier = config.load_classifier()?;
 
     let mut analyzer = BufferedAnalyzer::new(
+        classifier.into(),
+        config,
         LabelSmoother::new(Duration::from_millis(0), Duration::from_millis(1000)),
         BitFlags::empty(),
     );
@@ -46,9 +154,17 @@ fn main() -> anyhow::Result<()> {
     );
 
     let mut prev_kind = ContentKind::Unknown;
+    let mut segments = SegmentCollector::starting_at(window.start);
 
+    let mut pushed = Duration::ZERO;
     for frame in decoder {
-        analyzer.push(frame?)?;
+        if window.length.is_some_and(|length| pushed >= length) {
+            break;
+        }
+
+        let frame = frame?;
+        pushed += frame.duration();
+        analyzer.push(frame)?;
     }
 
     pb_frames.write("Decoded")?;
@@ -57,7 +173,9 @@ fn main() -> anyhow::Result<()> {
 
     pb_frames.write("Flushed")?;
 
-    for (kind, frame) in analyzer.pop()? {
+    for (kind, confidence, frame) in analyzer.pop_with_confidence()? {
+        segments.push(kind, confidence, frame.duration());
+
         pb_frames.update(1)?;
         if prev_kind != kind {
             pb_frames.write(format!("{:?} {kind}", frame.pts()))?;
@@ -76,5 +194,28 @@ fn main() -> anyhow::Result<()> {
         ((pb_ads.counter as f64) / (pb_frames.counter as f64) * 100.0).trunc() as u32
     );
 
-    Ok(())
+    Ok(segments.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_command() {
+        let args = Args::try_parse_from(["analyzer", "in.wav", "--start", "1000"]).unwrap();
+        assert!(args.command.is_none());
+        let analyze = args.analyze.unwrap();
+        assert_eq!(analyze.input, PathBuf::from("in.wav"));
+        assert_eq!(analyze.start, 1_000);
+
+        let args = Args::try_parse_from(["analyzer", "--model", "ao", "eval", "in.wav", "in.txt"])
+            .unwrap();
+        assert!(matches!(args.command, Some(Command::Eval { .. })));
+        assert!(args.analyze.is_none());
+
+        let args =
+            Args::try_parse_from(["analyzer", "analyze", "in.wav", "--model", "ao"]).unwrap();
+        assert!(matches!(args.command, Some(Command::Analyze(_))));
+    }
 }
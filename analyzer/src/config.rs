@@ -0,0 +1,191 @@
+use std::{
+    fmt::Display,
+    hash::{Hash, Hasher},
+    path::PathBuf,
+    str::FromStr,
+};
+
+use clap::builder::RangedU64ValueParser;
+
+use classifier::{Classify, ClassifyModel};
+
+pub const DEFAULT_MODEL: ClassifyModel = ClassifyModel::AMT;
+pub const DEFAULT_MODELS_DIR: &str = "./models";
+pub const DEFAULT_REPEAT_SAMPLE: usize = 4;
+pub const MAX_REPEAT_SAMPLE: usize = 16;
+
+/// Classification settings.
+///
+/// `model` and `models_dir` select the classifier, the rest tunes how the analyzer feeds and reads it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, clap::Args)]
+pub struct AnalyzerConfig {
+    /// Classification model: AMT, MOAT or AO.
+    #[arg(long, global = true, default_value_t = DEFAULT_MODEL)]
+    pub model: ClassifyModel,
+
+    /// Directory with `yamnet` and `adbanda_*` models.
+    #[arg(long, global = true, default_value = DEFAULT_MODELS_DIR)]
+    pub models_dir: PathBuf,
+
+    /// Per-class coefficients applied to predictions: advertisement,music,talk.
+    #[arg(long, global = true, default_value_t = Amplification::default())]
+    pub amplification: Amplification,
+
+    /// Duplicate samples N times to increase prediction accuracy.
+    #[arg(long, global = true, default_value_t = DEFAULT_REPEAT_SAMPLE)]
+    #[arg(value_parser = RangedU64ValueParser::<usize>::new().range(1..=MAX_REPEAT_SAMPLE as u64))]
+    pub repeat_sample: usize,
+}
+
+impl Default for AnalyzerConfig {
+    fn default() -> Self {
+        Self {
+            model: DEFAULT_MODEL,
+            models_dir: PathBuf::from(DEFAULT_MODELS_DIR),
+            amplification: Amplification::default(),
+            repeat_sample: DEFAULT_REPEAT_SAMPLE,
+        }
+    }
+}
+
+impl AnalyzerConfig {
+    pub fn load_classifier(&self) -> anyhow::Result<Box<dyn Classify>> {
+        log::info!(
+            "Loading classifier {} from {}",
+            self.model,
+            self.models_dir.display()
+        );
+        classifier::create(&self.models_dir, self.model)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Amplification(pub [f32; 3]);
+
+impl Default for Amplification {
+    fn default() -> Self {
+        Self([1., 5., 5.])
+    }
+}
+
+// Compared bitwise, like they are hashed, parsing turns `-0` into `0`.
+impl PartialEq for Amplification {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.map(f32::to_bits) == other.0.map(f32::to_bits)
+    }
+}
+
+impl Eq for Amplification {}
+
+impl Hash for Amplification {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.map(f32::to_bits).hash(state);
+    }
+}
+
+impl FromStr for Amplification {
+    type Err = anyhow::Error;
+
+    /// Parses comma separated coefficients, e.g. `1,5,5`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values = s
+            .split(',')
+            .map(|v| v.trim().parse::<f32>())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let coefficients: [f32; 3] = values
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("Expected 3 coefficients, got {s}"))?;
+
+        anyhow::ensure!(
+            coefficients.iter().all(|c| c.is_finite() && *c >= 0.0),
+            "Coefficients must be non-negative numbers, got {s}"
+        );
+        anyhow::ensure!(
+            coefficients.iter().sum::<f32>() > 0.0,
+            "At least one coefficient must be positive"
+        );
+
+        Ok(Self(coefficients.map(|c| c + 0.0)))
+    }
+}
+
+impl Display for Amplification {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let [ads, music, talk] = self.0;
+        write!(f, "{ads},{music},{talk}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_amplification() {
+        assert_eq!(
+            "1, 5,5".parse::<Amplification>().unwrap(),
+            Amplification::default()
+        );
+        assert_eq!(
+            Amplification::default()
+                .to_string()
+                .parse::<Amplification>()
+                .unwrap(),
+            Amplification::default()
+        );
+        assert!("1,5".parse::<Amplification>().is_err());
+        assert!("1,5,5,5".parse::<Amplification>().is_err());
+        assert!("1,-5,5".parse::<Amplification>().is_err());
+        assert!("0,0,0".parse::<Amplification>().is_err());
+        assert!("a,b,c".parse::<Amplification>().is_err());
+    }
+
+    #[test]
+    fn test_negative_zero_amplification() {
+        use std::hash::BuildHasher;
+
+        let negative = "-0,1,1".parse::<Amplification>().unwrap();
+        let positive = "0,1,1".parse::<Amplification>().unwrap();
+        assert_eq!(negative, positive);
+
+        let hasher = std::collections::hash_map::RandomState::new();
+        assert_eq!(hasher.hash_one(negative), hasher.hash_one(positive));
+    }
+
+    #[derive(Debug, clap::Parser)]
+    struct Cli {
+        #[command(subcommand)]
+        command: Command,
+
+        #[command(flatten)]
+        config: AnalyzerConfig,
+    }
+
+    #[derive(Debug, clap::Subcommand)]
+    enum Command {
+        Eval { input: PathBuf },
+    }
+
+    #[test]
+    fn test_options_after_subcommand() {
+        use clap::Parser;
+
+        let cli = Cli::try_parse_from([
+            "analyzer",
+            "eval",
+            "in.wav",
+            "--model",
+            "moat",
+            "--amplification",
+            "1,2,3",
+        ])
+        .unwrap();
+        assert_eq!(cli.config.model, ClassifyModel::MOAT);
+        assert_eq!(cli.config.amplification, Amplification([1., 2., 3.]));
+
+        let cli = Cli::try_parse_from(["analyzer", "--model", "ao", "eval", "in.wav"]).unwrap();
+        assert_eq!(cli.config.model, ClassifyModel::AO);
+        assert_eq!(cli.config.repeat_sample, DEFAULT_REPEAT_SAMPLE);
+    }
+}
@@ -0,0 +1,351 @@
+use std::{fmt::Display, time::Duration};
+
+use crate::{ContentKind, Segment};
+
+// Segments are compared on this time grid.
+const RESOLUTION: Duration = Duration::from_millis(10);
+
+const KINDS: [ContentKind; 4] = [
+    ContentKind::Advertisement,
+    ContentKind::Music,
+    ContentKind::Talk,
+    ContentKind::Unknown,
+];
+
+/// Parses ground truth labels, either Audacity label track or CSV with `start,end,kind` columns.
+///
+/// Times are in seconds, the label is a content kind name, anything after it is ignored.
+pub fn parse_labels(text: &str) -> anyhow::Result<Vec<Segment>> {
+    let mut segments = vec![];
+
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        // Audacity puts frequency range of spectral labels on a separate line.
+        if line.is_empty() || line.starts_with('\\') {
+            continue;
+        }
+
+        let fields = if line.contains('\t') {
+            line.split('\t').collect::<Vec<_>>()
+        } else {
+            line.split(',').collect::<Vec<_>>()
+        };
+
+        let [start, end, label, ..] = fields.as_slice() else {
+            anyhow::bail!("Line {}: expected start, end and label", number + 1);
+        };
+
+        let Ok(start) = start.trim().parse::<f64>() else {
+            // CSV header.
+            if number == 0 {
+                continue;
+            }
+            anyhow::bail!("Line {}: invalid start {start}", number + 1);
+        };
+
+        let end = end
+            .trim()
+            .parse::<f64>()
+            .map_err(|err| anyhow::anyhow!("Line {}: invalid end {end}, {err}", number + 1))?;
+
+        anyhow::ensure!(
+            start.is_finite() && end.is_finite() && 0.0 <= start && start <= end,
+            "Line {}: invalid range {start}..{end}",
+            number + 1
+        );
+
+        let kind = parse_kind(label.split_whitespace().next().unwrap_or_default())
+            .map_err(|err| anyhow::anyhow!("Line {}: {err}", number + 1))?;
+
+        segments.push(Segment {
+            start: Duration::from_secs_f64(start),
+            end: Duration::from_secs_f64(end),
+            kind,
+            confidence: 1.0,
+        });
+    }
+
+    segments.sort_by_key(|segment| segment.start);
+
+    Ok(segments)
+}
+
+fn parse_kind(label: &str) -> anyhow::Result<ContentKind> {
+    match label.to_ascii_lowercase().as_str() {
+        "advertisement" | "ad" | "ads" => Ok(ContentKind::Advertisement),
+        "music" => Ok(ContentKind::Music),
+        "talk" => Ok(ContentKind::Talk),
+        _ => anyhow::bail!("Unknown label {label}, expected Advertisement, Music or Talk"),
+    }
+}
+
+/// Detected segments compared to the ground truth.
+///
+/// Only the time covered by the ground truth is evaluated.
+#[derive(Debug)]
+pub struct Evaluation {
+    /// Time steps by ground truth kind (rows) and detected kind (columns), in `KINDS` order.
+    confusion: [[usize; 4]; 4],
+    truth_boundaries: usize,
+    detected_boundaries: usize,
+    /// Distance from each ground truth boundary to the nearest detected one.
+    boundary_errors: Vec<Duration>,
+}
+
+impl Evaluation {
+    #[must_use]
+    pub fn new(truth: &[Segment], detected: &[Segment]) -> Self {
+        let mut confusion = [[0; 4]; 4];
+
+        let end = truth.iter().map(|s| s.end).max().unwrap_or_default();
+        let mut position = RESOLUTION / 2;
+        while position < end {
+            if let Some(expected) = kind_at(truth, position) {
+                let actual = kind_at(detected, position).unwrap_or(ContentKind::Unknown);
+                confusion[index(expected)][index(actual)] += 1;
+            }
+            position += RESOLUTION;
+        }
+
+        let truth_boundaries = boundaries(truth);
+        let detected_boundaries = boundaries(detected);
+
+        let boundary_errors = truth_boundaries
+            .iter()
+            .filter_map(|expected| {
+                detected_boundaries
+                    .iter()
+                    .map(|actual| {
+                        actual
+                            .saturating_sub(*expected)
+                            .max(expected.saturating_sub(*actual))
+                    })
+                    .min()
+            })
+            .collect();
+
+        Self {
+            confusion,
+            truth_boundaries: truth_boundaries.len(),
+            detected_boundaries: detected_boundaries.len(),
+            boundary_errors,
+        }
+    }
+
+    #[must_use]
+    pub fn precision(&self, kind: ContentKind) -> f64 {
+        let column = index(kind);
+        ratio(
+            self.confusion[column][column],
+            self.confusion.iter().map(|row| row[column]).sum(),
+        )
+    }
+
+    #[must_use]
+    pub fn recall(&self, kind: ContentKind) -> f64 {
+        let row = index(kind);
+        ratio(self.confusion[row][row], self.confusion[row].iter().sum())
+    }
+
+    /// Share of the evaluated time with wrong kind detected.
+    #[must_use]
+    pub fn mislabelled(&self) -> f64 {
+        let total = self.confusion.iter().flatten().sum::<usize>();
+        let correct = (0..KINDS.len())
+            .map(|i| self.confusion[i][i])
+            .sum::<usize>();
+        ratio(total - correct, total)
+    }
+
+    #[must_use]
+    pub fn mean_boundary_error(&self) -> Option<Duration> {
+        let count = u32::try_from(self.boundary_errors.len()).ok()?;
+        (count > 0).then(|| self.boundary_errors.iter().sum::<Duration>() / count)
+    }
+
+    #[must_use]
+    pub fn max_boundary_error(&self) -> Option<Duration> {
+        self.boundary_errors.iter().max().copied()
+    }
+
+    fn duration(steps: usize) -> Duration {
+        RESOLUTION * u32::try_from(steps).unwrap_or(u32::MAX)
+    }
+}
+
+impl Display for Evaluation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let total = self.confusion.iter().flatten().sum::<usize>();
+
+        writeln!(
+            f,
+            "Evaluated {:.1}s, mislabelled {:.1}%",
+            Self::duration(total).as_secs_f64(),
+            self.mislabelled() * 100.0
+        )?;
+
+        writeln!(f, "\n{:<14} {:>9} {:>9}", "Kind", "Precision", "Recall")?;
+        for kind in &KINDS[..3] {
+            writeln!(
+                f,
+                "{:<14} {:>9.3} {:>9.3}",
+                kind.name(),
+                self.precision(*kind),
+                self.recall(*kind)
+            )?;
+        }
+
+        writeln!(
+            f,
+            "\nConfusion matrix, seconds, truth by rows, detected by columns"
+        )?;
+        write!(f, "{:<14}", "")?;
+        for kind in KINDS {
+            write!(f, " {:>9}", kind.name().chars().take(9).collect::<String>())?;
+        }
+        writeln!(f)?;
+        for (kind, row) in KINDS.iter().zip(self.confusion).take(3) {
+            write!(f, "{:<14}", kind.name())?;
+            for steps in row {
+                write!(f, " {:>9.1}", Self::duration(steps).as_secs_f64())?;
+            }
+            writeln!(f)?;
+        }
+
+        write!(
+            f,
+            "\nBoundaries: {} in truth, {} detected",
+            self.truth_boundaries, self.detected_boundaries
+        )?;
+        if let (Some(mean), Some(max)) = (self.mean_boundary_error(), self.max_boundary_error()) {
+            write!(
+                f,
+                ", error mean {}ms, max {}ms",
+                mean.as_millis(),
+                max.as_millis()
+            )?;
+        }
+        writeln!(f)
+    }
+}
+
+fn index(kind: ContentKind) -> usize {
+    KINDS.iter().position(|k| *k == kind).expect("Known kind")
+}
+
+fn ratio(value: usize, total: usize) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        value as f64 / total as f64
+    }
+}
+
+fn kind_at(segments: &[Segment], position: Duration) -> Option<ContentKind> {
+    // Segments are sorted by start.
+    let next = segments.partition_point(|segment| segment.start <= position);
+    segments[..next]
+        .iter()
+        .rev()
+        .find(|segment| position < segment.end)
+        .map(|segment| segment.kind)
+}
+
+// Points where the kind changes between adjacent segments.
+fn boundaries(segments: &[Segment]) -> Vec<Duration> {
+    segments
+        .windows(2)
+        .filter(|pair| pair[0].kind != pair[1].kind)
+        .map(|pair| pair[1].start)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(start: u64, end: u64, kind: ContentKind) -> Segment {
+        Segment {
+            start: Duration::from_secs(start),
+            end: Duration::from_secs(end),
+            kind,
+            confidence: 1.0,
+        }
+    }
+
+    #[test]
+    fn test_parse_audacity() {
+        let labels = "10.5\t20\tAdvertisement 0.812\n\
+                      \\\t100.0\t2000.0\n\
+                      0.000000\t10.500000\tmusic\n";
+
+        assert_eq!(
+            parse_labels(labels).unwrap(),
+            vec![
+                Segment {
+                    start: Duration::ZERO,
+                    end: Duration::from_millis(10_500),
+                    kind: ContentKind::Music,
+                    confidence: 1.0
+                },
+                Segment {
+                    start: Duration::from_millis(10_500),
+                    end: Duration::from_secs(20),
+                    kind: ContentKind::Advertisement,
+                    confidence: 1.0
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_csv() {
+        let labels = "start,end,kind,confidence\n0,10,Talk,0.5\n10,20,ad\n";
+
+        assert_eq!(
+            parse_labels(labels).unwrap(),
+            vec![
+                segment(0, 10, ContentKind::Talk),
+                segment(10, 20, ContentKind::Advertisement)
+            ]
+        );
+
+        assert!(parse_labels("0,10,Jingle").is_err());
+        assert!(parse_labels("0,10").is_err());
+        assert!(parse_labels("10,0,Talk").is_err());
+        assert!(parse_labels("0\tinf\tMusic").is_err());
+        assert!(parse_labels("0,10,Talk\nx,20,Talk").is_err());
+    }
+
+    #[test]
+    fn test_evaluation() {
+        let truth = [
+            segment(0, 10, ContentKind::Music),
+            segment(10, 20, ContentKind::Advertisement),
+            segment(20, 30, ContentKind::Music),
+        ];
+        let detected = [
+            segment(0, 12, ContentKind::Music),
+            segment(12, 20, ContentKind::Advertisement),
+            segment(20, 29, ContentKind::Music),
+            segment(29, 30, ContentKind::Unknown),
+        ];
+
+        let sut = Evaluation::new(&truth, &detected);
+
+        assert!((sut.precision(ContentKind::Advertisement) - 1.0).abs() < 1e-9);
+        assert!((sut.recall(ContentKind::Advertisement) - 0.8).abs() < 1e-9);
+        assert!((sut.precision(ContentKind::Music) - 19.0 / 21.0).abs() < 1e-9);
+        assert!((sut.recall(ContentKind::Music) - 0.95).abs() < 1e-9);
+        assert!((sut.mislabelled() - 0.1).abs() < 1e-9);
+
+        assert_eq!(sut.truth_boundaries, 2);
+        assert_eq!(sut.detected_boundaries, 3);
+        assert_eq!(sut.mean_boundary_error(), Some(Duration::from_secs(1)));
+        assert_eq!(sut.max_boundary_error(), Some(Duration::from_secs(2)));
+
+        let report = sut.to_string();
+        assert!(report.contains("Evaluated 30.0s, mislabelled 10.0%"));
+        assert!(report.contains("error mean 1000ms, max 2000ms"));
+    }
+}
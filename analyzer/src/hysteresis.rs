@@ -0,0 +1,156 @@
+use std::time::Duration;
+
+use crate::ContentKind;
+
+/// How long a kind must persist before the output switches to it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MinDurations {
+    pub advertisement: Duration,
+    pub music: Duration,
+    pub talk: Duration,
+}
+
+impl MinDurations {
+    const fn get(&self, kind: ContentKind) -> Duration {
+        match kind {
+            ContentKind::Advertisement => self.advertisement,
+            ContentKind::Music => self.music,
+            ContentKind::Talk => self.talk,
+            ContentKind::Unknown => Duration::ZERO,
+        }
+    }
+}
+
+/// Suppresses short segments: ATA -> AAA, MMMMAAMM -> MMMMMMMM.
+///
+/// A new kind is accepted once it lasts for its minimal duration,
+/// until then the previous kind is kept.
+#[derive(Debug, Default)]
+pub struct Hysteresis {
+    min_durations: MinDurations,
+    current: Option<ContentKind>,
+    candidate: Option<(ContentKind, Duration)>,
+    // A change is accepted right away while the window lasts.
+    boundary_window: Duration,
+}
+
+impl Hysteresis {
+    #[must_use]
+    pub fn new(min_durations: MinDurations) -> Self {
+        Self {
+            min_durations,
+            ..Self::default()
+        }
+    }
+
+    /// Hints that a boundary is likely around here, e.g. the stream title has changed.
+    ///
+    /// The next change within the longest minimal duration is accepted without waiting.
+    pub fn hint_boundary(&mut self) {
+        self.boundary_window = self
+            .min_durations
+            .advertisement
+            .max(self.min_durations.music)
+            .max(self.min_durations.talk);
+    }
+
+    /// Takes detected kind of the next `duration` of audio, returns the kind to output.
+    pub fn push(&mut self, kind: ContentKind, duration: Duration) -> ContentKind {
+        let boundary = !self.boundary_window.is_zero();
+        self.boundary_window = self.boundary_window.saturating_sub(duration);
+
+        let Some(current) = self.current else {
+            // Nothing to hold on to yet.
+            self.current = Some(kind);
+            return kind;
+        };
+
+        if kind == current {
+            self.candidate = None;
+            return current;
+        }
+
+        let pending = match self.candidate {
+            Some((candidate, pending)) if candidate == kind => pending + duration,
+            _ => duration,
+        };
+
+        if boundary || pending >= self.min_durations.get(kind) {
+            self.current = Some(kind);
+            self.candidate = None;
+            self.boundary_window = Duration::ZERO;
+            kind
+        } else {
+            self.candidate = Some((kind, pending));
+            current
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STEP: Duration = Duration::from_millis(200);
+
+    // `|` in the input hints a boundary.
+    fn run(min_durations: MinDurations, input: &str) -> String {
+        let mut sut = Hysteresis::new(min_durations);
+        input
+            .chars()
+            .filter(|c| {
+                if *c == '|' {
+                    sut.hint_boundary();
+                }
+                *c != '|'
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|c| {
+                let kind = match c {
+                    'A' => ContentKind::Advertisement,
+                    'M' => ContentKind::Music,
+                    'T' => ContentKind::Talk,
+                    _ => ContentKind::Unknown,
+                };
+                sut.push(kind, STEP).name().chars().next().unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_disabled() {
+        assert_eq!(run(MinDurations::default(), "MMAMTTA"), "MMAMTTA");
+    }
+
+    #[test]
+    fn test_short_segments() {
+        let min_durations = MinDurations {
+            advertisement: STEP * 3,
+            music: STEP * 2,
+            talk: STEP * 2,
+        };
+
+        assert_eq!(run(min_durations, "ATA"), "AAA");
+        assert_eq!(run(min_durations, "MMMMAAMM"), "MMMMMMMM");
+        // Ad is accepted after 3 steps, ad block ends after 2 steps of music.
+        assert_eq!(run(min_durations, "MMAAAAAMAMMM"), "MMMMAAAAAAMM");
+        // Interrupted candidate starts over.
+        assert_eq!(run(min_durations, "MAATAAAM"), "MMMMMMAA");
+    }
+
+    #[test]
+    fn test_boundary_hint() {
+        let min_durations = MinDurations {
+            advertisement: STEP * 3,
+            music: STEP * 2,
+            talk: STEP * 2,
+        };
+
+        assert_eq!(run(min_durations, "MM|AAAM"), "MMAAAA");
+        // The hint expires after the longest minimal duration.
+        assert_eq!(run(min_durations, "MM|MMMAAAA"), "MMMMMMMAA");
+        // Only the first change is accepted early.
+        assert_eq!(run(min_durations, "MM|ATAA"), "MMAAAA");
+    }
+}
@@ -4,12 +4,23 @@ use enumflags2::bitflags;
 
 mod amplify;
 mod analyzer;
+mod batch;
+mod config;
 mod content_kind;
+mod eval;
+mod hysteresis;
 mod rate;
+mod report;
 mod smooth;
 
 pub use analyzer::BufferedAnalyzer;
+pub use batch::BatchClassifier;
+pub use classifier::ClassifyModel;
+pub use config::{Amplification, AnalyzerConfig, MAX_REPEAT_SAMPLE};
 pub use content_kind::ContentKind;
+pub use eval::{parse_labels, Evaluation};
+pub use hysteresis::{Hysteresis, MinDurations};
+pub use report::{write_report, ReportFormat, Segment, SegmentCollector};
 pub use smooth::LabelSmoother;
 
 #[bitflags]
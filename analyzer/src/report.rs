@@ -0,0 +1,194 @@
+use std::{io::Write, time::Duration};
+
+use crate::ContentKind;
+
+/// Continuous run of frames of the same kind.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+    pub start: Duration,
+    pub end: Duration,
+    pub kind: ContentKind,
+    /// Mean smoothed confidence over the segment frames.
+    pub confidence: f32,
+}
+
+/// Merges classified frames into segments.
+#[derive(Debug, Default)]
+pub struct SegmentCollector {
+    segments: Vec<Segment>,
+    position: Duration,
+    confidence_sum: f64,
+    frames: usize,
+}
+
+impl SegmentCollector {
+    /// Collector of frames which start at the position, e.g. after a seek.
+    #[must_use]
+    pub fn starting_at(position: Duration) -> Self {
+        Self {
+            position,
+            ..Self::default()
+        }
+    }
+
+    pub fn push(&mut self, kind: ContentKind, confidence: f32, duration: Duration) {
+        let start = self.position;
+        self.position += duration;
+
+        match self.segments.last_mut() {
+            Some(last) if last.kind == kind => {
+                last.end = self.position;
+                self.confidence_sum += f64::from(confidence);
+                self.frames += 1;
+                last.confidence = (self.confidence_sum / self.frames as f64) as f32;
+            }
+            _ => {
+                self.segments.push(Segment {
+                    start,
+                    end: self.position,
+                    kind,
+                    confidence,
+                });
+                self.confidence_sum = f64::from(confidence);
+                self.frames = 1;
+            }
+        }
+    }
+
+    #[must_use]
+    pub fn finish(self) -> Vec<Segment> {
+        self.segments
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ReportFormat {
+    Json,
+    Csv,
+    /// Audacity label track, import via File > Import > Labels.
+    Audacity,
+}
+
+pub fn write_report<W: Write>(
+    segments: &[Segment],
+    format: ReportFormat,
+    mut output: W,
+) -> std::io::Result<()> {
+    match format {
+        ReportFormat::Json => {
+            writeln!(output, "[")?;
+            for (index, segment) in segments.iter().enumerate() {
+                let separator = if index + 1 < segments.len() { "," } else { "" };
+                writeln!(
+                    output,
+                    r#"  {{"start": {:.3}, "end": {:.3}, "kind": "{:#}", "confidence": {:.3}}}{separator}"#,
+                    segment.start.as_secs_f64(),
+                    segment.end.as_secs_f64(),
+                    segment.kind,
+                    segment.confidence,
+                )?;
+            }
+            writeln!(output, "]")?;
+        }
+        ReportFormat::Csv => {
+            writeln!(output, "start,end,kind,confidence")?;
+            for segment in segments {
+                writeln!(
+                    output,
+                    "{:.3},{:.3},{:#},{:.3}",
+                    segment.start.as_secs_f64(),
+                    segment.end.as_secs_f64(),
+                    segment.kind,
+                    segment.confidence,
+                )?;
+            }
+        }
+        ReportFormat::Audacity => {
+            for segment in segments {
+                writeln!(
+                    output,
+                    "{:.6}\t{:.6}\t{:#} {:.3}",
+                    segment.start.as_secs_f64(),
+                    segment.end.as_secs_f64(),
+                    segment.kind,
+                    segment.confidence,
+                )?;
+            }
+        }
+    }
+
+    output.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME: Duration = Duration::from_millis(500);
+
+    fn segments() -> Vec<Segment> {
+        let mut sut = SegmentCollector::default();
+        sut.push(ContentKind::Music, 0.5, FRAME);
+        sut.push(ContentKind::Music, 1.0, FRAME);
+        sut.push(ContentKind::Advertisement, 0.25, FRAME);
+        sut.finish()
+    }
+
+    fn report(format: ReportFormat) -> String {
+        let mut output = vec![];
+        write_report(&segments(), format, &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_collect_segments() {
+        assert_eq!(
+            segments(),
+            vec![
+                Segment {
+                    start: Duration::ZERO,
+                    end: Duration::from_secs(1),
+                    kind: ContentKind::Music,
+                    confidence: 0.75
+                },
+                Segment {
+                    start: Duration::from_secs(1),
+                    end: Duration::from_millis(1_500),
+                    kind: ContentKind::Advertisement,
+                    confidence: 0.25
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_json() {
+        assert_eq!(
+            report(ReportFormat::Json),
+            r#"[
+  {"start": 0.000, "end": 1.000, "kind": "Music", "confidence": 0.750},
+  {"start": 1.000, "end": 1.500, "kind": "Advertisement", "confidence": 0.250}
+]
+"#
+        );
+    }
+
+    #[test]
+    fn test_csv() {
+        assert_eq!(
+            report(ReportFormat::Csv),
+            "start,end,kind,confidence\n\
+             0.000,1.000,Music,0.750\n\
+             1.000,1.500,Advertisement,0.250\n"
+        );
+    }
+
+    #[test]
+    fn test_audacity() {
+        assert_eq!(
+            report(ReportFormat::Audacity),
+            "0.000000\t1.000000\tMusic 0.750\n\
+             1.000000\t1.500000\tAdvertisement 0.250\n"
+        );
+    }
+}
@@ -5,7 +5,7 @@ use log::info;
 use ndarray::{array, Array1, Array2, Axis, Slice};
 use ndarray_stats::{DeviationExt, QuantileExt};
 
-use crate::analyzer::DRAIN_DURATION;
+use crate::{analyzer::DRAIN_DURATION, ContentKind, Hysteresis, MinDurations};
 
 #[allow(dead_code)]
 pub struct LabelSmoother {
@@ -16,6 +16,7 @@ pub struct LabelSmoother {
     music_label: PredictedLabels,
     talk_label: PredictedLabels,
     last: PredictedLabels,
+    hysteresis: Hysteresis,
 }
 
 impl LabelSmoother {
@@ -40,9 +41,36 @@ impl LabelSmoother {
             music_label: PredictedLabels::from_shape_vec((1, 3), vec![0.0, 1.0, 0.0]).unwrap(),
             talk_label: PredictedLabels::from_shape_vec((1, 3), vec![0.0, 0.0, 1.0]).unwrap(),
             last: PredictedLabels::from_shape_vec((1, 3), vec![0.0, 1.0, 0.0]).unwrap(),
+            hysteresis: Hysteresis::default(),
         }
     }
 
+    /// Enables the second stage, which drops segments shorter than given durations.
+    #[must_use]
+    pub fn with_min_durations(self, min_durations: MinDurations) -> Self {
+        info!(
+            "SMOOTHER min ads={}ms music={}ms talk={}ms",
+            min_durations.advertisement.as_millis(),
+            min_durations.music.as_millis(),
+            min_durations.talk.as_millis()
+        );
+
+        Self {
+            hysteresis: Hysteresis::new(min_durations),
+            ..self
+        }
+    }
+
+    /// Second stage, takes the kind detected for the next `duration` of audio and returns the accepted one.
+    pub fn settle(&mut self, kind: ContentKind, duration: Duration) -> ContentKind {
+        self.hysteresis.push(kind, duration)
+    }
+
+    /// See `Hysteresis::hint_boundary`.
+    pub fn hint_boundary(&mut self) {
+        self.hysteresis.hint_boundary();
+    }
+
     #[must_use]
     pub fn get_buffer_content(&self) -> String {
         self.buffer
@@ -73,8 +101,7 @@ impl LabelSmoother {
             return Ok(None);
         }
 
-        // TODO How to eliminate short segments?
-        // Second buffer on confidence? ATA -> A, MMMMAAMM -> MMMMMMMMMMM
+        // Short segments are eliminated later, see `settle`.
         Ok(Some(confidence))
     }
 
@@ -1,4 +1,4 @@
-use std::path::Path;
+use std::{fmt::Display, path::Path, str::FromStr};
 
 use tensorflow::Tensor;
 
@@ -10,15 +10,43 @@ use tfmodel::TfModel;
 
 pub trait Classify: Send + Sync {
     fn classify(&self, data: &Data) -> anyhow::Result<PredictedLabels>;
+
+    /// Classifies several inputs at once.
+    /// Implementations run the batch through the models in as few session calls as possible.
+    fn classify_batch(&self, data: &[Data]) -> anyhow::Result<Vec<PredictedLabels>> {
+        data.iter().map(|data| self.classify(data)).collect()
+    }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum ClassifyModel {
+    /// Advertisement, music and talk.
     AMT,
+    /// Music vs other, then advertisement vs talk.
     MOAT,
+    /// Advertisement vs other.
     AO,
 }
 
+impl FromStr for ClassifyModel {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "AMT" => Ok(Self::AMT),
+            "MOAT" => Ok(Self::MOAT),
+            "AO" => Ok(Self::AO),
+            _ => anyhow::bail!("Unknown model {s}, expected one of AMT, MOAT, AO"),
+        }
+    }
+}
+
+impl Display for ClassifyModel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self, f)
+    }
+}
+
 pub fn create<P: AsRef<Path>>(dir: P, model: ClassifyModel) -> anyhow::Result<Box<dyn Classify>> {
     match model {
         ClassifyModel::AMT => Ok(Box::new(AmtClassifier::load(dir)?)),
@@ -48,12 +76,30 @@ impl Classify for AmtClassifier {
     fn classify(&self, data: &Data) -> anyhow::Result<PredictedLabels> {
         let embedding = self.yamnet.run(&Tensor::from(data))?;
         let prediction = self.adbanda.run(&embedding)?;
-        let dims = prediction.dims();
-        Ok(PredictedLabels::from_shape_vec(
-            (dims[0] as usize, dims[1] as usize),
-            prediction.to_vec(),
-        )?)
+        to_labels(&prediction)
     }
+
+    fn classify_batch(&self, data: &[Data]) -> anyhow::Result<Vec<PredictedLabels>> {
+        // YAMNet takes a single waveform, but embeddings of all inputs go through adbanda at once.
+        let embeddings = data
+            .iter()
+            .map(|data| self.yamnet.run(&Tensor::from(data)))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        self.adbanda
+            .run_batch(&embeddings)?
+            .iter()
+            .map(to_labels)
+            .collect()
+    }
+}
+
+fn to_labels(prediction: &Tensor<f32>) -> anyhow::Result<PredictedLabels> {
+    let dims = prediction.dims();
+    Ok(PredictedLabels::from_shape_vec(
+        (dims[0] as usize, dims[1] as usize),
+        prediction.to_vec(),
+    )?)
 }
 
 struct AoClassifier {
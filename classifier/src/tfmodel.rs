@@ -62,4 +62,47 @@ impl TfModel {
         let output = args.fetch(token_output)?;
         Ok(output)
     }
+
+    /// Runs inputs of shape `[rows, ...]` in one session call.
+    /// Inputs are stacked by rows, the output is split back by the same rows.
+    pub fn run_batch(&self, inputs: &[Tensor<f32>]) -> anyhow::Result<Vec<Tensor<f32>>> {
+        let Some(first) = inputs.first() else {
+            return Ok(vec![]);
+        };
+
+        let row_dims = &first.dims()[1..];
+        anyhow::ensure!(
+            inputs.iter().all(|input| &input.dims()[1..] == row_dims),
+            "Batch inputs have different shapes"
+        );
+
+        let rows = inputs
+            .iter()
+            .map(|input| input.dims()[0])
+            .collect::<Vec<_>>();
+
+        let batch = {
+            let dims = [&[rows.iter().sum::<u64>()][..], row_dims].concat();
+            let values = inputs
+                .iter()
+                .flat_map(|input| input.iter().copied())
+                .collect::<Vec<_>>();
+            Tensor::new(&dims).with_values(&values)?
+        };
+
+        let output = self.run(&batch)?;
+        let output_row_dims = &output.dims()[1..];
+        let row_len = output_row_dims.iter().product::<u64>() as usize;
+
+        let mut offset = 0;
+        rows.into_iter()
+            .map(|rows| {
+                let len = rows as usize * row_len;
+                let tensor = Tensor::new(&[&[rows][..], output_row_dims].concat())
+                    .with_values(&output[offset..offset + len])?;
+                offset += len;
+                Ok(tensor)
+            })
+            .collect()
+    }
 }
@@ -1,37 +1,99 @@
-use std::io::Read;
+use std::io::{Read, Seek};
+use std::time::Duration;
 
 use ac_ffmpeg::codec::audio::{AudioDecoder, AudioFrame};
 use ac_ffmpeg::codec::{AudioCodecParameters, Decoder as AcDecoder};
-use ac_ffmpeg::format::demuxer::{Demuxer, DemuxerWithStreamInfo};
+use ac_ffmpeg::format::demuxer::{Demuxer, DemuxerWithStreamInfo, SeekTarget};
 use ac_ffmpeg::format::io::IO;
+use ac_ffmpeg::time::Timestamp;
 
-use crate::{CodecParams, ResamplingDecoder};
+use crate::frame_ext::trim_start;
+use crate::{CodecParams, FrameDuration, ResamplingDecoder, StreamInfo, StreamSelector};
 
 #[non_exhaustive]
 pub struct Decoder<T> {
     demuxer: DemuxerWithStreamInfo<T>,
-    decoder: AudioDecoder,
+    codec: AudioDecoder,
+    stream_index: usize,
+    // Frames before the position are dropped after a seek.
+    seek_position: Option<Duration>,
 }
 
 impl<R: Read> Decoder<R> {
     pub fn try_from(input: R) -> anyhow::Result<Self> {
-        let io = IO::from_read_stream(input);
+        Self::with_stream(input, StreamSelector::Best)
+    }
+
+    /// Decodes the selected audio stream, packets of other streams are skipped.
+    pub fn with_stream(input: R, selector: StreamSelector) -> anyhow::Result<Self> {
+        Self::open(IO::from_read_stream(input), selector)
+    }
 
+    fn open(io: IO<R>, selector: StreamSelector) -> anyhow::Result<Self> {
         let demuxer = Demuxer::builder()
             .build(io)?
             .find_stream_info(None)
             .map_err(|(_, err)| err)?;
 
-        let decoder = AudioDecoder::from_stream(&demuxer.streams()[0])?.build()?;
+        let stream_index = selector.select(&StreamInfo::audio_streams(demuxer.streams()))?;
+        let codec = AudioDecoder::from_stream(&demuxer.streams()[stream_index])?.build()?;
+
+        Ok(Self {
+            demuxer,
+            codec,
+            stream_index,
+            seek_position: None,
+        })
+    }
+}
 
-        Ok(Self { demuxer, decoder })
+impl<R: Read + Seek> Decoder<R> {
+    /// Decoder of a file or an in-memory track, which can `seek`.
+    pub fn try_from_seekable(input: R, selector: StreamSelector) -> anyhow::Result<Self> {
+        Self::open(IO::from_seekable_read_stream(input), selector)
     }
 }
 
 impl<T> Decoder<T> {
+    /// Next frames start exactly at the position, the input must be seekable.
+    pub fn seek(&mut self, position: Duration) -> anyhow::Result<()> {
+        let micros = i64::try_from(position.as_micros())?;
+        // Land on a packet before the position, frames up to it are decoded and dropped.
+        self.demuxer
+            .seek_to_timestamp(Timestamp::from_micros(micros), SeekTarget::UpTo)?;
+
+        // Frames buffered before the seek belong to the old position.
+        self.codec =
+            AudioDecoder::from_stream(&self.demuxer.streams()[self.stream_index])?.build()?;
+        self.seek_position = Some(position);
+
+        Ok(())
+    }
+
+    /// Container format, e.g. `mp3` or `mpegts`.
+    #[must_use]
+    pub fn format_name(&self) -> String {
+        self.demuxer.input_format().name().to_string()
+    }
+
+    /// The decoded stream.
+    #[must_use]
+    pub fn stream(&self) -> StreamInfo {
+        self.streams()
+            .into_iter()
+            .find(|stream| stream.index == self.stream_index)
+            .expect("Selected stream is an audio one")
+    }
+
+    /// All audio streams of the input.
+    #[must_use]
+    pub fn streams(&self) -> Vec<StreamInfo> {
+        StreamInfo::audio_streams(self.demuxer.streams())
+    }
+
     #[must_use]
     pub fn codec_parameters(&self) -> AudioCodecParameters {
-        self.demuxer.streams()[0]
+        self.demuxer.streams()[self.stream_index]
             .codec_parameters()
             .as_audio_codec_parameters()
             .cloned()
@@ -50,39 +112,112 @@ impl<T> Decoder<T> {
 
     #[must_use]
     pub fn frames(&self) -> u64 {
-        self.demuxer.streams()[0].frames().unwrap_or_default()
+        self.demuxer.streams()[self.stream_index]
+            .frames()
+            .unwrap_or_default()
     }
 }
 
-impl<T> Iterator for Decoder<T> {
-    type Item = anyhow::Result<AudioFrame>;
+impl<T> Decoder<T> {
+    // Drops or trims frames before the seek position.
+    fn skip_to_position(&mut self, frame: AudioFrame) -> Option<AudioFrame> {
+        let Some(position) = self.seek_position else {
+            return Some(frame);
+        };
+
+        // Without timestamps the position can not be found, the frames are taken as they are.
+        let Some(start) = frame.pts().as_micros() else {
+            self.seek_position = None;
+            return Some(frame);
+        };
+        let start = Duration::from_micros(u64::try_from(start).unwrap_or_default());
+
+        if start + frame.duration() <= position {
+            return None;
+        }
 
-    fn next(&mut self) -> Option<Self::Item> {
+        self.seek_position = None;
+        if start >= position {
+            return Some(frame);
+        }
+
+        let samples = position.saturating_sub(start).as_secs_f64() * f64::from(frame.sample_rate());
+        Some(trim_start(&frame, samples.round() as usize))
+    }
+
+    fn decode(&mut self) -> Option<anyhow::Result<AudioFrame>> {
         // Is there anything in decoder already?
-        let frame = self.decoder.take().map_err(Into::into).transpose();
+        let frame = self.codec.take().map_err(Into::into).transpose();
         if frame.is_some() {
             return frame;
         }
 
-        // If not, push demuxed packet
-        let packet = self.demuxer.take().map_err(Into::into).transpose();
-
-        match packet {
-            None => {}
-            Some(Ok(packet)) => {
-                if let Err(error) = self.decoder.try_push(packet) {
-                    return Some(Err(error.into()));
+        // If not, push demuxed packets of the selected stream, until the decoder has a frame.
+        loop {
+            match self.demuxer.take() {
+                Ok(None) => break,
+                Ok(Some(packet)) if packet.stream_index() != self.stream_index => {}
+                Ok(Some(packet)) => {
+                    if let Err(error) = self.codec.try_push(packet) {
+                        return Some(Err(error.into()));
+                    }
+                    let frame = self.codec.take().map_err(Into::into).transpose();
+                    if frame.is_some() {
+                        return frame;
+                    }
                 }
-                return self.decoder.take().map_err(Into::into).transpose();
+                Err(error) => return Some(Err(error.into())),
             }
-            Some(Err(error)) => return Some(Err(error)),
         }
 
         // If no packet, flush decoder.
-        if let Err(error) = self.decoder.flush() {
+        if let Err(error) = self.codec.flush() {
             return Some(Err(error.into()));
         }
 
-        self.decoder.take().map_err(Into::into).transpose()
+        self.codec.take().map_err(Into::into).transpose()
+    }
+}
+
+impl<T> Iterator for Decoder<T> {
+    type Item = anyhow::Result<AudioFrame>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.decode()? {
+                Ok(frame) => {
+                    if let Some(frame) = self.skip_to_position(frame) {
+                        return Some(Ok(frame));
+                    }
+                }
+                Err(error) => return Some(Err(error)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    const SAMPLE: &[u8] = include_bytes!("../../restreamer/sample.aac");
+
+    #[test]
+    fn test_seek() {
+        let mut decoder =
+            Decoder::try_from_seekable(Cursor::new(SAMPLE), StreamSelector::Best).unwrap();
+        // 48kHz AAC, 1s is 896 samples into the 47th frame of 1024.
+        decoder.seek(Duration::from_secs(1)).unwrap();
+
+        let first = decoder.next().unwrap().unwrap();
+        let pts = first.pts().as_micros().unwrap();
+        // Within a sample.
+        assert!((pts - 1_000_000).abs() <= 21, "{pts}");
+        assert_eq!(first.samples(), 128);
+
+        let second = decoder.next().unwrap().unwrap();
+        assert_eq!(second.samples(), 1024);
     }
 }
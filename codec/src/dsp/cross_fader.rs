@@ -3,17 +3,37 @@ use std::time::Duration;
 
 use crate::AudioFrame;
 
-use super::{CrossFade, CrossFadePair};
+use super::{CrossFade, CrossFadeCurve, CrossFadePair};
 
 pub struct CrossFader {
     values: Vec<CrossFadePair>,
+    generate: fn(usize) -> Vec<CrossFadePair>,
+    base_len: usize,
     pos: Cell<usize>,
 }
 
 impl CrossFader {
     #[must_use]
     pub fn new<CF: CrossFade>(cf_duration: Duration, frame_duration: Duration) -> Self {
-        let values = CF::generate((cf_duration.as_millis() / frame_duration.as_millis()) as usize);
+        Self::with_generator(CF::generate, cf_duration, frame_duration)
+    }
+
+    #[must_use]
+    pub fn with_curve(
+        curve: CrossFadeCurve,
+        cf_duration: Duration,
+        frame_duration: Duration,
+    ) -> Self {
+        log::info!("Cross-fade curve {curve}");
+        Self::with_generator(curve.generator(), cf_duration, frame_duration)
+    }
+
+    fn with_generator(
+        generate: fn(usize) -> Vec<CrossFadePair>,
+        cf_duration: Duration,
+        frame_duration: Duration,
+    ) -> Self {
+        let values = generate(frames(cf_duration, frame_duration));
 
         log::info!(
             "Cross-fade {:0.1}s, {} frames",
@@ -22,7 +42,9 @@ impl CrossFader {
         );
 
         Self {
+            base_len: values.len(),
             values,
+            generate,
             pos: Cell::default(),
         }
     }
@@ -32,7 +54,9 @@ impl CrossFader {
     pub fn exact<CF: CrossFade>(length: usize) -> Self {
         let values = CF::generate(length);
         Self {
+            base_len: length,
             values,
+            generate: CF::generate,
             pos: Cell::default(),
         }
     }
@@ -41,6 +65,18 @@ impl CrossFader {
         self.pos.set(0);
     }
 
+    /// Duration the cross-fader was created with, whatever it was restarted with since.
+    #[must_use]
+    pub fn base_duration(&self, frame_duration: Duration) -> Duration {
+        frame_duration * self.base_len as u32
+    }
+
+    /// Restarts with a cross-fade of another duration along the same curve.
+    pub fn reset_to(&mut self, cf_duration: Duration, frame_duration: Duration) {
+        self.values = (self.generate)(frames(cf_duration, frame_duration));
+        self.reset();
+    }
+
     pub fn apply(&self, fade_out: &AudioFrame, fade_in: &AudioFrame) -> AudioFrame {
         let pos = self.pos.get();
 
@@ -62,3 +98,7 @@ impl CrossFader {
         self.pos.set(self.len());
     }
 }
+
+const fn frames(cf_duration: Duration, frame_duration: Duration) -> usize {
+    (cf_duration.as_millis() / frame_duration.as_millis()) as usize
+}
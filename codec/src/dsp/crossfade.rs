@@ -1,8 +1,11 @@
 use std::cmp::Ordering;
+use std::fmt::Display;
 use std::ops::Mul;
+use std::str::FromStr;
 
 use ac_ffmpeg::codec::audio::AudioFrame;
-use bytemuck::{cast_slice, cast_slice_mut};
+
+use crate::{FrameSamples, FrameSamplesMut};
 
 #[derive(Debug, Default, Clone, Copy)]
 pub struct CrossFadePair(f64, f64);
@@ -32,30 +35,28 @@ impl Mul<(&AudioFrame, &AudioFrame)> for &CrossFadePair {
             "Frames must have equal number of samples",
         );
 
-        let samples_per_frame = left.samples();
-
-        let left_planes = left.planes();
-        let right_planes = right.planes();
+        let left_channels = left.channel_samples::<f32>();
+        let right_channels = right.channel_samples::<f32>();
 
         assert_eq!(
-            left_planes.len(),
-            right_planes.len(),
-            "Frames must have equal number of planes",
+            left_channels.len(),
+            right_channels.len(),
+            "Frames must have equal number of channels",
         );
 
-        let mut frame = left.clone().into_mut();
-        let mut planes = frame.planes_mut();
-
-        for i in 0..left_planes.len() {
-            let left_data = cast_slice::<_, f32>(left_planes[i].data());
-            let right_data = cast_slice::<_, f32>(right_planes[i].data());
-            let data = cast_slice_mut::<_, f32>(planes[i].data_mut());
-
-            for x in 0..samples_per_frame {
-                data[x] = self.apply(left_data[x], right_data[x]);
-            }
-        }
+        let mixed = left_channels
+            .iter()
+            .zip(&right_channels)
+            .map(|(left, right)| {
+                left.iter()
+                    .zip(right)
+                    .map(|(left, right)| self.apply(*left, *right))
+                    .collect()
+            })
+            .collect::<Vec<_>>();
 
+        let mut frame = left.clone().into_mut();
+        frame.set_channel_samples(&mixed);
         frame.freeze()
     }
 }
@@ -131,7 +132,8 @@ pub trait CrossFade {
     }
 
     fn generate(size: usize) -> Vec<CrossFadePair> {
-        let step = Self::step(size);
+        // A single frame is the first of two, the incoming track follows it.
+        let step = Self::step(size.max(2));
 
         (0..size)
             .map(|n| n as f64 * step)
@@ -214,6 +216,70 @@ impl CrossFade for ParabolicCrossFade {
     }
 }
 
+/// Cross-fade curves by name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CrossFadeCurve {
+    Linear,
+    Parabolic,
+    EqualPower,
+    Cossin,
+    Semicircle,
+}
+
+impl CrossFadeCurve {
+    pub const ALL: [Self; 5] = [
+        Self::Linear,
+        Self::Parabolic,
+        Self::EqualPower,
+        Self::Cossin,
+        Self::Semicircle,
+    ];
+
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Linear => "linear",
+            Self::Parabolic => "parabolic",
+            Self::EqualPower => "equal-power",
+            Self::Cossin => "cossin",
+            Self::Semicircle => "semicircle",
+        }
+    }
+
+    #[must_use]
+    pub fn generator(self) -> fn(usize) -> Vec<CrossFadePair> {
+        match self {
+            Self::Linear => LinearCrossFade::generate,
+            Self::Parabolic => ParabolicCrossFade::generate,
+            Self::EqualPower => EqualPowerCrossFade::generate,
+            Self::Cossin => CossinCrossFade::generate,
+            Self::Semicircle => SemicircleCrossFade::generate,
+        }
+    }
+}
+
+impl Display for CrossFadeCurve {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for CrossFadeCurve {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|curve| curve.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Unknown cross-fade curve {s}, expected one of: {}",
+                    Self::ALL.map(Self::name).join(", ")
+                )
+            })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use nearly::{
@@ -352,4 +418,24 @@ mod tests {
             eps = 1e-3
         );
     }
+
+    #[test]
+    fn test_curve_names() {
+        for curve in CrossFadeCurve::ALL {
+            assert_eq!(curve.to_string().parse::<CrossFadeCurve>().unwrap(), curve);
+        }
+        assert_eq!(
+            "Equal-Power".parse::<CrossFadeCurve>().unwrap(),
+            CrossFadeCurve::EqualPower
+        );
+        assert!("cubic".parse::<CrossFadeCurve>().is_err());
+    }
+
+    #[test]
+    fn test_short_cross_fades() {
+        for curve in CrossFadeCurve::ALL {
+            assert!(curve.generator()(0).is_empty());
+            assert_eq!(curve.generator()(1), vec![(1.0, 0.0).into()], "{curve}");
+        }
+    }
 }
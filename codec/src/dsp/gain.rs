@@ -0,0 +1,23 @@
+use ac_ffmpeg::codec::audio::AudioFrame;
+
+use crate::{FrameSamples, FrameSamplesMut};
+
+/// Frame with all samples multiplied by `gain`, integer formats saturate at full scale.
+#[must_use]
+pub fn apply_gain(frame: &AudioFrame, gain: f32) -> AudioFrame {
+    let channels = frame
+        .channel_samples::<f32>()
+        .into_iter()
+        .map(|samples| samples.into_iter().map(|sample| sample * gain).collect())
+        .collect::<Vec<Vec<f32>>>();
+
+    let mut gained = frame.clone().into_mut();
+    gained.set_channel_samples(&channels);
+    gained.freeze()
+}
+
+/// Linear gain of a level change in decibels.
+#[must_use]
+pub fn db_to_gain(db: f64) -> f32 {
+    10_f64.powf(db / 20.0) as f32
+}
@@ -0,0 +1,161 @@
+use std::collections::VecDeque;
+
+use crate::{AudioFrame, FrameSamples};
+
+use super::loudness::{STEP, STEPS_PER_SHORT_TERM};
+use super::LoudnessMeter;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Levels {
+    /// Loudness of the last 400ms in LUFS.
+    pub momentary: f64,
+    /// Loudness of the last 3s in LUFS.
+    pub short_term: f64,
+    pub channels: Vec<ChannelLevels>,
+}
+
+/// Levels of a channel over the last 3s in dBFS.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChannelLevels {
+    pub rms: f64,
+    pub peak: f64,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Step {
+    energy: f64,
+    peak: f32,
+}
+
+/// Live levels of a stream, updated with every pushed frame.
+pub struct LevelMeter {
+    loudness: LoudnessMeter,
+    step_length: usize,
+    step_samples: usize,
+    current: Vec<Step>,
+    steps: VecDeque<Vec<Step>>,
+    peak: f32,
+}
+
+impl LevelMeter {
+    #[must_use]
+    pub fn new(sample_rate: u32) -> Self {
+        let step_length = (u128::from(sample_rate) * STEP.as_millis() / 1000) as usize;
+        Self {
+            // Only momentary and short-term loudness are needed.
+            loudness: LoudnessMeter::new(sample_rate).with_window(STEP),
+            step_length: step_length.max(1),
+            step_samples: 0,
+            current: vec![],
+            steps: VecDeque::with_capacity(STEPS_PER_SHORT_TERM),
+            peak: 0.0,
+        }
+    }
+
+    pub fn push(&mut self, frame: &AudioFrame) {
+        self.push_channels(&frame.channel_samples::<f32>());
+    }
+
+    pub fn push_channels(&mut self, channels: &[Vec<f32>]) {
+        self.loudness.push_channels(channels);
+        if self.current.len() != channels.len() {
+            self.current.resize(channels.len(), Step::default());
+        }
+
+        let samples = channels.iter().map(Vec::len).min().unwrap_or_default();
+        let mut position = 0;
+        while position < samples {
+            let length = (self.step_length - self.step_samples).min(samples - position);
+            for (step, channel) in self.current.iter_mut().zip(channels) {
+                for sample in &channel[position..position + length] {
+                    step.energy += f64::from(*sample) * f64::from(*sample);
+                    step.peak = step.peak.max(sample.abs());
+                }
+                self.peak = self.peak.max(step.peak);
+            }
+
+            position += length;
+            self.step_samples += length;
+            if self.step_samples == self.step_length {
+                self.push_step();
+            }
+        }
+    }
+
+    #[must_use]
+    pub fn levels(&self) -> Levels {
+        let channels = (0..self.current.len())
+            .map(|channel| {
+                let (energy, peak) = self
+                    .steps
+                    .iter()
+                    .filter_map(|steps| steps.get(channel))
+                    .fold((0.0, 0.0_f32), |(energy, peak), step| {
+                        (energy + step.energy, peak.max(step.peak))
+                    });
+                let samples = (self.steps.len() * self.step_length).max(1) as f64;
+                ChannelLevels {
+                    rms: 10.0 * (energy / samples).log10(),
+                    peak: 20.0 * f64::from(peak).log10(),
+                }
+            })
+            .collect();
+
+        Levels {
+            momentary: self.loudness.momentary(),
+            short_term: self.loudness.short_term(),
+            channels,
+        }
+    }
+
+    /// Highest peak of all channels since the last call in dBFS.
+    pub fn take_peak(&mut self) -> f64 {
+        20.0 * f64::from(std::mem::take(&mut self.peak)).log10()
+    }
+
+    fn push_step(&mut self) {
+        if self.steps.len() == STEPS_PER_SHORT_TERM {
+            self.steps.pop_front();
+        }
+        let step = vec![Step::default(); self.current.len()];
+        self.steps
+            .push_back(std::mem::replace(&mut self.current, step));
+        self.step_samples = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nearly::assert_nearly_eq;
+
+    use super::*;
+
+    #[allow(clippy::float_cmp)]
+    #[test]
+    fn test_levels() {
+        let sine = (0..48_000 * 5)
+            .map(|i| 0.5 * (2.0 * std::f32::consts::PI * 1_000.0 * i as f32 / 48_000.0).sin())
+            .collect::<Vec<_>>();
+
+        let mut meter = LevelMeter::new(48_000);
+        meter.push_channels(&[sine, vec![0.0; 48_000 * 5]]);
+
+        let levels = meter.levels();
+        assert_nearly_eq!(levels.momentary, -9.03, eps = 0.05);
+        assert_nearly_eq!(levels.short_term, -9.03, eps = 0.05);
+        assert_nearly_eq!(levels.channels[0].rms, -9.03, eps = 0.05);
+        assert_nearly_eq!(levels.channels[0].peak, -6.02, eps = 0.05);
+        assert_eq!(levels.channels[1].peak, f64::NEG_INFINITY);
+
+        // A second of silence drops the momentary loudness, 3s still have 2s of the sine.
+        meter.push_channels(&[vec![0.0; 48_000], vec![0.0; 48_000]]);
+        let levels = meter.levels();
+        assert!(levels.momentary < -70.0);
+        assert_nearly_eq!(levels.short_term, -10.79, eps = 0.05);
+        assert_nearly_eq!(levels.channels[0].peak, -6.02, eps = 0.05);
+
+        assert_nearly_eq!(meter.take_peak(), -6.02, eps = 0.05);
+        meter.push_channels(&[vec![0.25; 10], vec![0.0; 10]]);
+        assert_nearly_eq!(meter.take_peak(), -12.04, eps = 0.05);
+    }
+}
@@ -0,0 +1,101 @@
+use std::time::Duration;
+
+use crate::{AudioFrame, FrameSamples, FrameSamplesMut};
+
+use super::db_to_gain;
+
+/// Applies a gain, cutting it at once for samples that would peak over the ceiling
+/// and letting it recover exponentially in about `release`.
+///
+/// The ceiling is of sample peaks, peaks between samples may exceed it.
+pub struct Limiter {
+    ceiling: f32,
+    release: Duration,
+    reduction: f32,
+}
+
+impl Limiter {
+    #[must_use]
+    pub fn new(ceiling_db: f64, release: Duration) -> Self {
+        Self {
+            ceiling: db_to_gain(ceiling_db),
+            release,
+            reduction: 1.0,
+        }
+    }
+
+    pub fn apply(&mut self, frame: &AudioFrame, gain: f32) -> AudioFrame {
+        let mut channels = frame.channel_samples::<f32>();
+
+        // The gain changes sample by sample, so it does not step at frame boundaries.
+        let recovery =
+            1.0 - (-1.0 / (self.release.as_secs_f32() * frame.sample_rate() as f32)).exp();
+        let samples = channels.iter().map(Vec::len).max().unwrap_or_default();
+        for index in 0..samples {
+            let peak = channels
+                .iter()
+                .filter_map(|channel| channel.get(index))
+                .fold(0.0_f32, |peak, sample| peak.max(sample.abs()));
+
+            let released = (1.0 - self.reduction).mul_add(recovery, self.reduction);
+            let limit = if peak * gain > self.ceiling {
+                self.ceiling / (peak * gain)
+            } else {
+                1.0
+            };
+            self.reduction = released.min(limit);
+
+            let gain = gain * self.reduction;
+            for sample in channels
+                .iter_mut()
+                .filter_map(|channel| channel.get_mut(index))
+            {
+                *sample *= gain;
+            }
+        }
+
+        let mut limited = frame.clone().into_mut();
+        limited.set_channel_samples(&channels);
+        limited.freeze()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ac_ffmpeg::codec::audio::{AudioFrameMut, ChannelLayout};
+    use nearly::assert_nearly_eq;
+
+    use super::*;
+    use crate::SampleFormat;
+
+    fn frame(value: f32) -> AudioFrame {
+        let mut frame = AudioFrameMut::silence(
+            &ChannelLayout::from_channels(1).unwrap(),
+            SampleFormat::Flt.into(),
+            10,
+            10,
+        );
+        frame.set_channel_samples(&[vec![value; 10]]);
+        frame.freeze()
+    }
+
+    #[test]
+    fn test_limiter() {
+        let mut limiter = Limiter::new(0.0, Duration::from_secs(1));
+
+        let loud = limiter.apply(&frame(0.8), 2.0).channel_samples::<f32>();
+        assert_nearly_eq!(loud[0][0], 1.0);
+
+        // Gain recovers slowly, even if the frame has room for it.
+        let recovered = limiter.apply(&frame(0.1), 2.0).channel_samples::<f32>();
+        assert!(recovered[0][0] > 0.125 && recovered[0][0] < 0.2);
+        // Sample by sample, without steps.
+        assert!(recovered[0].windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(recovered[0][9] - recovered[0][0] < 0.05);
+
+        let quiet = Limiter::new(0.0, Duration::from_secs(1))
+            .apply(&frame(0.1), 2.0)
+            .channel_samples::<f32>();
+        assert_nearly_eq!(quiet[0][0], 0.2);
+    }
+}
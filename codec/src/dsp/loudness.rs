@@ -0,0 +1,376 @@
+//! Loudness of ITU-R BS.1770 as used by EBU R128: gated K-weighted loudness and true peak.
+
+use std::collections::VecDeque;
+use std::f64::consts::PI;
+use std::time::Duration;
+
+use crate::{AudioFrame, FrameSamples};
+
+const ABSOLUTE_GATE: f64 = -70.0;
+const RELATIVE_GATE: f64 = -10.0;
+// Blocks of 400ms overlap by 75%, each one is made of four 100ms steps.
+const STEPS_PER_BLOCK: usize = 4;
+// Short-term loudness is measured over 3s.
+pub const STEPS_PER_SHORT_TERM: usize = 30;
+pub const STEP: Duration = Duration::from_millis(100);
+const OVERSAMPLING: usize = 4;
+const TAPS_PER_PHASE: usize = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Loudness {
+    /// Integrated loudness in LUFS, negative infinity for silence.
+    pub integrated: f64,
+    /// True peak in dBTP.
+    pub true_peak: f64,
+}
+
+impl Loudness {
+    /// Loudness of a whole track, `None` for a track without frames.
+    pub fn measure<'a>(frames: impl IntoIterator<Item = &'a AudioFrame>) -> Option<Self> {
+        let mut frames = frames.into_iter().peekable();
+        let sample_rate = frames.peek()?.sample_rate();
+
+        let mut loudness = LoudnessMeter::new(sample_rate);
+        let mut peak = TruePeakMeter::new();
+        for frame in frames {
+            let channels = frame.channel_samples::<f32>();
+            loudness.push_channels(&channels);
+            peak.push_channels(&channels);
+        }
+
+        Some(Self {
+            integrated: loudness.integrated(),
+            true_peak: peak.true_peak(),
+        })
+    }
+}
+
+/// Gated loudness of K-weighted samples, of the whole input or of its last `window`.
+pub struct LoudnessMeter {
+    filters: Vec<KWeighting>,
+    sample_rate: u32,
+    step_length: usize,
+    step_energy: f64,
+    step_samples: usize,
+    steps: VecDeque<f64>,
+    blocks: VecDeque<f64>,
+    window: Option<usize>,
+}
+
+impl LoudnessMeter {
+    #[must_use]
+    pub fn new(sample_rate: u32) -> Self {
+        let step_length = (u128::from(sample_rate) * STEP.as_millis() / 1000) as usize;
+        Self {
+            filters: vec![],
+            sample_rate,
+            step_length: step_length.max(1),
+            step_energy: 0.0,
+            step_samples: 0,
+            steps: VecDeque::with_capacity(STEPS_PER_SHORT_TERM),
+            blocks: VecDeque::new(),
+            window: None,
+        }
+    }
+
+    /// Keeps blocks of the last `window` only.
+    #[must_use]
+    pub fn with_window(mut self, window: Duration) -> Self {
+        self.window = Some(((window.as_millis() / STEP.as_millis()) as usize).max(1));
+        self
+    }
+
+    pub fn push(&mut self, frame: &AudioFrame) {
+        self.push_channels(&frame.channel_samples::<f32>());
+    }
+
+    #[allow(clippy::needless_range_loop)]
+    pub fn push_channels(&mut self, channels: &[Vec<f32>]) {
+        let sample_rate = self.sample_rate;
+        if self.filters.len() != channels.len() {
+            self.filters
+                .resize_with(channels.len(), || KWeighting::new(sample_rate));
+        }
+        let weights = (0..channels.len())
+            .map(|channel| channel_weight(channel, channels.len()))
+            .collect::<Vec<_>>();
+
+        let samples = channels.iter().map(Vec::len).min().unwrap_or_default();
+        for sample in 0..samples {
+            for (channel, filter) in self.filters.iter_mut().enumerate() {
+                let weighted = filter.process(f64::from(channels[channel][sample]));
+                self.step_energy += weights[channel] * weighted * weighted;
+            }
+
+            self.step_samples += 1;
+            if self.step_samples == self.step_length {
+                self.push_step();
+            }
+        }
+    }
+
+    /// Loudness in LUFS, negative infinity if nothing is above the absolute gate.
+    #[must_use]
+    pub fn integrated(&self) -> f64 {
+        let absolute = self
+            .blocks
+            .iter()
+            .copied()
+            .filter(|energy| loudness(*energy) > ABSOLUTE_GATE)
+            .collect::<Vec<_>>();
+        let relative_gate = loudness(mean(absolute.iter().copied())) + RELATIVE_GATE;
+
+        loudness(mean(
+            absolute
+                .into_iter()
+                .filter(|energy| loudness(*energy) > relative_gate),
+        ))
+    }
+
+    /// Loudness of the last 400ms in LUFS.
+    #[must_use]
+    pub fn momentary(&self) -> f64 {
+        if self.steps.len() < STEPS_PER_BLOCK {
+            return f64::NEG_INFINITY;
+        }
+        loudness(self.last_steps(STEPS_PER_BLOCK))
+    }
+
+    /// Loudness of the last 3s in LUFS, or of less at the start.
+    #[must_use]
+    pub fn short_term(&self) -> f64 {
+        loudness(self.last_steps(STEPS_PER_SHORT_TERM))
+    }
+
+    fn last_steps(&self, count: usize) -> f64 {
+        let count = count.min(self.steps.len());
+        mean(self.steps.range(self.steps.len() - count..).copied())
+    }
+
+    fn push_step(&mut self) {
+        if self.steps.len() == STEPS_PER_SHORT_TERM {
+            self.steps.pop_front();
+        }
+        self.steps
+            .push_back(self.step_energy / self.step_length as f64);
+        self.step_energy = 0.0;
+        self.step_samples = 0;
+
+        if self.steps.len() >= STEPS_PER_BLOCK {
+            self.blocks.push_back(self.last_steps(STEPS_PER_BLOCK));
+            if let Some(window) = self.window {
+                while self.blocks.len() > window {
+                    self.blocks.pop_front();
+                }
+            }
+        }
+    }
+}
+
+/// Peak of the signal oversampled four times, catching peaks between samples.
+pub struct TruePeakMeter {
+    phases: Vec<[f64; TAPS_PER_PHASE]>,
+    history: Vec<VecDeque<f64>>,
+    peak: f64,
+}
+
+impl Default for TruePeakMeter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TruePeakMeter {
+    #[must_use]
+    pub fn new() -> Self {
+        // Hann windowed sinc with the cut-off at the original Nyquist frequency, split into phases.
+        let length = OVERSAMPLING * TAPS_PER_PHASE;
+        let center = (length - 1) as f64 / 2.0;
+        let taps = (0..length)
+            .map(|tap| {
+                let x = (tap as f64 - center) / OVERSAMPLING as f64;
+                let window = 0.5_f64.mul_add(
+                    -(2.0 * PI * (tap + 1) as f64 / (length + 1) as f64).cos(),
+                    0.5,
+                );
+                window * (PI * x).sin() / (PI * x)
+            })
+            .collect::<Vec<_>>();
+        let phases = (0..OVERSAMPLING)
+            .map(|phase| {
+                let mut coefficients = [0.0; TAPS_PER_PHASE];
+                for (j, coefficient) in coefficients.iter_mut().enumerate() {
+                    *coefficient = taps[phase + OVERSAMPLING * j];
+                }
+                coefficients
+            })
+            .collect();
+
+        Self {
+            phases,
+            history: vec![],
+            peak: 0.0,
+        }
+    }
+
+    pub fn push(&mut self, frame: &AudioFrame) {
+        self.push_channels(&frame.channel_samples::<f32>());
+    }
+
+    pub fn push_channels(&mut self, channels: &[Vec<f32>]) {
+        if self.history.len() != channels.len() {
+            self.history
+                .resize_with(channels.len(), || VecDeque::from(vec![0.0; TAPS_PER_PHASE]));
+        }
+
+        for (samples, history) in channels.iter().zip(&mut self.history) {
+            for sample in samples {
+                history.pop_back();
+                history.push_front(f64::from(*sample));
+
+                self.peak = self.peak.max(f64::from(*sample).abs());
+                for phase in &self.phases {
+                    let value = phase
+                        .iter()
+                        .zip(history.iter())
+                        .map(|(coefficient, sample)| coefficient * sample)
+                        .sum::<f64>();
+                    self.peak = self.peak.max(value.abs());
+                }
+            }
+        }
+    }
+
+    /// Peak in dBTP.
+    #[must_use]
+    pub fn true_peak(&self) -> f64 {
+        20.0 * self.peak.log10()
+    }
+}
+
+// High shelf modelling the head, then a high pass, coefficients for any sample rate.
+struct KWeighting {
+    shelf: Biquad,
+    high_pass: Biquad,
+}
+
+impl KWeighting {
+    fn new(sample_rate: u32) -> Self {
+        let rate = f64::from(sample_rate);
+
+        let k = (PI * 1_681.974_450_955_533 / rate).tan();
+        let q = 0.707_175_236_955_419_6;
+        let vh = 10_f64.powf(3.999_843_853_973_347 / 20.0);
+        let vb = vh.powf(0.499_666_774_154_541_6);
+        let a0 = 1.0 + k / q + k * k;
+        let shelf = Biquad::new(
+            [
+                (vh + vb * k / q + k * k) / a0,
+                2.0 * (k * k - vh) / a0,
+                (vh - vb * k / q + k * k) / a0,
+            ],
+            [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        );
+
+        let k = (PI * 38.135_470_876_024_44 / rate).tan();
+        let q = 0.500_327_037_323_877_3;
+        let a0 = 1.0 + k / q + k * k;
+        let high_pass = Biquad::new(
+            [1.0, -2.0, 1.0],
+            [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        );
+
+        Self { shelf, high_pass }
+    }
+
+    fn process(&mut self, sample: f64) -> f64 {
+        self.high_pass.process(self.shelf.process(sample))
+    }
+}
+
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    z: [f64; 2],
+}
+
+impl Biquad {
+    const fn new(b: [f64; 3], a: [f64; 2]) -> Self {
+        Self { b, a, z: [0.0; 2] }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0].mul_add(x, self.z[0]);
+        self.z[0] = self.b[1].mul_add(x, -self.a[0] * y) + self.z[1];
+        self.z[1] = self.b[2].mul_add(x, -self.a[1] * y);
+        y
+    }
+}
+
+// Surround channels of 5.0 and 5.1 weigh more, LFE is left out.
+const fn channel_weight(channel: usize, channels: usize) -> f64 {
+    match (channels, channel) {
+        (5, 3 | 4) | (6, 4 | 5) => 1.41,
+        (6, 3) => 0.0,
+        _ => 1.0,
+    }
+}
+
+fn loudness(energy: f64) -> f64 {
+    10.0_f64.mul_add(energy.log10(), -0.691)
+}
+
+fn mean(values: impl IntoIterator<Item = f64>) -> f64 {
+    let (sum, count) = values
+        .into_iter()
+        .fold((0.0, 0_u32), |(sum, count), value| (sum + value, count + 1));
+    if count == 0 {
+        0.0
+    } else {
+        sum / f64::from(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nearly::assert_nearly_eq;
+
+    use super::*;
+
+    fn sine(amplitude: f32, seconds: usize) -> Vec<f32> {
+        (0..48_000 * seconds)
+            .map(|i| amplitude * (2.0 * std::f32::consts::PI * 1_000.0 * i as f32 / 48_000.0).sin())
+            .collect()
+    }
+
+    #[test]
+    fn test_sine() {
+        let mut loudness = LoudnessMeter::new(48_000);
+        let mut peak = TruePeakMeter::new();
+        let channels = [sine(0.5, 5)];
+        loudness.push_channels(&channels);
+        peak.push_channels(&channels);
+
+        // A full scale 1kHz sine in one channel reads -3.01 LUFS.
+        assert_nearly_eq!(loudness.integrated(), -9.03, eps = 0.05);
+        assert_nearly_eq!(peak.true_peak(), -6.02, eps = 0.05);
+    }
+
+    #[allow(clippy::float_cmp)]
+    #[test]
+    fn test_gating() {
+        let mut loudness = LoudnessMeter::new(48_000);
+        loudness.push_channels(&[vec![0.0; 48_000]]);
+        assert_eq!(loudness.integrated(), f64::NEG_INFINITY);
+
+        // Quiet part is below the relative gate, only blocks over the change lower the loudness.
+        loudness.push_channels(&[sine(0.5, 5)]);
+        loudness.push_channels(&[sine(0.01, 5)]);
+        assert_nearly_eq!(loudness.integrated(), -9.03, eps = 0.3);
+
+        let mut window = LoudnessMeter::new(48_000).with_window(Duration::from_secs(3));
+        window.push_channels(&[sine(0.5, 5)]);
+        window.push_channels(&[sine(0.1, 5)]);
+        assert_nearly_eq!(window.integrated(), -23.01, eps = 0.1);
+    }
+}
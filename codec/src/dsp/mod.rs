@@ -1,9 +1,21 @@
 mod cross_fader;
 mod crossfade;
+mod gain;
+mod levels;
+mod limiter;
+mod loudness;
+mod silence_detector;
+mod transition_planner;
 
 pub use crossfade::{
-    CossinCrossFade, CrossFade, CrossFadePair, EqualPowerCrossFade, LinearCrossFade,
-    ParabolicCrossFade, SemicircleCrossFade, ToFadeInOut,
+    CossinCrossFade, CrossFade, CrossFadeCurve, CrossFadePair, EqualPowerCrossFade,
+    LinearCrossFade, ParabolicCrossFade, SemicircleCrossFade, ToFadeInOut,
 };
 
 pub use cross_fader::CrossFader;
+pub use gain::{apply_gain, db_to_gain};
+pub use levels::{ChannelLevels, LevelMeter, Levels};
+pub use limiter::Limiter;
+pub use loudness::{Loudness, LoudnessMeter, TruePeakMeter};
+pub use silence_detector::{SilenceDetector, SilenceEvent};
+pub use transition_planner::{Transition, TransitionPlanner, TransitionPoint};
@@ -0,0 +1,105 @@
+use std::time::Duration;
+
+use crate::{AudioFrame, FrameDuration, FrameSamples};
+
+use super::db_to_gain;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SilenceEvent {
+    /// Silent for the minimum duration, the silence started that long ago.
+    Started(Duration),
+    /// Sound is back after dead air of that length.
+    Ended(Duration),
+}
+
+/// Detects dead air: RMS of every channel stays below the threshold for the minimum duration.
+pub struct SilenceDetector {
+    threshold: f32,
+    min_duration: Duration,
+    silent_for: Duration,
+}
+
+impl SilenceDetector {
+    #[must_use]
+    pub fn new(threshold_db: f64, min_duration: Duration) -> Self {
+        Self {
+            threshold: db_to_gain(threshold_db),
+            min_duration,
+            silent_for: Duration::ZERO,
+        }
+    }
+
+    pub fn push(&mut self, frame: &AudioFrame) -> Option<SilenceEvent> {
+        self.push_channels(&frame.channel_samples::<f32>(), frame.duration())
+    }
+
+    pub fn push_channels(
+        &mut self,
+        channels: &[Vec<f32>],
+        duration: Duration,
+    ) -> Option<SilenceEvent> {
+        let was_silent = self.is_silent();
+
+        let quiet = channels.iter().all(|samples| rms(samples) < self.threshold);
+        if !quiet {
+            let silent_for = std::mem::take(&mut self.silent_for);
+            return was_silent.then_some(SilenceEvent::Ended(silent_for));
+        }
+
+        self.silent_for += duration;
+        (!was_silent && self.is_silent()).then_some(SilenceEvent::Started(self.silent_for))
+    }
+
+    /// Dead air is going on.
+    #[must_use]
+    pub fn is_silent(&self) -> bool {
+        !self.silent_for.is_zero() && self.silent_for >= self.min_duration
+    }
+}
+
+fn rms(samples: &[f32]) -> f32 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    let energy = samples
+        .iter()
+        .map(|sample| f64::from(*sample) * f64::from(*sample))
+        .sum::<f64>();
+    (energy / samples.len() as f64).sqrt() as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: Duration = Duration::from_secs(1);
+
+    #[test]
+    fn test_dead_air() {
+        let mut detector = SilenceDetector::new(-50.0, Duration::from_secs(2));
+        let sound = [vec![0.1; 10], vec![0.0; 10]];
+        // Noise floor of about -60dBFS.
+        let hiss = [vec![0.001, -0.001], vec![0.001, -0.001]];
+
+        assert_eq!(detector.push_channels(&sound, SECOND), None);
+        assert_eq!(detector.push_channels(&hiss, SECOND), None);
+        assert!(!detector.is_silent());
+
+        assert_eq!(
+            detector.push_channels(&hiss, SECOND),
+            Some(SilenceEvent::Started(Duration::from_secs(2)))
+        );
+        assert_eq!(detector.push_channels(&hiss, SECOND), None);
+        assert!(detector.is_silent());
+
+        assert_eq!(
+            detector.push_channels(&sound, SECOND),
+            Some(SilenceEvent::Ended(Duration::from_secs(3)))
+        );
+        assert!(!detector.is_silent());
+
+        // A short pause is not dead air.
+        assert_eq!(detector.push_channels(&hiss, SECOND), None);
+        assert_eq!(detector.push_channels(&sound, SECOND), None);
+    }
+}
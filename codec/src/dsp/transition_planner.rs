@@ -0,0 +1,276 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use crate::{AudioFrame, FrameDuration, FrameSamples};
+
+/// Energy history the quiet points are relative to.
+const AVERAGE_WINDOW: Duration = Duration::from_secs(2);
+/// A quiet point is 10dB below the average energy.
+const QUIET_RATIO: f64 = 0.1;
+/// Energy history an onset stands out from.
+const ONSET_WINDOW: Duration = Duration::from_millis(200);
+/// An onset rises 3dB over the energy before it.
+const ONSET_RATIO: f64 = 2.0;
+/// Beats are 0.25s to 1.5s apart, 40 to 240 bpm.
+const MIN_BEAT: Duration = Duration::from_millis(250);
+const MAX_BEAT: Duration = Duration::from_millis(1_500);
+/// Onsets the beat period is estimated from.
+const TEMPO_WINDOW: Duration = Duration::from_secs(4);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransitionPoint {
+    /// A pause, e.g. between words.
+    Quiet,
+    /// An onset, on the beat when music plays.
+    Beat,
+    /// Nothing better within the tolerance.
+    Timeout,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Transition {
+    pub point: TransitionPoint,
+    pub length: Duration,
+}
+
+/// Times transitions of a stream by its onsets and energy.
+///
+/// A wanted transition is postponed to the next quiet point or beat, at most by the tolerance.
+/// Cuts at a pause take half the fade, cuts on a beat a fade of whole beats.
+pub struct TransitionPlanner {
+    tolerance: Duration,
+    energies: VecDeque<(f64, Duration)>,
+    onsets: VecDeque<Duration>,
+    clock: Duration,
+    last: Option<(TransitionPoint, Duration)>,
+    waited: Option<Duration>,
+}
+
+impl TransitionPlanner {
+    #[must_use]
+    pub const fn new(tolerance: Duration) -> Self {
+        Self {
+            tolerance,
+            energies: VecDeque::new(),
+            onsets: VecDeque::new(),
+            clock: Duration::ZERO,
+            last: None,
+            waited: None,
+        }
+    }
+
+    /// Analyzes the next frame of the stream.
+    pub fn push(&mut self, frame: &AudioFrame) {
+        let channels = frame.channel_samples::<f32>();
+        let samples = channels.iter().map(Vec::len).sum::<usize>().max(1);
+        let energy = channels
+            .iter()
+            .flatten()
+            .map(|sample| f64::from(*sample) * f64::from(*sample))
+            .sum::<f64>()
+            / samples as f64;
+        self.push_energy(energy, frame.duration());
+    }
+
+    /// Analyzes the next frame of the stream by its mean square.
+    pub fn push_energy(&mut self, energy: f64, duration: Duration) {
+        let quiet =
+            !self.energies.is_empty() && energy <= QUIET_RATIO * self.mean_energy(AVERAGE_WINDOW);
+        let onset = !self.energies.is_empty()
+            && energy > ONSET_RATIO * self.mean_energy(ONSET_WINDOW)
+            && self
+                .onsets
+                .back()
+                .map_or(Duration::MAX, |last| self.clock.saturating_sub(*last))
+                >= MIN_BEAT;
+
+        if onset {
+            self.onsets.push_back(self.clock);
+        }
+        while self
+            .onsets
+            .front()
+            .is_some_and(|first| self.clock.saturating_sub(*first) > TEMPO_WINDOW)
+        {
+            self.onsets.pop_front();
+        }
+
+        self.energies.push_back((energy, duration));
+        while self
+            .energies
+            .iter()
+            .map(|(_, duration)| *duration)
+            .sum::<Duration>()
+            > AVERAGE_WINDOW + duration
+        {
+            self.energies.pop_front();
+        }
+
+        self.clock += duration;
+        let point = if quiet {
+            TransitionPoint::Quiet
+        } else if onset {
+            TransitionPoint::Beat
+        } else {
+            TransitionPoint::Timeout
+        };
+        self.last = Some((point, duration));
+    }
+
+    /// A transition with a fade of about the length is wanted,
+    /// returns it if it should start with the last pushed frame.
+    pub fn transition(&mut self, length: Duration) -> Option<Transition> {
+        let (point, duration) = self.last?;
+        let waited = self.waited.unwrap_or_default();
+
+        let length = match point {
+            TransitionPoint::Quiet => Some(length / 2),
+            TransitionPoint::Beat => Some(self.beat_length(length)),
+            TransitionPoint::Timeout => (waited >= self.tolerance).then_some(length),
+        };
+
+        if let Some(length) = length {
+            self.waited = None;
+            Some(Transition { point, length })
+        } else {
+            self.waited = Some(waited + duration);
+            None
+        }
+    }
+
+    /// The transition is not wanted anymore.
+    pub const fn cancel(&mut self) {
+        self.waited = None;
+    }
+
+    /// Whole beats closest to the fade length.
+    fn beat_length(&self, length: Duration) -> Duration {
+        self.beat_period().map_or(length, |period| {
+            let beats = (length.as_secs_f64() / period.as_secs_f64())
+                .round()
+                .max(1.0);
+            (period * beats as u32).clamp(length / 2, length * 2)
+        })
+    }
+
+    /// Median interval of recent onsets.
+    fn beat_period(&self) -> Option<Duration> {
+        let mut intervals = self
+            .onsets
+            .iter()
+            .zip(self.onsets.iter().skip(1))
+            .map(|(first, second)| second.saturating_sub(*first))
+            .filter(|interval| (MIN_BEAT..=MAX_BEAT).contains(interval))
+            .collect::<Vec<_>>();
+        if intervals.len() < 2 {
+            return None;
+        }
+        intervals.sort();
+        Some(intervals[intervals.len() / 2])
+    }
+
+    fn mean_energy(&self, window: Duration) -> f64 {
+        let mut length = Duration::ZERO;
+        let mut count = 0_u32;
+        let mut energy = 0.0;
+        for (frame_energy, duration) in self.energies.iter().rev() {
+            if length >= window {
+                break;
+            }
+            length += *duration;
+            count += 1;
+            energy += frame_energy;
+        }
+        energy / f64::from(count.max(1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME: Duration = Duration::from_millis(50);
+    const LENGTH: Duration = Duration::from_millis(1_500);
+    const TOLERANCE: Duration = Duration::from_millis(500);
+
+    fn push(planner: &mut TransitionPlanner, energies: &[f64]) {
+        for energy in energies {
+            planner.push_energy(*energy, FRAME);
+        }
+    }
+
+    #[test]
+    fn test_quiet_point() {
+        let mut planner = TransitionPlanner::new(TOLERANCE);
+        push(&mut planner, &[1.0; 40]);
+        assert_eq!(planner.transition(LENGTH), None);
+
+        push(&mut planner, &[1.0]);
+        assert_eq!(planner.transition(LENGTH), None);
+
+        push(&mut planner, &[0.01]);
+        assert_eq!(
+            planner.transition(LENGTH),
+            Some(Transition {
+                point: TransitionPoint::Quiet,
+                length: LENGTH / 2
+            })
+        );
+    }
+
+    #[test]
+    fn test_beat() {
+        // A beat every 0.7s.
+        let mut bar = [1.0; 14];
+        bar[0] = 4.0;
+        let mut planner = TransitionPlanner::new(Duration::from_secs(1));
+        for _ in 0..5 {
+            push(&mut planner, &bar);
+        }
+
+        push(&mut planner, &[1.0; 4]);
+        for _ in 0..8 {
+            push(&mut planner, &[1.0]);
+            assert_eq!(planner.transition(LENGTH), None);
+        }
+
+        push(&mut planner, &[4.0]);
+        assert_eq!(
+            planner.transition(LENGTH),
+            Some(Transition {
+                point: TransitionPoint::Beat,
+                length: Duration::from_millis(1_400)
+            })
+        );
+    }
+
+    #[test]
+    fn test_timeout() {
+        let mut planner = TransitionPlanner::new(TOLERANCE);
+        push(&mut planner, &[1.0; 40]);
+        assert_eq!(planner.transition(LENGTH), None);
+
+        for _ in 0..9 {
+            push(&mut planner, &[1.0]);
+            assert_eq!(planner.transition(LENGTH), None);
+        }
+
+        push(&mut planner, &[1.0]);
+        assert_eq!(
+            planner.transition(LENGTH),
+            Some(Transition {
+                point: TransitionPoint::Timeout,
+                length: LENGTH
+            })
+        );
+
+        // Cancelling starts waiting anew.
+        push(&mut planner, &[1.0]);
+        assert_eq!(planner.transition(LENGTH), None);
+        planner.cancel();
+        for _ in 0..10 {
+            push(&mut planner, &[1.0]);
+            assert_eq!(planner.transition(LENGTH), None);
+        }
+    }
+}
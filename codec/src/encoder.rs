@@ -18,6 +18,9 @@ static OPUS_SAMPLE_FORMAT: SampleFormat = SampleFormat::Flt;
 static AAC_SAMPLE_RATE: u32 = 44_100;
 static AAC_SAMPLE_FORMAT: SampleFormat = SampleFormat::FltPlanar;
 
+static MP3_SAMPLE_RATE: u32 = 44_100;
+static MP3_SAMPLE_FORMAT: SampleFormat = SampleFormat::FltPlanar;
+
 #[non_exhaustive]
 pub struct Encoder<T> {
     encoder: AudioEncoder,
@@ -88,6 +91,37 @@ impl<W: Write> Encoder<W> {
         })
     }
 
+    pub fn mp3(params: CodecParams, output: W) -> anyhow::Result<Self> {
+        let encoder = AudioEncoder::builder("libmp3lame")?
+            .sample_rate(MP3_SAMPLE_RATE)
+            .sample_format(MP3_SAMPLE_FORMAT.into())
+            .bit_rate(params.bit_rate)
+            .channel_layout(params.channel_layout())
+            .build()?;
+
+        let mut muxer_builder = Muxer::builder();
+        muxer_builder.add_stream(&encoder.codec_parameters().into())?;
+
+        let muxer = muxer_builder.build(
+            IO::from_write_stream(output),
+            OutputFormat::find_by_name("mp3").expect("Output format for MP3"),
+        )?;
+
+        let target = {
+            let mut params = CodecParams::from(&encoder.codec_parameters());
+            params.samples_per_frame = encoder.samples_per_frame();
+            params
+        };
+
+        let resampler = Resampler::new(params, target);
+
+        Ok(Self {
+            encoder,
+            muxer,
+            resampler,
+        })
+    }
+
     pub fn push(&mut self, frame: AudioFrame) -> anyhow::Result<&mut Self> {
         for frame in self.resampler.push(frame)? {
             self.encoder.try_push(frame?)?;
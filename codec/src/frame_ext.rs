@@ -1,6 +1,8 @@
 use std::time::Duration;
 
-use ac_ffmpeg::codec::audio::AudioFrame;
+use ac_ffmpeg::codec::audio::{AudioFrame, AudioFrameMut};
+
+use crate::SampleFormat;
 
 pub trait FrameDuration {
     fn duration(&self) -> Duration;
@@ -14,11 +16,44 @@ impl FrameDuration for AudioFrame {
     }
 }
 
+/// Drops the first `samples` of the frame and moves its pts accordingly.
+pub fn trim_start(frame: &AudioFrame, samples: usize) -> AudioFrame {
+    let samples = samples.min(frame.samples());
+    let remaining = frame.samples() - samples;
+
+    let format = SampleFormat::from(frame.sample_format());
+    // Packed formats interleave all channels in the only plane.
+    let sample_size = if format.is_planar() {
+        format.bytes_per_sample()
+    } else {
+        format.bytes_per_sample() * frame.channel_layout().channels() as usize
+    };
+
+    let mut trimmed = AudioFrameMut::silence(
+        frame.channel_layout(),
+        frame.sample_format(),
+        frame.sample_rate(),
+        remaining,
+    );
+    for (target, source) in trimmed.planes_mut().iter_mut().zip(frame.planes().iter()) {
+        let start = samples * sample_size;
+        let end = start + remaining * sample_size;
+        target.data_mut()[..remaining * sample_size].copy_from_slice(&source.data()[start..end]);
+    }
+
+    let skipped = Duration::from_secs_f64(samples as f64 / f64::from(frame.sample_rate()));
+    trimmed
+        .with_time_base(frame.time_base())
+        .with_pts(frame.pts() + skipped)
+        .freeze()
+}
+
 #[cfg(test)]
 mod tests {
     use ac_ffmpeg::codec::audio::{AudioFrameMut, ChannelLayout};
+    use ac_ffmpeg::time::Timestamp;
 
-    use crate::frame_ext::FrameDuration;
+    use crate::frame_ext::{trim_start, FrameDuration};
     use crate::SampleFormat;
 
     #[test]
@@ -33,4 +68,22 @@ mod tests {
 
         assert_eq!(frame.duration().as_millis(), 46);
     }
+
+    #[test]
+    fn test_trim_start() {
+        let mut frame = AudioFrameMut::silence(
+            &ChannelLayout::from_channels(2).unwrap(),
+            SampleFormat::S16.into(),
+            4,
+            4,
+        );
+        frame.planes_mut()[0].data_mut()[..16]
+            .copy_from_slice(&[0, 0, 0, 0, 1, 0, 1, 0, 2, 0, 2, 0, 3, 0, 3, 0]);
+        let frame = frame.with_pts(Timestamp::from_secs(1)).freeze();
+
+        let trimmed = trim_start(&frame, 3);
+        assert_eq!(trimmed.samples(), 1);
+        assert_eq!(&trimmed.planes()[0].data()[..4], &[3, 0, 3, 0]);
+        assert_eq!(trimmed.pts().as_millis(), Some(1_750));
+    }
 }
@@ -7,13 +7,8 @@ pub use ac_ffmpeg::packet::Packet;
 pub use ac_ffmpeg::time::{TimeBase, Timestamp};
 
 use ac_ffmpeg::{
-    codec::audio::AudioFrameMut,
-    codec::audio::ChannelLayout as AcChannelLayout,
-    codec::audio::SampleFormat as AcSampleFormat,
-    codec::audio::{AudioDecoder, AudioResampler},
-    codec::Decoder as AcDecoder,
-    format::demuxer::Demuxer,
-    format::io::IO,
+    codec::audio::AudioFrameMut, codec::audio::AudioResampler,
+    codec::audio::ChannelLayout as AcChannelLayout, codec::audio::SampleFormat as AcSampleFormat,
     set_log_callback,
 };
 
@@ -25,6 +20,15 @@ pub use frame_ext::FrameDuration;
 mod decoder;
 pub use decoder::Decoder;
 
+mod stream;
+pub use stream::{StreamInfo, StreamSelector};
+
+mod probe;
+pub use probe::{probe, Probe};
+
+mod tags;
+pub use tags::Tags;
+
 mod encoder;
 pub use encoder::Encoder;
 
@@ -35,6 +39,12 @@ pub use resampler::{Resampler, ResamplingDecoder};
 mod sample_format;
 pub use sample_format::SampleFormat;
 
+mod sample;
+pub use sample::Sample;
+
+mod samples;
+pub use samples::{FrameSamples, FrameSamplesMut};
+
 mod codec_params;
 pub use codec_params::{CodecParams, CodecParamsBuilder};
 
@@ -69,17 +79,8 @@ pub fn configure_ffmpeg_log() {
 }
 
 pub fn resample_16k_mono_s16_stream<R: Read>(input: R) -> anyhow::Result<Vec<i16>> {
-    let io = IO::from_read_stream(input);
-
-    let mut demuxer = Demuxer::builder()
-        .build(io)?
-        .find_stream_info(None)
-        .map_err(|(_, err)| err)?;
-
-    let params = demuxer.streams()[0].codec_parameters();
-    let source = params.as_audio_codec_parameters().unwrap();
-
-    let mut decoder = AudioDecoder::from_stream(&demuxer.streams()[0])?.build()?;
+    let decoder = Decoder::try_from(input)?;
+    let source = decoder.codec_parameters();
 
     let mut resampler = AudioResampler::builder()
         .source_sample_rate(source.sample_rate())
@@ -93,19 +94,9 @@ pub fn resample_16k_mono_s16_stream<R: Read>(input: R) -> anyhow::Result<Vec<i16
 
     let mut output: Vec<i16> = vec![];
 
-    while let Some(packet) = demuxer.take()? {
-        decoder.push(packet)?;
-        while let Some(frame) = decoder.take()? {
-            resampler.push(frame)?;
-            while let Some(frame) = resampler.take()? {
-                output.extend_from_slice(cast_slice(frame.planes()[0].data()));
-            }
-        }
-    }
-
-    decoder.flush()?;
-    while let Some(frame) = decoder.take()? {
-        resampler.push(frame)?;
+    // The decoder flushes itself at the end of the input.
+    for frame in decoder {
+        resampler.push(frame?)?;
         while let Some(frame) = resampler.take()? {
             output.extend_from_slice(cast_slice(frame.planes()[0].data()));
         }
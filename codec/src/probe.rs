@@ -0,0 +1,33 @@
+use std::io::Cursor;
+use std::time::Duration;
+
+use crate::{Decoder, StreamInfo, StreamSelector, Tags};
+
+/// What a track is, read from the container headers and tags without decoding it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Probe {
+    /// Container format, e.g. `mp3` or `mov,mp4,m4a,3gp,3g2,mj2`.
+    pub format: String,
+    /// The audio stream a `Decoder` would decode.
+    pub stream: StreamInfo,
+    pub tags: Tags,
+}
+
+impl Probe {
+    /// Duration of the headers, or of all frames if the container does not tell it.
+    pub fn duration(&self, input: &[u8]) -> anyhow::Result<Duration> {
+        self.stream
+            .duration
+            .map_or_else(|| crate::track_duration(input), Ok)
+    }
+}
+
+pub fn probe(input: &[u8]) -> anyhow::Result<Probe> {
+    let decoder = Decoder::try_from_seekable(Cursor::new(input), StreamSelector::Best)?;
+
+    Ok(Probe {
+        format: decoder.format_name(),
+        stream: decoder.stream(),
+        tags: Tags::read(input),
+    })
+}
@@ -0,0 +1,109 @@
+use bytemuck::Pod;
+
+use crate::SampleFormat;
+
+/// Sample type of a format, DSP works on `f32` values in `-1.0..=1.0`.
+pub trait Sample: Pod {
+    /// Packed format of the type, `FORMAT.planar()` is the planar one.
+    const FORMAT: SampleFormat;
+
+    fn to_f32(self) -> f32;
+
+    /// Saturates values out of range.
+    fn from_f32(value: f32) -> Self;
+}
+
+impl Sample for u8 {
+    const FORMAT: SampleFormat = SampleFormat::U8;
+
+    fn to_f32(self) -> f32 {
+        (f32::from(self) - 128.0) / 128.0
+    }
+
+    fn from_f32(value: f32) -> Self {
+        value.mul_add(128.0, 128.0).round() as Self
+    }
+}
+
+impl Sample for i16 {
+    const FORMAT: SampleFormat = SampleFormat::S16;
+
+    fn to_f32(self) -> f32 {
+        f32::from(self) / 32_768.0
+    }
+
+    fn from_f32(value: f32) -> Self {
+        (value * 32_768.0).round() as Self
+    }
+}
+
+impl Sample for i32 {
+    const FORMAT: SampleFormat = SampleFormat::S32;
+
+    fn to_f32(self) -> f32 {
+        (f64::from(self) / 2_147_483_648.0) as f32
+    }
+
+    fn from_f32(value: f32) -> Self {
+        (f64::from(value) * 2_147_483_648.0).round() as Self
+    }
+}
+
+impl Sample for i64 {
+    const FORMAT: SampleFormat = SampleFormat::S64;
+
+    fn to_f32(self) -> f32 {
+        (self as f64 / 9_223_372_036_854_775_808.0) as f32
+    }
+
+    fn from_f32(value: f32) -> Self {
+        (f64::from(value) * 9_223_372_036_854_775_808.0).round() as Self
+    }
+}
+
+impl Sample for f32 {
+    const FORMAT: SampleFormat = SampleFormat::Flt;
+
+    fn to_f32(self) -> f32 {
+        self
+    }
+
+    fn from_f32(value: f32) -> Self {
+        value
+    }
+}
+
+impl Sample for f64 {
+    const FORMAT: SampleFormat = SampleFormat::Dbl;
+
+    fn to_f32(self) -> f32 {
+        self as f32
+    }
+
+    fn from_f32(value: f32) -> Self {
+        Self::from(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[allow(clippy::float_cmp)]
+    #[test]
+    fn test_conversions() {
+        assert_eq!(u8::from_f32(0.0), 128);
+        assert_eq!(u8::from_f32(-1.0), 0);
+        assert_eq!(u8::from_f32(2.0), 255);
+        assert_eq!(0_u8.to_f32(), -1.0);
+
+        assert_eq!(i16::from_f32(0.5), 16_384);
+        assert_eq!(i16::from_f32(1.0), i16::MAX);
+        assert_eq!(i16::MIN.to_f32(), -1.0);
+
+        assert_eq!(i32::from_f32(-1.0), i32::MIN);
+        assert_eq!(i32::from_f32(0.25).to_f32(), 0.25);
+        assert_eq!(i64::from_f32(-0.5).to_f32(), -0.5);
+        assert_eq!(f64::from_f32(0.75).to_f32(), 0.75);
+    }
+}
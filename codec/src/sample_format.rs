@@ -2,30 +2,159 @@ use std::str::FromStr;
 
 use ac_ffmpeg::codec::audio::SampleFormat as AcSampleFormat;
 
+/// Sample formats of ffmpeg, packed ones interleave channels in a single plane.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum SampleFormat {
+    U8,
     S16,
+    S32,
+    S64,
     Flt,
+    Dbl,
+    U8Planar,
+    S16Planar,
+    S32Planar,
+    S64Planar,
     FltPlanar,
+    DblPlanar,
+}
+
+impl SampleFormat {
+    pub const ALL: [Self; 12] = [
+        Self::U8,
+        Self::S16,
+        Self::S32,
+        Self::S64,
+        Self::Flt,
+        Self::Dbl,
+        Self::U8Planar,
+        Self::S16Planar,
+        Self::S32Planar,
+        Self::S64Planar,
+        Self::FltPlanar,
+        Self::DblPlanar,
+    ];
+
+    /// Name of the format in ffmpeg.
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::U8 => "u8",
+            Self::S16 => "s16",
+            Self::S32 => "s32",
+            Self::S64 => "s64",
+            Self::Flt => "flt",
+            Self::Dbl => "dbl",
+            Self::U8Planar => "u8p",
+            Self::S16Planar => "s16p",
+            Self::S32Planar => "s32p",
+            Self::S64Planar => "s64p",
+            Self::FltPlanar => "fltp",
+            Self::DblPlanar => "dblp",
+        }
+    }
+
+    /// Each channel has its own plane.
+    #[must_use]
+    pub const fn is_planar(self) -> bool {
+        matches!(
+            self,
+            Self::U8Planar
+                | Self::S16Planar
+                | Self::S32Planar
+                | Self::S64Planar
+                | Self::FltPlanar
+                | Self::DblPlanar
+        )
+    }
+
+    #[must_use]
+    pub const fn bytes_per_sample(self) -> usize {
+        match self.packed() {
+            Self::U8 => 1,
+            Self::S16 => 2,
+            Self::S32 | Self::Flt => 4,
+            _ => 8,
+        }
+    }
+
+    /// The same samples with interleaved channels.
+    #[must_use]
+    pub const fn packed(self) -> Self {
+        match self {
+            Self::U8Planar => Self::U8,
+            Self::S16Planar => Self::S16,
+            Self::S32Planar => Self::S32,
+            Self::S64Planar => Self::S64,
+            Self::FltPlanar => Self::Flt,
+            Self::DblPlanar => Self::Dbl,
+            packed => packed,
+        }
+    }
+
+    /// The same samples with a plane per channel.
+    #[must_use]
+    pub const fn planar(self) -> Self {
+        match self {
+            Self::U8 => Self::U8Planar,
+            Self::S16 => Self::S16Planar,
+            Self::S32 => Self::S32Planar,
+            Self::S64 => Self::S64Planar,
+            Self::Flt => Self::FltPlanar,
+            Self::Dbl => Self::DblPlanar,
+            planar => planar,
+        }
+    }
+}
+
+impl FromStr for SampleFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|format| format.name() == name)
+            .ok_or_else(|| anyhow::anyhow!("Unknown sample format {name}"))
+    }
 }
 
 impl From<SampleFormat> for AcSampleFormat {
     fn from(format: SampleFormat) -> Self {
-        match format {
-            SampleFormat::S16 => Self::from_str("s16").expect("s16"),
-            SampleFormat::Flt => Self::from_str("flt").expect("flt"),
-            SampleFormat::FltPlanar => Self::from_str("fltp").expect("flt"),
-        }
+        Self::from_str(format.name()).expect("FFMpeg sample format")
     }
 }
 
 impl From<AcSampleFormat> for SampleFormat {
     fn from(format: AcSampleFormat) -> Self {
-        match format.name() {
-            "s16" => Self::S16,
-            "flt" => Self::Flt,
-            "fltp" => Self::FltPlanar,
-            x => unreachable!("Unknown format {}", x),
+        // FFMpeg has no other formats, but `none` of an unset frame.
+        format
+            .name()
+            .parse()
+            .unwrap_or_else(|err| unreachable!("{err}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names() {
+        for format in SampleFormat::ALL {
+            assert_eq!(format.name().parse::<SampleFormat>().unwrap(), format);
+            assert_eq!(SampleFormat::from(AcSampleFormat::from(format)), format);
+            assert_eq!(AcSampleFormat::from(format).is_planar(), format.is_planar());
         }
+        assert!("none".parse::<SampleFormat>().is_err());
+    }
+
+    #[test]
+    fn test_layouts() {
+        assert_eq!(SampleFormat::S16Planar.packed(), SampleFormat::S16);
+        assert_eq!(SampleFormat::Dbl.planar(), SampleFormat::DblPlanar);
+        assert_eq!(SampleFormat::FltPlanar.planar(), SampleFormat::FltPlanar);
+        assert_eq!(SampleFormat::U8Planar.bytes_per_sample(), 1);
+        assert_eq!(SampleFormat::S32.bytes_per_sample(), 4);
+        assert_eq!(SampleFormat::S64Planar.bytes_per_sample(), 8);
     }
 }
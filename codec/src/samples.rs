@@ -0,0 +1,178 @@
+//! Typed access to frame samples, whatever the sample format and channel layout.
+
+use ac_ffmpeg::codec::audio::frame::Plane;
+use ac_ffmpeg::codec::audio::{AudioFrame, AudioFrameMut};
+use bytemuck::{cast, cast_slice, cast_slice_mut};
+
+use crate::{Sample, SampleFormat};
+
+pub trait FrameSamples {
+    /// Samples of each channel converted to `T`, e.g. `frame.channel_samples::<f32>()`.
+    fn channel_samples<T: Sample>(&self) -> Vec<Vec<T>>;
+}
+
+pub trait FrameSamplesMut {
+    /// Replaces samples of each channel, converted from `T` to the frame format.
+    fn set_channel_samples<T: Sample>(&mut self, channels: &[Vec<T>]);
+}
+
+impl FrameSamples for AudioFrame {
+    fn channel_samples<T: Sample>(&self) -> Vec<Vec<T>> {
+        let layout = Layout {
+            format: self.sample_format().into(),
+            channels: self.channel_layout().channels() as usize,
+            samples: self.samples(),
+        };
+        read(&self.planes(), layout)
+    }
+}
+
+impl FrameSamples for AudioFrameMut {
+    fn channel_samples<T: Sample>(&self) -> Vec<Vec<T>> {
+        let layout = Layout {
+            format: self.sample_format().into(),
+            channels: self.channel_layout().channels() as usize,
+            samples: self.samples(),
+        };
+        read(&self.planes(), layout)
+    }
+}
+
+impl FrameSamplesMut for AudioFrameMut {
+    fn set_channel_samples<T: Sample>(&mut self, channels: &[Vec<T>]) {
+        let layout = Layout {
+            format: self.sample_format().into(),
+            channels: self.channel_layout().channels() as usize,
+            samples: self.samples(),
+        };
+        write(&mut self.planes_mut(), layout, channels);
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Layout {
+    format: SampleFormat,
+    channels: usize,
+    samples: usize,
+}
+
+impl Layout {
+    // Plane of the channel, the first sample and the distance between samples in it.
+    const fn position(&self, channel: usize) -> (usize, usize, usize) {
+        if self.format.is_planar() {
+            (channel, 0, 1)
+        } else {
+            (0, channel, self.channels)
+        }
+    }
+}
+
+fn read<T: Sample>(planes: &[Plane], layout: Layout) -> Vec<Vec<T>> {
+    match layout.format.packed() {
+        SampleFormat::U8 => read_as::<u8, T>(planes, layout),
+        SampleFormat::S16 => read_as::<i16, T>(planes, layout),
+        SampleFormat::S32 => read_as::<i32, T>(planes, layout),
+        SampleFormat::S64 => read_as::<i64, T>(planes, layout),
+        SampleFormat::Dbl => read_as::<f64, T>(planes, layout),
+        _ => read_as::<f32, T>(planes, layout),
+    }
+}
+
+fn read_as<S: Sample, T: Sample>(planes: &[Plane], layout: Layout) -> Vec<Vec<T>> {
+    (0..layout.channels)
+        .map(|channel| {
+            let (plane, first, step) = layout.position(channel);
+            cast_slice::<_, S>(planes[plane].data())
+                .iter()
+                .skip(first)
+                .step_by(step)
+                .take(layout.samples)
+                .map(|sample| convert(*sample))
+                .collect()
+        })
+        .collect()
+}
+
+fn write<T: Sample>(planes: &mut [Plane], layout: Layout, channels: &[Vec<T>]) {
+    match layout.format.packed() {
+        SampleFormat::U8 => write_as::<u8, T>(planes, layout, channels),
+        SampleFormat::S16 => write_as::<i16, T>(planes, layout, channels),
+        SampleFormat::S32 => write_as::<i32, T>(planes, layout, channels),
+        SampleFormat::S64 => write_as::<i64, T>(planes, layout, channels),
+        SampleFormat::Dbl => write_as::<f64, T>(planes, layout, channels),
+        _ => write_as::<f32, T>(planes, layout, channels),
+    }
+}
+
+fn write_as<S: Sample, T: Sample>(planes: &mut [Plane], layout: Layout, channels: &[Vec<T>]) {
+    for (channel, samples) in channels.iter().enumerate().take(layout.channels) {
+        let (plane, first, step) = layout.position(channel);
+        let data = cast_slice_mut::<_, S>(planes[plane].data_mut());
+        for (target, sample) in data
+            .iter_mut()
+            .skip(first)
+            .step_by(step)
+            .zip(samples.iter().take(layout.samples))
+        {
+            *target = convert(*sample);
+        }
+    }
+}
+
+fn convert<S: Sample, T: Sample>(sample: S) -> T {
+    if S::FORMAT == T::FORMAT {
+        cast(sample)
+    } else {
+        T::from_f32(sample.to_f32())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ac_ffmpeg::codec::audio::ChannelLayout;
+
+    use super::*;
+
+    fn stereo(format: SampleFormat) -> AudioFrameMut {
+        AudioFrameMut::silence(
+            &ChannelLayout::from_channels(2).unwrap(),
+            format.into(),
+            48_000,
+            3,
+        )
+    }
+
+    #[test]
+    fn test_packed() {
+        let mut frame = stereo(SampleFormat::S16);
+        cast_slice_mut::<_, i16>(frame.planes_mut()[0].data_mut())[..6]
+            .copy_from_slice(&[1, -1, 2, -2, 3, -3]);
+
+        assert_eq!(
+            frame.channel_samples::<i16>(),
+            vec![vec![1, 2, 3], vec![-1, -2, -3]]
+        );
+
+        frame.set_channel_samples(&[vec![0.5_f32, 0.0, -0.5], vec![0.25, 0.0, -0.25]]);
+        assert_eq!(
+            &cast_slice::<_, i16>(frame.planes()[0].data())[..6],
+            &[16_384, 8_192, 0, 0, -16_384, -8_192]
+        );
+    }
+
+    #[allow(clippy::float_cmp)]
+    #[test]
+    fn test_planar() {
+        let mut frame = stereo(SampleFormat::FltPlanar);
+        frame.set_channel_samples(&[vec![0.5_f32, 0.25, 0.0], vec![-0.5, -0.25, 0.0]]);
+
+        assert_eq!(
+            &cast_slice::<_, f32>(frame.planes()[1].data())[..3],
+            &[-0.5, -0.25, 0.0]
+        );
+        assert_eq!(
+            frame.freeze().channel_samples::<i16>(),
+            vec![vec![16_384, 8_192, 0], vec![-16_384, -8_192, 0]]
+        );
+    }
+}
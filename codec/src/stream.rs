@@ -0,0 +1,155 @@
+use std::time::Duration;
+
+use ac_ffmpeg::codec::audio::{ChannelLayout, ChannelLayoutRef};
+use ac_ffmpeg::format::stream::Stream;
+
+// FFmpeg names of the common channel layouts, ac-ffmpeg cannot name a layout itself.
+static CHANNEL_LAYOUTS: [&str; 19] = [
+    "mono",
+    "stereo",
+    "2.1",
+    "3.0",
+    "3.0(back)",
+    "4.0",
+    "quad",
+    "quad(side)",
+    "3.1",
+    "4.1",
+    "5.0",
+    "5.0(side)",
+    "5.1",
+    "5.1(side)",
+    "6.0",
+    "6.1",
+    "7.0",
+    "7.1",
+    "7.1(wide)",
+];
+
+/// Which audio stream of a container `Decoder` decodes.
+///
+/// There is no selection by language, see `StreamInfo`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StreamSelector {
+    /// The first decodable audio stream with the highest bit rate.
+    #[default]
+    Best,
+    /// Index of the stream in the container.
+    Index(usize),
+    /// Container specific id of the stream, e.g. the PID in MPEG-TS.
+    Id(i32),
+}
+
+/// Audio stream of a container.
+///
+/// The language is not known, ac-ffmpeg gives no access to the stream metadata,
+/// so streams are not selected by it either.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamInfo {
+    pub index: usize,
+    pub id: i32,
+    /// Name of the decoder, `None` if the codec is not supported.
+    pub codec: Option<&'static str>,
+    pub sample_rate: u32,
+    pub channels: u32,
+    /// Name of the channel layout, e.g. `5.1`, `None` if it is not a common one.
+    pub channel_layout: Option<&'static str>,
+    pub bit_rate: u64,
+    pub duration: Option<Duration>,
+}
+
+impl StreamInfo {
+    /// Audio streams only, video, data and subtitles are left out.
+    pub(crate) fn audio_streams(streams: &[Stream]) -> Vec<Self> {
+        streams
+            .iter()
+            .enumerate()
+            .filter_map(|(index, stream)| {
+                let params = stream.codec_parameters();
+                let audio = params.as_audio_codec_parameters()?;
+                let duration = stream
+                    .duration()
+                    .as_micros()
+                    .and_then(|micros| u64::try_from(micros).ok())
+                    .map(Duration::from_micros);
+
+                Some(Self {
+                    index,
+                    id: stream.stream_id(),
+                    codec: audio.decoder_name(),
+                    sample_rate: audio.sample_rate(),
+                    channels: audio.channel_layout().channels(),
+                    channel_layout: channel_layout_name(audio.channel_layout()),
+                    bit_rate: audio.bit_rate(),
+                    duration,
+                })
+            })
+            .collect()
+    }
+
+    const fn is_decodable(&self) -> bool {
+        self.codec.is_some() && self.sample_rate > 0 && self.channels > 0
+    }
+}
+
+fn channel_layout_name(layout: &ChannelLayoutRef) -> Option<&'static str> {
+    CHANNEL_LAYOUTS.into_iter().find(|name| {
+        name.parse::<ChannelLayout>()
+            .is_ok_and(|known| known == *layout)
+    })
+}
+
+impl StreamSelector {
+    /// Index of the selected stream among the audio `streams`.
+    pub(crate) fn select(self, streams: &[StreamInfo]) -> anyhow::Result<usize> {
+        let stream = match self {
+            // Reversed, so the first one wins a tie.
+            Self::Best => streams
+                .iter()
+                .rev()
+                .filter(|stream| stream.is_decodable())
+                .max_by_key(|stream| stream.bit_rate),
+            Self::Index(index) => streams.iter().find(|stream| stream.index == index),
+            Self::Id(id) => streams.iter().find(|stream| stream.id == id),
+        };
+
+        stream
+            .map(|stream| stream.index)
+            .ok_or_else(|| anyhow::anyhow!("No audio stream matches {self:?}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stream(index: usize, codec: Option<&'static str>, bit_rate: u64) -> StreamInfo {
+        StreamInfo {
+            index,
+            id: 0x100 + i32::try_from(index).unwrap(),
+            codec,
+            sample_rate: 48_000,
+            channels: 2,
+            channel_layout: Some("stereo"),
+            bit_rate,
+            duration: None,
+        }
+    }
+
+    #[test]
+    fn test_select() {
+        // Stream 0 is video, so it is not listed.
+        let streams = vec![
+            stream(1, None, 384_000),
+            stream(2, Some("mp2"), 192_000),
+            stream(3, Some("aac"), 64_000),
+            stream(4, Some("mp2"), 192_000),
+        ];
+
+        assert_eq!(StreamSelector::Best.select(&streams).unwrap(), 2);
+        assert_eq!(StreamSelector::Index(3).select(&streams).unwrap(), 3);
+        assert_eq!(StreamSelector::Id(0x104).select(&streams).unwrap(), 4);
+        assert!(StreamSelector::Index(0).select(&streams).is_err());
+        assert!(StreamSelector::Best.select(&[]).is_err());
+    }
+}
@@ -0,0 +1,401 @@
+//! Track tags read from the raw file, as the `ac_ffmpeg` bindings give no access to metadata.
+
+// Ogg comment headers follow the identification header in the first pages.
+const OGG_SEARCH_LIMIT: usize = 64 * 1024;
+const ID3V1_SIZE: usize = 128;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Tags {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+}
+
+impl Tags {
+    /// Tags of `ID3v2`, Vorbis comments (FLAC and Ogg), MP4 or `ID3v1`, the first found wins.
+    #[must_use]
+    pub fn read(data: &[u8]) -> Self {
+        let mut tags = Self::default();
+        for found in [id3v2(data), flac(data), ogg(data), mp4(data), id3v1(data)]
+            .into_iter()
+            .flatten()
+        {
+            tags.merge(found);
+        }
+        tags
+    }
+
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.title.is_none() && self.artist.is_none() && self.album.is_none()
+    }
+
+    fn merge(&mut self, other: Self) {
+        self.title = self.title.take().or(other.title);
+        self.artist = self.artist.take().or(other.artist);
+        self.album = self.album.take().or(other.album);
+    }
+
+    fn set(&mut self, key: &str, value: &str) {
+        let value = value.trim_matches(|c: char| c == '\0' || c.is_whitespace());
+        if value.is_empty() {
+            return;
+        }
+
+        let field = match key {
+            "title" => &mut self.title,
+            "artist" => &mut self.artist,
+            "album" => &mut self.album,
+            _ => return,
+        };
+        field.get_or_insert_with(|| value.to_string());
+    }
+}
+
+fn id3v2(data: &[u8]) -> Option<Tags> {
+    if data.get(..3)? != b"ID3" {
+        return None;
+    }
+    let version = *data.get(3)?;
+    let flags = *data.get(5)?;
+    let size = syncsafe(data.get(6..10)?);
+    let mut body = data.get(10..10 + size)?.to_vec();
+
+    // Unsynchronisation inserts a zero after every 0xFF.
+    if flags & 0x80 != 0 {
+        body = body
+            .iter()
+            .enumerate()
+            .filter(|(i, byte)| **byte != 0 || *i == 0 || body[i - 1] != 0xFF)
+            .map(|(_, byte)| *byte)
+            .collect();
+    }
+
+    let mut position = if flags & 0x40 == 0 {
+        0
+    } else if version == 4 {
+        syncsafe(body.get(..4)?)
+    } else {
+        be(body.get(..4)?) + 4
+    };
+
+    // ID3v2.2 has three letter ids and sizes.
+    let (id_size, header_size) = if version == 2 { (3, 6) } else { (4, 10) };
+
+    let mut tags = Tags::default();
+    while let Some(header) = body.get(position..position + header_size) {
+        let id = &header[..id_size];
+        if id[0] == 0 {
+            // Padding.
+            break;
+        }
+        let size_bytes = &header[id_size..id_size * 2];
+        let size = if version == 4 {
+            syncsafe(size_bytes)
+        } else {
+            be(size_bytes)
+        };
+
+        let frame = body.get(position + header_size..position + header_size + size)?;
+        let key = match id {
+            b"TIT2" | b"TT2" => "title",
+            b"TPE1" | b"TP1" => "artist",
+            b"TALB" | b"TAL" => "album",
+            _ => "",
+        };
+        if let Some((encoding, text)) = frame.split_first() {
+            tags.set(key, &decode_id3_text(*encoding, text));
+        }
+
+        position += header_size + size;
+    }
+
+    Some(tags)
+}
+
+fn decode_id3_text(encoding: u8, text: &[u8]) -> String {
+    let text = match encoding {
+        0 => latin1(text),
+        1 | 2 => {
+            let big_endian = match text.get(..2) {
+                Some([0xFF, 0xFE]) => false,
+                Some([0xFE, 0xFF]) => true,
+                _ => encoding == 2,
+            };
+            let text = if encoding == 1 && text.len() >= 2 {
+                &text[2..]
+            } else {
+                text
+            };
+            let units = text.chunks_exact(2).map(|pair| {
+                if big_endian {
+                    u16::from_be_bytes([pair[0], pair[1]])
+                } else {
+                    u16::from_le_bytes([pair[0], pair[1]])
+                }
+            });
+            char::decode_utf16(units)
+                .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+                .collect()
+        }
+        _ => String::from_utf8_lossy(text).into_owned(),
+    };
+
+    // ID3v2.4 separates multiple values with zeros, the first one is enough.
+    text.split('\0').next().unwrap_or_default().to_string()
+}
+
+fn id3v1(data: &[u8]) -> Option<Tags> {
+    let tag = data.get(data.len().checked_sub(ID3V1_SIZE)?..)?;
+    if &tag[..3] != b"TAG" {
+        return None;
+    }
+
+    let mut tags = Tags::default();
+    tags.set("title", &latin1(&tag[3..33]));
+    tags.set("artist", &latin1(&tag[33..63]));
+    tags.set("album", &latin1(&tag[63..93]));
+    Some(tags)
+}
+
+fn flac(data: &[u8]) -> Option<Tags> {
+    if data.get(..4)? != b"fLaC" {
+        return None;
+    }
+
+    let mut position = 4;
+    loop {
+        let header = data.get(position..position + 4)?;
+        let size = be(&header[1..]);
+        let block = data.get(position + 4..position + 4 + size)?;
+        // Block type 4 is VORBIS_COMMENT.
+        if header[0] & 0x7F == 4 {
+            return vorbis_comment(block);
+        }
+        if header[0] & 0x80 != 0 {
+            return None;
+        }
+        position += 4 + size;
+    }
+}
+
+fn ogg(data: &[u8]) -> Option<Tags> {
+    if data.get(..4)? != b"OggS" {
+        return None;
+    }
+
+    let head = &data[..data.len().min(OGG_SEARCH_LIMIT)];
+    [b"\x03vorbis".as_slice(), b"OpusTags".as_slice()]
+        .iter()
+        .find_map(|magic| {
+            let start = find(head, magic)?;
+            vorbis_comment(&data[start + magic.len()..])
+        })
+}
+
+// Vendor string and `KEY=value` pairs, all little endian length prefixed.
+fn vorbis_comment(data: &[u8]) -> Option<Tags> {
+    let vendor = le(data.get(..4)?);
+    let mut position = 4 + vendor;
+    let count = le(data.get(position..position + 4)?);
+    position += 4;
+
+    let mut tags = Tags::default();
+    for _ in 0..count {
+        let size = le(data.get(position..position + 4)?);
+        let comment = data.get(position + 4..position + 4 + size)?;
+        if let Some((key, value)) = String::from_utf8_lossy(comment).split_once('=') {
+            tags.set(&key.to_ascii_lowercase(), value);
+        }
+        position += 4 + size;
+    }
+
+    Some(tags)
+}
+
+fn mp4(data: &[u8]) -> Option<Tags> {
+    if data.get(4..8)? != b"ftyp" {
+        return None;
+    }
+
+    let moov = mp4_box(data, *b"moov")?;
+    let meta = mp4_box(mp4_box(moov, *b"udta")?, *b"meta")?;
+    // `meta` is a full box, version and flags come first.
+    let ilst = mp4_box(meta.get(4..)?, *b"ilst")?;
+
+    let mut tags = Tags::default();
+    for (kind, key) in [
+        (*b"\xa9nam", "title"),
+        (*b"\xa9ART", "artist"),
+        (*b"\xa9alb", "album"),
+    ] {
+        // `data` box has type and locale before the text.
+        if let Some(value) = mp4_box(ilst, kind)
+            .and_then(|item| mp4_box(item, *b"data"))
+            .and_then(|data| data.get(8..))
+        {
+            tags.set(key, &String::from_utf8_lossy(value));
+        }
+    }
+
+    Some(tags)
+}
+
+// Content of the first child box of the kind.
+fn mp4_box(data: &[u8], kind: [u8; 4]) -> Option<&[u8]> {
+    let mut position = 0_usize;
+    while let Some(header) = data.get(position..position.checked_add(8)?) {
+        let (size, header_size) = match be(&header[..4]) {
+            0 => (data.len() - position, 8),
+            1 => {
+                let size = data.get(position + 8..position.checked_add(16)?)?;
+                (
+                    usize::try_from(u64::from_be_bytes(size.try_into().ok()?)).ok()?,
+                    16,
+                )
+            }
+            size => (size, 8),
+        };
+        if size < header_size {
+            return None;
+        }
+
+        // Sizes come from the file, a broken one must not overflow.
+        let end = position.checked_add(size)?;
+        if header[4..] == kind {
+            return data.get(position + header_size..end);
+        }
+        position = end;
+    }
+    None
+}
+
+fn find(data: &[u8], pattern: &[u8]) -> Option<usize> {
+    data.windows(pattern.len())
+        .position(|window| window == pattern)
+}
+
+fn latin1(data: &[u8]) -> String {
+    data.iter().map(|byte| char::from(*byte)).collect()
+}
+
+fn syncsafe(bytes: &[u8]) -> usize {
+    bytes
+        .iter()
+        .fold(0, |size, byte| (size << 7) | usize::from(byte & 0x7F))
+}
+
+fn be(bytes: &[u8]) -> usize {
+    bytes
+        .iter()
+        .fold(0, |size, byte| (size << 8) | usize::from(*byte))
+}
+
+fn le(bytes: &[u8]) -> usize {
+    bytes
+        .iter()
+        .rev()
+        .fold(0, |size, byte| (size << 8) | usize::from(*byte))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id3v2_frame(id: [u8; 4], text: &[u8]) -> Vec<u8> {
+        let mut frame = id.to_vec();
+        frame.extend(u32::try_from(text.len()).unwrap().to_be_bytes());
+        frame.extend([0, 0]);
+        frame.extend(text);
+        frame
+    }
+
+    fn box_of(kind: [u8; 4], content: &[u8]) -> Vec<u8> {
+        let mut data = u32::try_from(content.len() + 8)
+            .unwrap()
+            .to_be_bytes()
+            .to_vec();
+        data.extend(kind);
+        data.extend(content);
+        data
+    }
+
+    #[test]
+    fn test_id3() {
+        let mut frames = id3v2_frame(*b"TIT2", b"\x00Jingle");
+        frames.extend(id3v2_frame(*b"TPE1", b"\x01\xFF\xFEA\x00d\x00"));
+        frames.extend([0; 10]);
+
+        let mut data = b"ID3\x03\x00\x00".to_vec();
+        data.extend([0, 0, 0, u8::try_from(frames.len()).unwrap()]);
+        data.extend(frames);
+        data.extend([0xFF; 100]);
+
+        let mut id3v1 = b"TAG".to_vec();
+        id3v1.extend(*b"Title v1");
+        id3v1.resize(63, 0);
+        id3v1.extend(*b"Album v1");
+        id3v1.resize(ID3V1_SIZE, 0);
+        data.extend(id3v1);
+
+        assert_eq!(
+            Tags::read(&data),
+            Tags {
+                title: Some("Jingle".to_string()),
+                artist: Some("Ad".to_string()),
+                album: Some("Album v1".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn test_flac() {
+        let mut comment = 6_u32.to_le_bytes().to_vec();
+        comment.extend(*b"vendor");
+        comment.extend(2_u32.to_le_bytes());
+        for entry in [b"TITLE=Spot".as_slice(), b"Artist=Brand".as_slice()] {
+            comment.extend(u32::try_from(entry.len()).unwrap().to_le_bytes());
+            comment.extend(entry);
+        }
+
+        // STREAMINFO block, then the last block with comments.
+        let mut data = b"fLaC\x00\x00\x00\x02ab".to_vec();
+        data.push(0x84);
+        data.extend(&u32::try_from(comment.len()).unwrap().to_be_bytes()[1..]);
+        data.extend(comment);
+
+        let tags = Tags::read(&data);
+        assert_eq!(tags.title.as_deref(), Some("Spot"));
+        assert_eq!(tags.artist.as_deref(), Some("Brand"));
+        assert_eq!(tags.album, None);
+    }
+
+    #[test]
+    fn test_mp4() {
+        let mut text = vec![0, 0, 0, 1, 0, 0, 0, 0];
+        text.extend(*b"Promo");
+        let ilst = box_of(*b"ilst", &box_of(*b"\xa9nam", &box_of(*b"data", &text)));
+        let mut meta = vec![0; 4];
+        meta.extend(ilst);
+        let moov = box_of(*b"moov", &box_of(*b"udta", &box_of(*b"meta", &meta)));
+
+        let mut data = box_of(*b"ftyp", b"M4A \x00\x00\x00\x00");
+        data.extend(box_of(*b"mdat", &[0; 16]));
+        data.extend(moov);
+
+        assert_eq!(Tags::read(&data).title.as_deref(), Some("Promo"));
+        assert!(Tags::read(b"no tags at all").is_empty());
+    }
+
+    #[test]
+    fn test_mp4_box_overflow() {
+        // A 64-bit size reaching past any file.
+        let mut data = 1_u32.to_be_bytes().to_vec();
+        data.extend(*b"free");
+        data.extend(u64::MAX.to_be_bytes());
+        data.extend(box_of(*b"moov", &[]));
+
+        assert_eq!(mp4_box(&data, *b"moov"), None);
+        assert_eq!(mp4_box(&data, *b"free"), None);
+    }
+}
@@ -0,0 +1,15 @@
+CREATE TABLE tracks (
+    "id"        TEXT NOT NULL UNIQUE,
+    "name"      TEXT NOT NULL,
+    "content"   BLOB NOT NULL,
+    "added"     TEXT NOT NULL,
+    "duration"  INTEGER NOT NULL,
+    PRIMARY KEY("id")
+);
+
+CREATE TABLE playbacks (
+    "client_id" TEXT NOT NULL,
+    "track_id"  TEXT NOT NULL,
+    "started"   TEXT NOT NULL,
+    "finished"  TEXT NOT NULL
+);
@@ -0,0 +1,4 @@
+-- Levels of the stream when a playback finished, NULL for silence or older records.
+ALTER TABLE playbacks ADD COLUMN "original_loudness" REAL;
+ALTER TABLE playbacks ADD COLUMN "processed_loudness" REAL;
+ALTER TABLE playbacks ADD COLUMN "processed_peak" REAL;
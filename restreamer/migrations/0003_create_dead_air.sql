@@ -0,0 +1,6 @@
+-- Dead air of sources, `finished` is NULL while it goes on.
+CREATE TABLE dead_air (
+    "source"    TEXT NOT NULL,
+    "started"   TEXT NOT NULL,
+    "finished"  TEXT
+);
@@ -24,6 +24,7 @@ impl Header for Accept {
                 v.to_str()
                     .ok()?
                     .split(',')
+                    .map(str::trim)
                     .map(Mime::from_str)
                     .collect::<Result<Vec<_>, _>>()
                     .ok()
@@ -44,6 +45,67 @@ impl Header for Accept {
     }
 }
 
+impl Accept {
+    /// Quality of `mime` for the client, taken from the most specific matching media range.
+    /// Returns 0.0 if no range matches.
+    pub fn quality(&self, mime: &Mime) -> f32 {
+        self.0
+            .iter()
+            .filter_map(|range| specificity(range, mime).map(|s| (s, range)))
+            .max_by_key(|(specificity, _)| *specificity)
+            .map_or(0.0, |(_, range)| range_quality(range))
+    }
+
+    /// Picks the item with the highest quality, ties are resolved by the order of `items`.
+    pub fn negotiate<T, F>(&self, items: impl IntoIterator<Item = T>, mime: F) -> Option<T>
+    where
+        F: Fn(&T) -> Mime,
+    {
+        items
+            .into_iter()
+            .map(|item| (self.quality(&mime(&item)), item))
+            .filter(|(q, _)| *q > 0.0)
+            .fold(None, |best: Option<(f32, T)>, (q, item)| match best {
+                Some((best_q, _)) if best_q >= q => best,
+                _ => Some((q, item)),
+            })
+            .map(|(_, item)| item)
+    }
+}
+
+impl From<Vec<Mime>> for Accept {
+    fn from(mimes: Vec<Mime>) -> Self {
+        Self(mimes)
+    }
+}
+
+impl Default for Accept {
+    fn default() -> Self {
+        Self(vec![mime::STAR_STAR])
+    }
+}
+
+fn specificity(range: &Mime, mime: &Mime) -> Option<u8> {
+    if range.type_() == mime::STAR {
+        Some(0)
+    } else if range.type_() != mime.type_() {
+        None
+    } else if range.subtype() == mime::STAR {
+        Some(1)
+    } else if range.subtype() == mime.subtype() {
+        Some(2)
+    } else {
+        None
+    }
+}
+
+fn range_quality(range: &Mime) -> f32 {
+    range
+        .get_param("q")
+        .and_then(|q| q.as_str().parse::<f32>().ok())
+        .map_or(1.0, |q| q.clamp(0.0, 1.0))
+}
+
 impl fmt::Display for Accept {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         fmt::Display::fmt(
@@ -80,4 +142,65 @@ mod tests {
 
         assert_eq!(accept, expexted);
     }
+
+    #[test]
+    fn test_parse_with_spaces() {
+        let input = "audio/aac, audio/mpeg;q=0.5";
+        let accept =
+            Accept::decode(&mut std::iter::once(&HeaderValue::from_str(input).unwrap())).unwrap();
+
+        let expexted = Accept(vec![
+            Mime::from_str("audio/aac").unwrap(),
+            Mime::from_str("audio/mpeg;q=0.5").unwrap(),
+        ]);
+
+        assert_eq!(accept, expexted);
+    }
+
+    #[allow(clippy::float_cmp)]
+    #[test]
+    fn test_quality() {
+        let accept = Accept(vec![
+            Mime::from_str("audio/ogg;q=0.3").unwrap(),
+            Mime::from_str("audio/*;q=0.5").unwrap(),
+            Mime::from_str("*/*;q=0.1").unwrap(),
+        ]);
+
+        assert_eq!(accept.quality(&Mime::from_str("audio/ogg").unwrap()), 0.3);
+        assert_eq!(accept.quality(&Mime::from_str("audio/aac").unwrap()), 0.5);
+        assert_eq!(accept.quality(&Mime::from_str("text/html").unwrap()), 0.1);
+        assert_eq!(
+            Accept(vec![mime::TEXT_HTML]).quality(&Mime::from_str("audio/aac").unwrap()),
+            0.0
+        );
+    }
+
+    #[test]
+    fn test_negotiate() {
+        let available = ["audio/aac", "audio/ogg", "audio/mpeg"];
+        let to_mime = |s: &&str| Mime::from_str(s).unwrap();
+
+        let accept = Accept(vec![
+            Mime::from_str("audio/mpeg").unwrap(),
+            Mime::from_str("audio/aac;q=0.8").unwrap(),
+        ]);
+        assert_eq!(accept.negotiate(available, to_mime), Some("audio/mpeg"));
+
+        let accept = Accept(vec![Mime::from_str("audio/*").unwrap()]);
+        assert_eq!(accept.negotiate(available, to_mime), Some("audio/aac"));
+
+        let accept = Accept(vec![
+            Mime::from_str("audio/aac;q=0").unwrap(),
+            Mime::from_str("audio/*;q=0.2").unwrap(),
+        ]);
+        assert_eq!(accept.negotiate(available, to_mime), Some("audio/ogg"));
+
+        let accept = Accept(vec![mime::TEXT_HTML]);
+        assert_eq!(accept.negotiate(available, to_mime), None);
+
+        assert_eq!(
+            Accept::default().negotiate(available, to_mime),
+            Some("audio/aac")
+        );
+    }
 }
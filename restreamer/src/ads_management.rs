@@ -6,8 +6,10 @@ mod ads_provider;
 use ad_cache::AdCache;
 
 pub use ad_id::AdId;
-pub use ads_planner::AdsPlanner;
-pub use ads_provider::{AdsProvider, ContentItem, PlaybackRecord, TrackRecord};
+pub use ads_planner::{AdsPlanner, PlannedAd};
+pub use ads_provider::{
+    AdsProvider, ContentItem, DeadAirRecord, PlaybackLevels, PlaybackRecord, TrackRecord,
+};
 
 #[cfg(test)]
 pub const CODEC_PARAMS: codec::CodecParams =
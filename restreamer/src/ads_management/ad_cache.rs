@@ -6,6 +6,7 @@ use std::{
 };
 
 use anyhow::bail;
+use codec::dsp::Loudness;
 use codec::{AudioFrame, CodecParams, Decoder, FrameDuration, Resampler};
 use tokio::sync::RwLock;
 
@@ -19,6 +20,7 @@ struct TrackCacheItem {
     track: Track,
     #[allow(dead_code)]
     duration: Duration,
+    loudness: Option<Loudness>,
 }
 
 type TrackCache = HashMap<AdId, TrackCacheItem>;
@@ -64,10 +66,14 @@ impl AdCache {
                     .iter()
                     .fold(Duration::ZERO, |acc, frame| acc + frame.duration());
 
+                let loudness = Loudness::measure(&track);
+                log::debug!("Track loudness, id={}, loudness={loudness:?}", id.as_ref());
+
                 entry.insert(TrackCacheItem {
                     params,
                     track,
                     duration,
+                    loudness,
                 });
 
                 Ok(())
@@ -83,6 +89,11 @@ impl AdCache {
         Ok(this)
     }
 
+    /// Loudness of the decoded track, `None` for unknown or empty tracks.
+    pub async fn loudness(&self, id: AdId) -> Option<Loudness> {
+        self.tracks.read().await.get(&id)?.loudness
+    }
+
     pub async fn get(
         &self,
         id: AdId,
@@ -147,6 +158,7 @@ impl AdCache {
                         params: super::CODEC_PARAMS,
                         track: track.clone(),
                         duration: Duration::from_secs(track.len() as u64),
+                        loudness: None,
                     },
                 ))
                 .collect(),
@@ -169,6 +181,13 @@ mod tests {
             .expect("Ad cache");
 
         assert_eq!(1, cache.ids().await.len());
+
+        let loudness = cache
+            .loudness(cache.ids().await[0])
+            .await
+            .expect("Loudness");
+        assert!(loudness.integrated.is_finite());
+        assert!(loudness.true_peak.is_finite());
     }
 
     #[tokio::test]
@@ -4,11 +4,14 @@ use std::sync::{
 };
 
 use chrono::{DateTime, Utc};
+use codec::dsp::{Levels, Loudness};
 use codec::{AudioFrame, CodecParams};
 use tokio::sync::RwLock;
 use uuid::Uuid;
 
-use super::{AdId, AdsProvider, ContentItem};
+use crate::levels::LevelsHandle;
+
+use super::{AdId, AdsProvider, ContentItem, PlaybackLevels};
 
 #[derive(Debug, Clone, Copy)]
 struct ActiveItem {
@@ -16,6 +19,12 @@ struct ActiveItem {
     started: DateTime<Utc>,
 }
 
+/// Frames of the next ad and its loudness, if measured.
+pub struct PlannedAd {
+    pub frames: Vec<AudioFrame>,
+    pub loudness: Option<Loudness>,
+}
+
 pub struct AdsPlanner {
     client_id: Uuid,
     ads_provider: Arc<AdsProvider>,
@@ -23,28 +32,31 @@ pub struct AdsPlanner {
     plan: Vec<AdId>,
     cursor: AtomicUsize,
     active_item: Arc<RwLock<Option<ActiveItem>>>,
+    levels: LevelsHandle,
 }
 
 impl AdsPlanner {
     pub async fn new(
         ads_provider: Arc<AdsProvider>,
         codec_params: CodecParams,
+        levels: LevelsHandle,
     ) -> anyhow::Result<Self> {
         let content = ads_provider.content().await?;
 
         let plan = arrange_plan(content);
 
         Ok(Self {
-            client_id: Uuid::new_v4(),
+            client_id: levels.client_id(),
             ads_provider,
             codec_params,
             plan,
             cursor: AtomicUsize::default(),
             active_item: Arc::new(RwLock::new(None)),
+            levels,
         })
     }
 
-    pub async fn next(&self) -> anyhow::Result<Vec<AudioFrame>> {
+    pub async fn next(&self) -> anyhow::Result<PlannedAd> {
         if self.active_item.read().await.is_some() {
             log::error!(
                 "Client {} Track is not completed: {:?}",
@@ -57,6 +69,8 @@ impl AdsPlanner {
         assert!(active_item < self.plan.len());
         let active_id = self.plan[active_item];
 
+        // Peaks of the program before the ad don't count.
+        self.levels.take_peak();
         *self.active_item.write().await = Some(ActiveItem {
             id: active_id,
             started: Utc::now(),
@@ -66,7 +80,7 @@ impl AdsPlanner {
             .report_started(self.client_id, active_id)
             .await?;
 
-        Ok((*self
+        let frames = (*self
             .ads_provider
             .get(active_id, self.codec_params)
             .await?
@@ -76,16 +90,22 @@ impl AdsPlanner {
                     self.client_id,
                 )
             })?)
-        .clone())
+        .clone();
+
+        Ok(PlannedAd {
+            frames,
+            loudness: self.ads_provider.loudness(active_id).await,
+        })
     }
 
     pub async fn finished(&self) {
         let active_item = self.active_item.write().await.take();
 
         if let Some(item) = active_item {
+            let levels = self.playback_levels();
             if let Err(err) = self
                 .ads_provider
-                .report_finished(self.client_id, item.id, item.started)
+                .report_finished(self.client_id, item.id, item.started, levels)
                 .await
             {
                 log::error!(
@@ -95,6 +115,21 @@ impl AdsPlanner {
             }
         }
     }
+
+    fn playback_levels(&self) -> PlaybackLevels {
+        let snapshot = self.levels.snapshot();
+        let short_term = |levels: Option<Levels>| {
+            levels
+                .map(|levels| levels.short_term)
+                .filter(|loudness| loudness.is_finite())
+        };
+
+        PlaybackLevels {
+            original_loudness: short_term(snapshot.original),
+            processed_loudness: short_term(snapshot.processed),
+            processed_peak: Some(self.levels.take_peak()).filter(|peak| peak.is_finite()),
+        }
+    }
 }
 
 fn arrange_plan(content: Vec<ContentItem>) -> Vec<AdId> {
@@ -107,6 +142,11 @@ fn arrange_plan(content: Vec<ContentItem>) -> Vec<AdId> {
 impl AdsPlanner {
     pub async fn testing(track: Vec<AudioFrame>) -> Self {
         let ads_provider = Arc::new(AdsProvider::testing(track).await);
-        Self::new(ads_provider, super::CODEC_PARAMS).await.unwrap()
+        let levels = crate::levels::StreamLevels::default()
+            .create("testing", super::CODEC_PARAMS)
+            .handle();
+        Self::new(ads_provider, super::CODEC_PARAMS, levels)
+            .await
+            .unwrap()
     }
 }
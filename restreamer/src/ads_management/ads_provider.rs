@@ -1,9 +1,13 @@
-use std::{hash::Hash, str::FromStr, sync::Arc};
+use std::{hash::Hash, path::Path, sync::Arc};
 
-use anyhow::ensure;
 use chrono::{DateTime, Utc};
-use codec::{AudioFrame, CodecParams};
-use sqlx::{sqlite::SqliteRow, FromRow, Row, SqlitePool};
+use codec::dsp::Loudness;
+use codec::{AudioFrame, CodecParams, Tags};
+use sqlx::{
+    migrate::Migrator,
+    sqlite::{SqliteConnectOptions, SqliteRow},
+    FromRow, Row, SqlitePool,
+};
 use uuid::Uuid;
 
 use super::{AdCache, AdId};
@@ -43,6 +47,21 @@ pub struct PlaybackRecord {
     pub name: String,
     pub started: DateTime<Utc>,
     pub finished: DateTime<Utc>,
+    #[sqlx(flatten)]
+    pub levels: PlaybackLevels,
+}
+
+/// Levels of the stream when a playback finished, `None` for silence.
+///
+/// Short-term loudness covers the last 3s, including the cross-fade out of the ad.
+#[derive(Debug, Clone, Copy, Default, PartialEq, FromRow)]
+pub struct PlaybackLevels {
+    /// Short-term loudness of the original stream in LUFS.
+    pub original_loudness: Option<f64>,
+    /// Short-term loudness of the processed stream in LUFS.
+    pub processed_loudness: Option<f64>,
+    /// Highest peak of the processed stream since the previous playback finished in dBFS.
+    pub processed_peak: Option<f64>,
 }
 
 #[derive(Debug, Clone, FromRow)]
@@ -54,9 +73,20 @@ pub struct TrackRecord {
     pub played: u32,
 }
 
+#[derive(Debug, Clone, FromRow)]
+pub struct DeadAirRecord {
+    pub source: String,
+    pub started: DateTime<Utc>,
+    pub finished: Option<DateTime<Utc>>,
+}
+
 impl AdsProvider {
-    pub async fn init() -> anyhow::Result<Self> {
-        let options = sqlx::sqlite::SqliteConnectOptions::from_str("sqlite::memory:")?;
+    pub async fn init(database: &Path) -> anyhow::Result<Self> {
+        log::info!("Opening database {}", database.display());
+
+        let options = SqliteConnectOptions::new()
+            .filename(database)
+            .create_if_missing(true);
         let db_pool = SqlitePool::connect_with(options).await?;
 
         init_db(&db_pool).await?;
@@ -99,6 +129,11 @@ impl AdsProvider {
         Ok(track)
     }
 
+    /// Loudness of a track obtained with [`Self::get`].
+    pub async fn loudness(&self, id: AdId) -> Option<Loudness> {
+        self.cache.loudness(id).await
+    }
+
     #[allow(clippy::unused_async)]
     pub async fn report_started(&self, client_id: Uuid, id: AdId) -> anyhow::Result<()> {
         log::info!("Client {}: start playing item {}", client_id, id.as_ref());
@@ -111,6 +146,7 @@ impl AdsProvider {
         client_id: Uuid,
         track_id: AdId,
         started: DateTime<Utc>,
+        levels: PlaybackLevels,
     ) -> anyhow::Result<()> {
         log::info!(
             "Client {}: finished playing item {}",
@@ -118,12 +154,20 @@ impl AdsProvider {
             track_id.as_ref()
         );
         sqlx::query(
-            r#"INSERT INTO playbacks (client_id, track_id, started, finished) VALUES(?,?,?,?)"#,
+            r#"
+                INSERT INTO playbacks (
+                    client_id, track_id, started, finished,
+                    original_loudness, processed_loudness, processed_peak
+                ) VALUES(?,?,?,?,?,?,?)
+            "#,
         )
         .bind(client_id)
         .bind(track_id)
         .bind(started)
         .bind(Utc::now())
+        .bind(levels.original_loudness)
+        .bind(levels.processed_loudness)
+        .bind(levels.processed_peak)
         .execute(&self.db_pool)
         .await?;
         Ok(())
@@ -132,7 +176,9 @@ impl AdsProvider {
     pub async fn playbacks(&self) -> anyhow::Result<Vec<PlaybackRecord>> {
         let records = sqlx::query_as::<_, PlaybackRecord>(
             r#"
-                SELECT p.client_id, p.track_id, t.name, p.started, p.finished FROM playbacks p
+                SELECT p.client_id, p.track_id, t.name, p.started, p.finished,
+                    p.original_loudness, p.processed_loudness, p.processed_peak
+                FROM playbacks p
                 LEFT JOIN tracks t ON t.id = p.track_id
                 ORDER BY p.finished DESC, p.started DESC;
             "#,
@@ -146,7 +192,9 @@ impl AdsProvider {
     pub async fn playbacks_by_id(&self, id: AdId) -> anyhow::Result<Vec<PlaybackRecord>> {
         let records = sqlx::query_as::<_, PlaybackRecord>(
             r#"
-                SELECT p.client_id, p.track_id, t.name, p.started, p.finished FROM playbacks p
+                SELECT p.client_id, p.track_id, t.name, p.started, p.finished,
+                    p.original_loudness, p.processed_loudness, p.processed_peak
+                FROM playbacks p
                 LEFT JOIN tracks t ON t.id = p.track_id
                 WHERE p.track_id = ?
                 ORDER BY p.finished DESC, p.started DESC;
@@ -174,11 +222,51 @@ impl AdsProvider {
         Ok(records)
     }
 
+    /// Records dead air of the source, returns the id to finish it with.
+    pub async fn report_dead_air_started(
+        &self,
+        source: &str,
+        started: DateTime<Utc>,
+    ) -> anyhow::Result<i64> {
+        let id = sqlx::query(r#"INSERT INTO dead_air (source, started) VALUES (?, ?)"#)
+            .bind(source)
+            .bind(started)
+            .execute(&self.db_pool)
+            .await?
+            .last_insert_rowid();
+        Ok(id)
+    }
+
+    pub async fn report_dead_air_finished(
+        &self,
+        id: i64,
+        finished: DateTime<Utc>,
+    ) -> anyhow::Result<()> {
+        sqlx::query(r#"UPDATE dead_air SET finished = ? WHERE rowid = ?"#)
+            .bind(finished)
+            .bind(id)
+            .execute(&self.db_pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn dead_air(&self) -> anyhow::Result<Vec<DeadAirRecord>> {
+        let records = sqlx::query_as::<_, DeadAirRecord>(
+            r#"SELECT source, started, finished FROM dead_air ORDER BY started DESC"#,
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(records)
+    }
+
+    /// Adds the track named by its tags, or by `name` if it has none.
     pub async fn add_track(&self, name: &str, content: &[u8]) -> anyhow::Result<AdId> {
-        let codec_params = codec::track_codec_params(content)?;
-        ensure!(codec_params.is_valid(), "Invalid codec params");
+        let probe = codec::probe(content)?;
+        log::debug!("Probed track {name}: {probe:?}");
 
-        let duration = codec::track_duration(content)?.as_secs();
+        let duration = probe.duration(content)?.as_secs();
+        let name = track_name(&probe.tags).unwrap_or_else(|| name.to_string());
         let id = AdId::new();
 
         sqlx::query(
@@ -196,30 +284,19 @@ impl AdsProvider {
     }
 }
 
+fn track_name(tags: &Tags) -> Option<String> {
+    match (&tags.artist, &tags.title) {
+        (Some(artist), Some(title)) => Some(format!("{artist} - {title}")),
+        (None, Some(title)) => Some(title.clone()),
+        _ => None,
+    }
+}
+
+// Schema versions are kept in `restreamer/migrations`, applied ones are tracked by the database.
+static MIGRATOR: Migrator = sqlx::migrate!();
+
 async fn init_db(pool: &SqlitePool) -> anyhow::Result<()> {
-    sqlx::query(
-        r#"CREATE TABLE tracks (
-            "id"	    TEXT NOT NULL UNIQUE,
-            "name"	    TEXT NOT NULL,
-            "content"	BLOB NOT NULL,
-            "added"     TEXT NOT NULL,
-            "duration"  INTEGER NOT NULL,
-            PRIMARY KEY("id")
-        )"#,
-    )
-    .execute(pool)
-    .await?;
-
-    sqlx::query(
-        r#"CREATE TABLE playbacks (
-            "client_id"     TEXT NOT NULL,
-            "track_id"	    TEXT NOT NULL,
-            "started"	    TEXT NOT NULL,
-            "finished"	    TEXT NOT NULL
-        )"#,
-    )
-    .execute(pool)
-    .await?;
+    MIGRATOR.run(pool).await?;
 
     Ok(())
 }
@@ -227,7 +304,7 @@ async fn init_db(pool: &SqlitePool) -> anyhow::Result<()> {
 #[cfg(test)]
 impl AdsProvider {
     pub async fn testing(track: Track) -> Self {
-        let options = sqlx::sqlite::SqliteConnectOptions::from_str("sqlite::memory:").unwrap();
+        let options = "sqlite::memory:".parse::<SqliteConnectOptions>().unwrap();
         let db_pool = SqlitePool::connect_with(options).await.unwrap();
         init_db(&db_pool).await.unwrap();
 
@@ -257,7 +334,27 @@ mod tests {
 
     #[tokio::test]
     async fn test_init() {
-        AdsProvider::init().await.expect("Initialized provider");
+        let database = std::env::temp_dir().join(format!("{}.sqlite", Uuid::new_v4()));
+
+        let id = {
+            let sut = AdsProvider::init(&database)
+                .await
+                .expect("Initialized provider");
+            sut.add_track("Sample", include_bytes!("../../sample.aac"))
+                .await
+                .expect("Track added")
+        };
+
+        // Re-opening the database keeps the content.
+        let sut = AdsProvider::init(&database)
+            .await
+            .expect("Re-initialized provider");
+        let content = sut.content().await.expect("Content items");
+
+        std::fs::remove_file(&database).expect("Database removed");
+
+        assert_eq!(1, content.len());
+        assert_eq!(id, content[0].id);
     }
 
     #[tokio::test]
@@ -279,13 +376,19 @@ mod tests {
         let started = Utc::now();
         sut.report_started(client_id, id).await.expect("Started");
         tokio::time::sleep(Duration::from_millis(200)).await;
-        sut.report_finished(client_id, id, started)
+        let levels = PlaybackLevels {
+            original_loudness: Some(-14.5),
+            processed_loudness: Some(-16.0),
+            processed_peak: None,
+        };
+        sut.report_finished(client_id, id, started, levels)
             .await
             .expect("Finished");
 
         let playbacks = sut.playbacks().await.expect("Playback records");
 
         assert_eq!(1, playbacks.len());
+        assert_eq!(levels, playbacks[0].levels);
     }
 
     #[tokio::test]
@@ -298,7 +401,7 @@ mod tests {
         let started = Utc::now();
         sut.report_started(client_id, id).await.expect("Started");
         tokio::time::sleep(Duration::from_millis(200)).await;
-        sut.report_finished(client_id, id, started)
+        sut.report_finished(client_id, id, started, PlaybackLevels::default())
             .await
             .expect("Finished");
 
@@ -317,7 +420,7 @@ mod tests {
         let started = Utc::now();
         sut.report_started(client_id, id).await.expect("Started");
         tokio::time::sleep(Duration::from_millis(200)).await;
-        sut.report_finished(client_id, id, started)
+        sut.report_finished(client_id, id, started, PlaybackLevels::default())
             .await
             .expect("Finished");
 
@@ -328,4 +431,45 @@ mod tests {
         assert_eq!(1, tracks.len());
         assert_eq!(1, tracks[0].played);
     }
+
+    #[tokio::test]
+    async fn test_dead_air() {
+        let sut = AdsProvider::testing(vec![]).await;
+
+        let first = sut
+            .report_dead_air_started("http://radio", Utc::now())
+            .await
+            .expect("Started");
+        let second = sut
+            .report_dead_air_started("http://other", Utc::now())
+            .await
+            .expect("Started");
+        sut.report_dead_air_finished(first, Utc::now())
+            .await
+            .expect("Finished");
+
+        let records = sut.dead_air().await.expect("Dead air records");
+        assert_eq!(2, records.len());
+        assert_ne!(first, second);
+        assert!(records
+            .iter()
+            .any(|record| record.source == "http://radio" && record.finished.is_some()));
+        assert!(records
+            .iter()
+            .any(|record| record.source == "http://other" && record.finished.is_none()));
+    }
+
+    #[test]
+    fn test_track_name() {
+        let mut tags = Tags {
+            title: Some("Summer Sale".to_string()),
+            ..Tags::default()
+        };
+        assert_eq!(track_name(&tags).as_deref(), Some("Summer Sale"));
+
+        tags.artist = Some("Brand".to_string());
+        assert_eq!(track_name(&tags).as_deref(), Some("Brand - Summer Sale"));
+
+        assert_eq!(track_name(&Tags::default()), None);
+    }
 }
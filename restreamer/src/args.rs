@@ -1,6 +1,12 @@
-use analyzer::AnalyzerOpts;
+use std::{path::PathBuf, time::Duration};
+
+use analyzer::{AnalyzerConfig, AnalyzerOpts, MinDurations};
 use clap::{value_parser, Parser};
+use codec::dsp::SilenceDetector;
 use enumflags2::BitFlags;
+use unstreamer::SegmentsConfig;
+
+use crate::cross_fades::CrossFadeConfig;
 
 #[derive(Debug, Clone, Parser)]
 #[allow(clippy::struct_excessive_bools)]
@@ -32,6 +38,21 @@ pub struct Args {
     #[arg(value_parser = value_parser!(u64).range(0..10_000))]
     pub smooth_ahead: u64,
 
+    /// Advertisement must last that long in ms to be accepted.
+    #[arg(long, default_value_t = 2000)]
+    #[arg(value_parser = value_parser!(u64).range(0..60_000))]
+    pub min_advertisement: u64,
+
+    /// Music must last that long in ms to be accepted, e.g. to end an ad block.
+    #[arg(long, default_value_t = 1000)]
+    #[arg(value_parser = value_parser!(u64).range(0..60_000))]
+    pub min_music: u64,
+
+    /// Talk must last that long in ms to be accepted.
+    #[arg(long, default_value_t = 1000)]
+    #[arg(value_parser = value_parser!(u64).range(0..60_000))]
+    pub min_talk: u64,
+
     #[arg(long, default_value_t = false)]
     pub buffer_stat: bool,
 
@@ -42,6 +63,40 @@ pub struct Args {
     /// Ignore classification and use advert
     #[arg(long, default_value_t = false)]
     pub advert: bool,
+
+    /// Path to the ads database, created if missing.
+    #[arg(long, default_value = "ads.sqlite")]
+    pub database: PathBuf,
+
+    /// Audio played to listeners while a live source reconnects, silence if not set.
+    #[arg(long)]
+    pub gap_filler: Option<PathBuf>,
+
+    /// Source quieter than that in dBFS is dead air, once it lasts long enough.
+    #[arg(long, default_value_t = -50.0, allow_negative_numbers = true)]
+    pub dead_air_threshold: f64,
+
+    /// Silence must last that long in ms to be dead air.
+    #[arg(long, default_value_t = 10_000)]
+    #[arg(value_parser = value_parser!(u64).range(1_000..600_000))]
+    pub dead_air_duration: u64,
+
+    /// Audio played to listeners instead of dead air, the silent source is played if not set.
+    #[arg(long)]
+    pub dead_air_fallback: Option<PathBuf>,
+
+    /// HLS and DASH sources falling that far behind live in ms skip to the live edge.
+    #[arg(long, default_value_t = 30_000)]
+    #[arg(value_parser = value_parser!(u64).range(1_000..600_000))]
+    pub max_live_lag: u64,
+
+    /// Default analyzer settings, model and its tuning can be overridden per request.
+    #[command(flatten)]
+    pub analyzer: AnalyzerConfig,
+
+    /// Default fades, curves and durations can be overridden per request.
+    #[command(flatten)]
+    pub cross_fades: CrossFadeConfig,
 }
 
 impl Args {
@@ -56,6 +111,28 @@ impl Args {
     pub const fn report_slow_processing(&self) -> bool {
         !self.gcp && !self.quiet && self.report_slow_processing
     }
+
+    pub fn segments_config(&self) -> SegmentsConfig {
+        SegmentsConfig {
+            max_lag: Duration::from_millis(self.max_live_lag),
+            ..SegmentsConfig::default()
+        }
+    }
+
+    pub fn silence_detector(&self) -> SilenceDetector {
+        SilenceDetector::new(
+            self.dead_air_threshold,
+            Duration::from_millis(self.dead_air_duration),
+        )
+    }
+
+    pub const fn min_durations(&self) -> MinDurations {
+        MinDurations {
+            advertisement: Duration::from_millis(self.min_advertisement),
+            music: Duration::from_millis(self.min_music),
+            talk: Duration::from_millis(self.min_talk),
+        }
+    }
 }
 
 impl From<Args> for BitFlags<AnalyzerOpts> {
@@ -0,0 +1,36 @@
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use analyzer::{AnalyzerConfig, BatchClassifier, ClassifyModel};
+use classifier::Classify;
+
+type ModelKey = (PathBuf, ClassifyModel);
+/// Locked while its model loads, sources of other models don't wait for it.
+type ModelSlot = Arc<Mutex<Option<Arc<dyn Classify>>>>;
+
+/// Classifiers loaded so far, each model is loaded once and shared by all sources.
+#[derive(Clone, Default)]
+pub struct Classifiers(Arc<Mutex<HashMap<ModelKey, ModelSlot>>>);
+
+impl Classifiers {
+    pub fn get(&self, config: &AnalyzerConfig) -> anyhow::Result<Arc<dyn Classify>> {
+        let key = (config.models_dir.clone(), config.model);
+
+        let slot = self.0.lock().unwrap().entry(key).or_default().clone();
+
+        let mut slot = slot.lock().unwrap();
+        if let Some(classifier) = slot.as_ref() {
+            return Ok(classifier.clone());
+        }
+
+        let classifier: Arc<dyn Classify> =
+            Arc::new(BatchClassifier::new(config.load_classifier()?));
+        *slot = Some(classifier.clone());
+        drop(slot);
+
+        Ok(classifier)
+    }
+}
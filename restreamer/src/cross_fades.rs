@@ -0,0 +1,75 @@
+use std::time::Duration;
+
+use clap::value_parser;
+use codec::dsp::{CrossFadeCurve, CrossFader};
+
+pub const MAX_CROSS_FADE_DURATION: u64 = 10_000;
+const DEFAULT_CROSS_FADE_DURATION: u64 = 1_500;
+
+/// Curves and durations of the fades of a played stream.
+///
+/// The entry fade brings the stream in when playback starts, the others cross the program into and out of ads.
+#[derive(Debug, Clone, PartialEq, Eq, clap::Args)]
+pub struct CrossFadeConfig {
+    /// Entry fade curve: linear, parabolic, equal-power, cossin or semicircle.
+    #[arg(long, default_value_t = CrossFadeCurve::Linear)]
+    pub entry_curve: CrossFadeCurve,
+
+    /// Entry fade duration in ms.
+    #[arg(long, default_value_t = DEFAULT_CROSS_FADE_DURATION)]
+    #[arg(value_parser = value_parser!(u64).range(0..=MAX_CROSS_FADE_DURATION))]
+    pub entry_duration: u64,
+
+    /// Curve of the cross-fade into ads.
+    #[arg(long, default_value_t = CrossFadeCurve::Parabolic)]
+    pub into_ad_curve: CrossFadeCurve,
+
+    /// Duration of the cross-fade into ads in ms.
+    #[arg(long, default_value_t = DEFAULT_CROSS_FADE_DURATION)]
+    #[arg(value_parser = value_parser!(u64).range(0..=MAX_CROSS_FADE_DURATION))]
+    pub into_ad_duration: u64,
+
+    /// Curve of the cross-fade out of ads.
+    #[arg(long, default_value_t = CrossFadeCurve::Parabolic)]
+    pub out_of_ad_curve: CrossFadeCurve,
+
+    /// Duration of the cross-fade out of ads in ms.
+    #[arg(long, default_value_t = DEFAULT_CROSS_FADE_DURATION)]
+    #[arg(value_parser = value_parser!(u64).range(0..=MAX_CROSS_FADE_DURATION))]
+    pub out_of_ad_duration: u64,
+}
+
+impl Default for CrossFadeConfig {
+    fn default() -> Self {
+        Self {
+            entry_curve: CrossFadeCurve::Linear,
+            entry_duration: DEFAULT_CROSS_FADE_DURATION,
+            into_ad_curve: CrossFadeCurve::Parabolic,
+            into_ad_duration: DEFAULT_CROSS_FADE_DURATION,
+            out_of_ad_curve: CrossFadeCurve::Parabolic,
+            out_of_ad_duration: DEFAULT_CROSS_FADE_DURATION,
+        }
+    }
+}
+
+impl CrossFadeConfig {
+    pub fn entry_fader(&self, frame_duration: Duration) -> CrossFader {
+        cross_fader(self.entry_curve, self.entry_duration, frame_duration)
+    }
+
+    pub fn fader_into_ad(&self, frame_duration: Duration) -> CrossFader {
+        cross_fader(self.into_ad_curve, self.into_ad_duration, frame_duration)
+    }
+
+    pub fn fader_out_of_ad(&self, frame_duration: Duration) -> CrossFader {
+        cross_fader(
+            self.out_of_ad_curve,
+            self.out_of_ad_duration,
+            frame_duration,
+        )
+    }
+}
+
+fn cross_fader(curve: CrossFadeCurve, duration: u64, frame_duration: Duration) -> CrossFader {
+    CrossFader::with_curve(curve, Duration::from_millis(duration), frame_duration)
+}
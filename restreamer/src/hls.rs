@@ -0,0 +1,199 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::body::Bytes;
+use hls_m3u8::{MediaPlaylist, MediaSegment};
+use uuid::Uuid;
+
+mod segmenter;
+pub use segmenter::Segmenter;
+
+pub const MIME_HLS: &str = "application/vnd.apple.mpegurl";
+pub const PLAYLIST_NAME: &str = "playlist.m3u8";
+pub const SEGMENT_DURATION: Duration = Duration::from_secs(4);
+
+// Number of segments in the live playlist.
+const PLAYLIST_LENGTH: usize = 6;
+// Session is closed when nobody requested its playlist or segments for that long.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Clone, Default)]
+pub struct HlsSessions(Arc<Mutex<HashMap<Uuid, Arc<HlsSession>>>>);
+
+impl HlsSessions {
+    pub fn create(&self) -> (Uuid, Arc<HlsSession>) {
+        let id = Uuid::new_v4();
+        let session = Arc::new(HlsSession::new());
+
+        let mut sessions = self.0.lock().unwrap();
+        sessions.retain(|id, session| {
+            let expired = session.is_expired();
+            if expired {
+                log::info!("HLS session {id} expired");
+            }
+            !expired
+        });
+        sessions.insert(id, session.clone());
+        drop(sessions);
+
+        (id, session)
+    }
+
+    pub fn get(&self, id: &Uuid) -> Option<Arc<HlsSession>> {
+        self.0
+            .lock()
+            .unwrap()
+            .get(id)
+            .filter(|session| !session.is_expired())
+            .cloned()
+    }
+}
+
+pub struct HlsSession(Mutex<Inner>);
+
+struct Inner {
+    segments: VecDeque<Segment>,
+    media_sequence: usize,
+    last_access: Instant,
+    finished: bool,
+}
+
+struct Segment {
+    duration: Duration,
+    data: Bytes,
+}
+
+impl HlsSession {
+    fn new() -> Self {
+        Self(Mutex::new(Inner {
+            segments: VecDeque::new(),
+            media_sequence: 0,
+            last_access: Instant::now(),
+            finished: false,
+        }))
+    }
+
+    pub fn push(&self, duration: Duration, data: Vec<u8>) {
+        let mut inner = self.0.lock().unwrap();
+
+        inner.segments.push_back(Segment {
+            duration,
+            data: data.into(),
+        });
+
+        while inner.segments.len() > PLAYLIST_LENGTH {
+            inner.segments.pop_front();
+            inner.media_sequence += 1;
+        }
+        drop(inner);
+    }
+
+    /// Marks the end of the stream, the playlist gets `EXT-X-ENDLIST`.
+    pub fn finish(&self) {
+        self.0.lock().unwrap().finished = true;
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.0.lock().unwrap().last_access.elapsed() > IDLE_TIMEOUT
+    }
+
+    pub fn is_ready(&self) -> bool {
+        let inner = self.0.lock().unwrap();
+        inner.finished || !inner.segments.is_empty()
+    }
+
+    pub fn playlist(&self) -> anyhow::Result<String> {
+        let mut inner = self.0.lock().unwrap();
+        inner.last_access = Instant::now();
+
+        let target_duration = inner
+            .segments
+            .iter()
+            .map(|segment| segment.duration)
+            .max()
+            .unwrap_or(SEGMENT_DURATION)
+            .as_secs_f64()
+            .ceil();
+
+        let segments = inner
+            .segments
+            .iter()
+            .enumerate()
+            .map(|(index, segment)| {
+                MediaSegment::builder()
+                    .duration(segment.duration)
+                    .uri(segment_name(inner.media_sequence + index))
+                    .build()
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| anyhow::anyhow!(err))?;
+
+        let media_sequence = inner.media_sequence;
+        let finished = inner.finished;
+        drop(inner);
+
+        let playlist = MediaPlaylist::builder()
+            .target_duration(Duration::from_secs_f64(target_duration))
+            .media_sequence(media_sequence)
+            .has_end_list(finished)
+            .segments(segments)
+            .build()
+            .map_err(|err| anyhow::anyhow!(err))?;
+
+        Ok(playlist.to_string())
+    }
+
+    pub fn segment(&self, name: &str) -> Option<Bytes> {
+        let mut inner = self.0.lock().unwrap();
+        inner.last_access = Instant::now();
+
+        let sequence = name
+            .strip_suffix(segmenter::SEGMENT_EXTENSION)?
+            .parse::<usize>()
+            .ok()?;
+
+        inner
+            .segments
+            .get(sequence.checked_sub(inner.media_sequence)?)
+            .map(|segment| segment.data.clone())
+    }
+}
+
+fn segment_name(sequence: usize) -> String {
+    format!("{sequence}{}", segmenter::SEGMENT_EXTENSION)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rolling_playlist() {
+        let sut = HlsSession::new();
+        assert!(!sut.is_ready());
+
+        for n in 0..=PLAYLIST_LENGTH {
+            sut.push(Duration::from_millis(4_100), vec![n as u8]);
+        }
+        assert!(sut.is_ready());
+
+        let playlist = sut.playlist().expect("Playlist");
+        assert!(playlist.contains("#EXT-X-TARGETDURATION:5"));
+        assert!(playlist.contains("#EXT-X-MEDIA-SEQUENCE:1"));
+        assert!(!playlist.contains("0.aac"));
+        assert!(playlist.contains("6.aac"));
+        assert!(!playlist.contains("#EXT-X-ENDLIST"));
+
+        assert_eq!(None, sut.segment("0.aac"));
+        assert_eq!(Some(Bytes::from(vec![1u8])), sut.segment("1.aac"));
+        assert_eq!(Some(Bytes::from(vec![6u8])), sut.segment("6.aac"));
+        assert_eq!(None, sut.segment("7.aac"));
+        assert_eq!(None, sut.segment("playlist.aac"));
+
+        sut.finish();
+        assert!(sut.playlist().expect("Playlist").contains("#EXT-X-ENDLIST"));
+    }
+}
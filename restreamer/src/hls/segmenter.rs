@@ -0,0 +1,263 @@
+use std::{
+    io::{self, Write},
+    sync::Arc,
+    time::Duration,
+};
+
+use super::HlsSession;
+
+pub const SEGMENT_EXTENSION: &str = ".aac";
+
+const ADTS_HEADER_SIZE: usize = 7;
+const ADTS_SAMPLE_RATES: [u32; 13] = [
+    96_000, 88_200, 64_000, 48_000, 44_100, 32_000, 24_000, 22_050, 16_000, 12_000, 11_025, 8_000,
+    7_350,
+];
+const AAC_SAMPLES_PER_FRAME: u64 = 1_024;
+
+// MPEG-2 timestamps are 33 bits at 90kHz.
+const MPEG_CLOCK: u64 = 90_000;
+const MPEG_TIMESTAMP_MASK: u64 = (1 << 33) - 1;
+const TIMESTAMP_OWNER: &[u8] = b"com.apple.streaming.transportStreamTimestamp\0";
+
+/// Cuts ADTS stream into HLS packed audio segments.
+///
+/// Segments are cut on frame boundaries once they reach the target duration.
+/// Each segment starts with ID3 tag carrying its timestamp, as HLS requires for packed audio.
+pub struct Segmenter {
+    session: Arc<HlsSession>,
+    target_duration: Duration,
+    buffer: Vec<u8>,
+    segment: Vec<u8>,
+    segment_samples: u64,
+    total_samples: u64,
+    sample_rate: u32,
+}
+
+impl Segmenter {
+    pub const fn new(session: Arc<HlsSession>, target_duration: Duration) -> Self {
+        Self {
+            session,
+            target_duration,
+            buffer: vec![],
+            segment: vec![],
+            segment_samples: 0,
+            total_samples: 0,
+            sample_rate: 0,
+        }
+    }
+
+    fn segment_duration(&self) -> Duration {
+        if self.sample_rate == 0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(self.segment_samples as f64 / f64::from(self.sample_rate))
+        }
+    }
+
+    fn consume_frames(&mut self) {
+        while self.buffer.len() >= ADTS_HEADER_SIZE {
+            let Some(header) = AdtsHeader::parse(&self.buffer) else {
+                let skip = self.buffer[1..]
+                    .iter()
+                    .position(|b| *b == 0xFF)
+                    .map_or(self.buffer.len(), |pos| pos + 1);
+                log::warn!("ADTS sync lost, skipping {skip} bytes");
+                self.buffer.drain(..skip);
+                continue;
+            };
+
+            if self.buffer.len() < header.length {
+                break;
+            }
+
+            self.segment.extend(self.buffer.drain(..header.length));
+            self.segment_samples += header.samples;
+            self.sample_rate = header.sample_rate;
+
+            if self.segment_duration() >= self.target_duration {
+                self.publish();
+            }
+        }
+    }
+
+    fn publish(&mut self) {
+        if self.segment.is_empty() {
+            return;
+        }
+
+        let timestamp = self.total_samples * MPEG_CLOCK / u64::from(self.sample_rate);
+
+        let mut data = id3_timestamp(timestamp);
+        data.append(&mut self.segment);
+
+        self.session.push(self.segment_duration(), data);
+
+        self.total_samples += self.segment_samples;
+        self.segment_samples = 0;
+    }
+}
+
+impl Write for Segmenter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.session.is_expired() {
+            return Err(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "HLS session expired",
+            ));
+        }
+
+        self.buffer.extend_from_slice(buf);
+        self.consume_frames();
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for Segmenter {
+    fn drop(&mut self) {
+        self.publish();
+        self.session.finish();
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct AdtsHeader {
+    length: usize,
+    samples: u64,
+    sample_rate: u32,
+}
+
+impl AdtsHeader {
+    fn parse(data: &[u8]) -> Option<Self> {
+        // Sync word and layer, which is always 0.
+        if data.len() < ADTS_HEADER_SIZE || data[0] != 0xFF || data[1] & 0xF6 != 0xF0 {
+            return None;
+        }
+
+        let sample_rate = *ADTS_SAMPLE_RATES.get(usize::from((data[2] >> 2) & 0x0F))?;
+
+        let length = (usize::from(data[3] & 0x03) << 11)
+            | (usize::from(data[4]) << 3)
+            | (usize::from(data[5]) >> 5);
+
+        if length < ADTS_HEADER_SIZE {
+            return None;
+        }
+
+        let blocks = u64::from(data[6] & 0x03) + 1;
+
+        Some(Self {
+            length,
+            samples: blocks * AAC_SAMPLES_PER_FRAME,
+            sample_rate,
+        })
+    }
+}
+
+/// ID3v2.4 tag with PRIV frame holding MPEG-2 timestamp of the first sample in the segment.
+fn id3_timestamp(timestamp: u64) -> Vec<u8> {
+    let frame_size = TIMESTAMP_OWNER.len() + 8;
+    let tag_size = 10 + frame_size;
+
+    let mut tag = Vec::with_capacity(10 + tag_size);
+    tag.extend_from_slice(b"ID3\x04\x00\x00");
+    tag.extend_from_slice(&syncsafe(tag_size));
+    tag.extend_from_slice(b"PRIV");
+    tag.extend_from_slice(&syncsafe(frame_size));
+    tag.extend_from_slice(&[0, 0]);
+    tag.extend_from_slice(TIMESTAMP_OWNER);
+    tag.extend_from_slice(&(timestamp & MPEG_TIMESTAMP_MASK).to_be_bytes());
+    tag
+}
+
+const fn syncsafe(size: usize) -> [u8; 4] {
+    [
+        ((size >> 21) & 0x7F) as u8,
+        ((size >> 14) & 0x7F) as u8,
+        ((size >> 7) & 0x7F) as u8,
+        (size & 0x7F) as u8,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 44.1kHz stereo AAC LC frame of given length, payload is zeroed.
+    fn adts_frame(length: usize) -> Vec<u8> {
+        let mut frame = vec![0u8; length];
+        frame[0] = 0xFF;
+        frame[1] = 0xF1;
+        frame[2] = 0x50;
+        frame[3] = 0x80 | ((length >> 11) & 0x03) as u8;
+        frame[4] = ((length >> 3) & 0xFF) as u8;
+        frame[5] = (((length & 0x07) << 5) | 0x1F) as u8;
+        frame[6] = 0xFC;
+        frame
+    }
+
+    #[test]
+    fn test_parse_adts_header() {
+        assert_eq!(
+            AdtsHeader::parse(&adts_frame(371)),
+            Some(AdtsHeader {
+                length: 371,
+                samples: 1_024,
+                sample_rate: 44_100
+            })
+        );
+        assert_eq!(AdtsHeader::parse(&[0u8; 7]), None);
+        assert_eq!(AdtsHeader::parse(&adts_frame(7)[..6]), None);
+    }
+
+    #[test]
+    fn test_id3_timestamp() {
+        let tag = id3_timestamp(900_000);
+
+        assert_eq!(tag.len(), 73);
+        assert_eq!(&tag[..10], b"ID3\x04\x00\x00\x00\x00\x00\x3F");
+        assert_eq!(&tag[10..20], b"PRIV\x00\x00\x00\x35\x00\x00");
+        assert_eq!(&tag[65..], &900_000u64.to_be_bytes());
+    }
+
+    #[test]
+    fn test_segments() {
+        let session = Arc::new(HlsSession::new());
+
+        {
+            let mut sut = Segmenter::new(session.clone(), Duration::from_millis(100));
+
+            // 10 frames, 232ms in total, written in uneven chunks with garbage in front.
+            let mut stream = vec![0x00, 0x12, 0xFF];
+            for _ in 0..10 {
+                stream.extend(adts_frame(20));
+            }
+            for chunk in stream.chunks(13) {
+                assert_eq!(sut.write(chunk).expect("Written"), chunk.len());
+            }
+
+            // 5 frames per segment, 2 segments published, nothing left.
+            assert!(session.segment("1.aac").is_some());
+            assert!(sut.segment.is_empty());
+            assert!(sut.buffer.is_empty());
+        }
+
+        let first = session.segment("0.aac").expect("First segment");
+        assert_eq!(first.len(), 73 + 5 * 20);
+        assert_eq!(&first[65..73], &0u64.to_be_bytes());
+
+        let second = session.segment("1.aac").expect("Second segment");
+        assert_eq!(&second[65..73], &10_448u64.to_be_bytes());
+        assert_eq!(&second[73..80], &adts_frame(20)[..7]);
+
+        assert!(session
+            .playlist()
+            .expect("Playlist")
+            .contains("#EXT-X-ENDLIST"));
+    }
+}
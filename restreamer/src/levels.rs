@@ -0,0 +1,154 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use codec::dsp::{LevelMeter, Levels};
+use codec::{AudioFrame, CodecParams};
+use uuid::Uuid;
+
+use crate::stream_saver::Destination;
+
+/// Levels of all played streams by client.
+#[derive(Clone, Default)]
+pub struct StreamLevels(Arc<Mutex<HashMap<Uuid, LevelsHandle>>>);
+
+impl StreamLevels {
+    pub fn create(&self, source: &str, codec_params: CodecParams) -> StreamMeter {
+        let handle = LevelsHandle {
+            client_id: Uuid::new_v4(),
+            inner: Arc::new(Mutex::new(Inner {
+                snapshot: LevelsSnapshot {
+                    source: source.to_string(),
+                    original: None,
+                    processed: None,
+                },
+                processed_peak: f64::NEG_INFINITY,
+            })),
+        };
+        self.0
+            .lock()
+            .unwrap()
+            .insert(handle.client_id, handle.clone());
+
+        StreamMeter {
+            registry: self.clone(),
+            handle,
+            original: LevelMeter::new(codec_params.sample_rate()),
+            processed: LevelMeter::new(codec_params.sample_rate()),
+        }
+    }
+
+    pub fn snapshots(&self) -> Vec<(Uuid, LevelsSnapshot)> {
+        self.0
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(client_id, handle)| (*client_id, handle.snapshot()))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LevelsSnapshot {
+    pub source: String,
+    pub original: Option<Levels>,
+    pub processed: Option<Levels>,
+}
+
+struct Inner {
+    snapshot: LevelsSnapshot,
+    processed_peak: f64,
+}
+
+/// Latest levels of a stream, shared with whoever reports on it.
+#[derive(Clone)]
+pub struct LevelsHandle {
+    client_id: Uuid,
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl LevelsHandle {
+    pub const fn client_id(&self) -> Uuid {
+        self.client_id
+    }
+
+    pub fn snapshot(&self) -> LevelsSnapshot {
+        self.inner.lock().unwrap().snapshot.clone()
+    }
+
+    /// Highest peak of the processed stream since the last call in dBFS.
+    pub fn take_peak(&self) -> f64 {
+        std::mem::replace(
+            &mut self.inner.lock().unwrap().processed_peak,
+            f64::NEG_INFINITY,
+        )
+    }
+}
+
+/// Meters of original and processed frames of a stream, listed until dropped.
+pub struct StreamMeter {
+    registry: StreamLevels,
+    handle: LevelsHandle,
+    original: LevelMeter,
+    processed: LevelMeter,
+}
+
+impl StreamMeter {
+    pub fn handle(&self) -> LevelsHandle {
+        self.handle.clone()
+    }
+
+    pub fn push(&mut self, destination: Destination, frame: &AudioFrame) {
+        match destination {
+            Destination::Original => {
+                self.original.push(frame);
+                let levels = self.original.levels();
+                self.handle.inner.lock().unwrap().snapshot.original = Some(levels);
+            }
+            Destination::Processed => {
+                self.processed.push(frame);
+                let levels = self.processed.levels();
+                let peak = self.processed.take_peak();
+
+                let mut inner = self.handle.inner.lock().unwrap();
+                inner.snapshot.processed = Some(levels);
+                inner.processed_peak = inner.processed_peak.max(peak);
+            }
+        }
+    }
+}
+
+impl Drop for StreamMeter {
+    fn drop(&mut self) {
+        self.registry
+            .0
+            .lock()
+            .unwrap()
+            .remove(&self.handle.client_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use codec::SampleFormat;
+
+    use super::*;
+
+    #[test]
+    fn test_registry() {
+        let levels = StreamLevels::default();
+        let meter = levels.create("http://radio", CodecParams::new(4, SampleFormat::Flt, 1));
+        let handle = meter.handle();
+
+        let snapshots = levels.snapshots();
+        assert_eq!(1, snapshots.len());
+        assert_eq!(handle.client_id(), snapshots[0].0);
+        assert_eq!("http://radio", snapshots[0].1.source);
+        assert!(snapshots[0].1.processed.is_none());
+
+        drop(meter);
+        assert!(levels.snapshots().is_empty());
+        assert!(handle.snapshot().original.is_none());
+    }
+}
@@ -8,13 +8,23 @@ use stderrlog::Timestamp;
 use tower_http::services::ServeDir;
 
 use args::Args;
+use classifiers::Classifiers;
 use codec::configure_ffmpeg_log;
+use hls::HlsSessions;
+use levels::StreamLevels;
+use sources::Sources;
 
 mod accept_header;
 mod ads_management;
 mod args;
+mod classifiers;
+mod cross_fades;
+mod hls;
+mod levels;
 mod rate;
 mod routes;
+mod signal;
+mod sources;
 mod state;
 mod stream_saver;
 mod terminate;
@@ -30,17 +40,38 @@ async fn main() {
 
     let serve_dir = get_service(ServeDir::new("restreamer/assets"));
     let terminator = Terminator::new();
-    let ads_provider = Arc::new(AdsProvider::init().await.expect("AdsProvider"));
-
-    ads_provider
-        .add_track("Sample Track", include_bytes!("../sample.aac"))
+    let ads_provider = Arc::new(
+        AdsProvider::init(&args.database)
+            .await
+            .expect("AdsProvider"),
+    );
+
+    if ads_provider
+        .content()
         .await
-        .expect("Sample Track is loaded");
+        .expect("Ads content")
+        .is_empty()
+    {
+        ads_provider
+            .add_track("Sample Track", include_bytes!("../sample.aac"))
+            .await
+            .expect("Sample Track is loaded");
+    }
+
+    // Default model is loaded upfront, others on first request.
+    let classifiers = Classifiers::default();
+    classifiers
+        .get(&args.analyzer)
+        .expect("Initialized classifier");
 
     let state = AppState {
         terminator,
         ads_provider,
+        classifiers,
         args,
+        hls_sessions: HlsSessions::default(),
+        levels: StreamLevels::default(),
+        sources: Sources::default(),
     };
 
     let app = Router::new()
@@ -70,7 +101,9 @@ fn configure_logger(args: &Args) {
         .module("codec")
         .module("codec::dsp::cross_fader")
         .module("restreamer")
+        .module("restreamer::hls")
         .module("restreamer::routes::play")
+        .module("restreamer::sources")
         .module("restreamer::stream_saver")
         .module("restreamer::terminate")
         .quiet(args.quiet);
@@ -5,8 +5,9 @@ use axum::{
     http::StatusCode,
     response::{Html, IntoResponse, Response},
     routing::get,
-    Router,
+    Json, Router,
 };
+use chrono::Utc;
 use minijinja::render;
 use serde::Serialize;
 use tower_http::limit::RequestBodyLimitLayer;
@@ -18,6 +19,9 @@ pub fn router(state: AppState) -> Router {
         .route("/playbacks", get(playbacks))
         .route("/playbacks/:track_id", get(playbacks_by_id))
         .route("/tracks", get(tracks).post(upload))
+        .route("/levels", get(levels))
+        .route("/sources", get(sources))
+        .route("/dead-air", get(dead_air))
         .layer(DefaultBodyLimit::disable())
         .layer(RequestBodyLimitLayer::new(25 * 1024 * 1024 /* 25mb */))
         .with_state(state)
@@ -33,6 +37,10 @@ fn live_tracks_template() -> String {
     std::fs::read_to_string("restreamer/templates/tracks.html").unwrap()
 }
 
+fn live_dead_air_template() -> String {
+    std::fs::read_to_string("restreamer/templates/dead_air.html").unwrap()
+}
+
 async fn playbacks(State(state): State<AppState>) -> Result<Html<String>, AppError> {
     let records = state.ads_provider.playbacks().await?;
 
@@ -106,6 +114,42 @@ async fn upload(
     tracks(State(state)).await
 }
 
+async fn dead_air(State(state): State<AppState>) -> Result<Html<String>, AppError> {
+    let records = state.ads_provider.dead_air().await?;
+    let records = records
+        .into_iter()
+        .map(DeadAirRecord::from)
+        .collect::<Vec<_>>();
+    log::debug!("Dead air records: {records:?}");
+
+    let r = render!(&live_dead_air_template(), records => records);
+    Ok(Html(r))
+}
+
+/// Current levels of all played streams.
+async fn levels(State(state): State<AppState>) -> Json<Vec<StreamLevels>> {
+    Json(
+        state
+            .levels
+            .snapshots()
+            .into_iter()
+            .map(StreamLevels::from)
+            .collect(),
+    )
+}
+
+/// Running sources and how far behind the live edge they play.
+async fn sources(State(state): State<AppState>) -> Json<Vec<SourceStatus>> {
+    Json(
+        state
+            .sources
+            .snapshots()
+            .into_iter()
+            .map(SourceStatus::from)
+            .collect(),
+    )
+}
+
 struct AppError(anyhow::Error);
 
 impl IntoResponse for AppError {
@@ -134,6 +178,9 @@ struct PlaybackRecord {
     name: String,
     started: String,
     finished: String,
+    original_loudness: String,
+    processed_loudness: String,
+    processed_peak: String,
 }
 
 impl From<crate::ads_management::PlaybackRecord> for PlaybackRecord {
@@ -144,6 +191,9 @@ impl From<crate::ads_management::PlaybackRecord> for PlaybackRecord {
             name: record.name,
             started: record.started.format("%Y-%m-%d %H:%M:%S").to_string(),
             finished: record.finished.format("%Y-%m-%d %H:%M:%S").to_string(),
+            original_loudness: level(record.levels.original_loudness, "LUFS"),
+            processed_loudness: level(record.levels.processed_loudness, "LUFS"),
+            processed_peak: level(record.levels.processed_peak, "dBFS"),
         }
     }
 }
@@ -168,3 +218,102 @@ impl From<crate::ads_management::TrackRecord> for TrackRecord {
         }
     }
 }
+
+#[derive(Debug, Serialize)]
+struct DeadAirRecord {
+    source: String,
+    started: String,
+    finished: Option<String>,
+    duration: String,
+}
+
+impl From<crate::ads_management::DeadAirRecord> for DeadAirRecord {
+    fn from(record: crate::ads_management::DeadAirRecord) -> Self {
+        // Ongoing dead air lasts until now.
+        let duration = record.finished.unwrap_or_else(Utc::now) - record.started;
+        Self {
+            source: record.source,
+            started: record.started.format("%Y-%m-%d %H:%M:%S").to_string(),
+            finished: record
+                .finished
+                .map(|finished| finished.format("%Y-%m-%d %H:%M:%S").to_string()),
+            duration: format!("{} s", duration.num_seconds()),
+        }
+    }
+}
+
+fn level(value: Option<f64>, unit: &str) -> String {
+    value.map_or_else(
+        || "silence".to_string(),
+        |value| format!("{value:.1} {unit}"),
+    )
+}
+
+// Negative infinity of silent streams is serialized as `null`.
+#[derive(Debug, Serialize)]
+struct StreamLevels {
+    client_id: String,
+    source: String,
+    original: Option<Levels>,
+    processed: Option<Levels>,
+}
+
+impl From<(uuid::Uuid, crate::levels::LevelsSnapshot)> for StreamLevels {
+    fn from((client_id, snapshot): (uuid::Uuid, crate::levels::LevelsSnapshot)) -> Self {
+        Self {
+            client_id: client_id.to_string(),
+            source: snapshot.source,
+            original: snapshot.original.map(Levels::from),
+            processed: snapshot.processed.map(Levels::from),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct SourceStatus {
+    url: String,
+    subscribers: usize,
+    live_lag_ms: Option<u64>,
+}
+
+impl From<crate::sources::SourceSnapshot> for SourceStatus {
+    fn from(snapshot: crate::sources::SourceSnapshot) -> Self {
+        Self {
+            url: snapshot.url,
+            subscribers: snapshot.subscribers,
+            live_lag_ms: snapshot
+                .live_lag
+                .map(|lag| u64::try_from(lag.as_millis()).unwrap_or(u64::MAX)),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Levels {
+    momentary: f64,
+    short_term: f64,
+    channels: Vec<ChannelLevels>,
+}
+
+impl From<codec::dsp::Levels> for Levels {
+    fn from(levels: codec::dsp::Levels) -> Self {
+        Self {
+            momentary: levels.momentary,
+            short_term: levels.short_term,
+            channels: levels
+                .channels
+                .into_iter()
+                .map(|channel| ChannelLevels {
+                    rms: channel.rms,
+                    peak: channel.peak,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ChannelLevels {
+    rms: f64,
+    peak: f64,
+}
@@ -3,23 +3,24 @@ use std::{
     time::Duration,
 };
 
-use anyhow::anyhow;
 use async_stream::stream;
 use axum::{
     body::StreamBody,
     extract::{Query, State},
-    http::header::{self},
-    response::IntoResponse,
+    http::{
+        header::{self},
+        HeaderMap, HeaderName, HeaderValue, StatusCode,
+    },
+    response::{IntoResponse, Response},
     routing::get,
     Router, TypedHeader,
 };
 use futures::Stream;
 
-use analyzer::{BufferedAnalyzer, LabelSmoother};
-use codec::{
-    dsp::{CrossFader, LinearCrossFade, ParabolicCrossFade},
-    Decoder, Encoder, FrameDuration,
-};
+use codec::dsp::TransitionPlanner;
+use unstreamer::icy::{IcyTitle, IcyWriter, ICY_METADATA_HEADER, ICY_METAINT_HEADER, METAINT};
+
+mod hls;
 
 mod play_params;
 use play_params::{PlayAction, PlayParams};
@@ -27,43 +28,83 @@ use play_params::{PlayAction, PlayParams};
 mod mixer;
 use mixer::{AdsMixer, Mixer, PassthroughMixer, SilenceMixer};
 
+mod output_codec;
+use output_codec::OutputCodec;
+
+mod gap_filler;
+use gap_filler::GapFiller;
+
 use crate::{
     accept_header::Accept,
     ads_management::AdsPlanner,
+    signal::Signal,
+    sources::{SourceInfo, SourceItem},
     state::AppState,
     stream_saver::{Destination, StreamSaver},
 };
 
-const OUTPUT_MIME: &str = "audio/aac";
-
 pub fn router(state: AppState) -> Router {
-    Router::new().route("/", get(serve)).with_state(state)
+    Router::new()
+        .route("/", get(serve))
+        .route("/hls", get(hls::start))
+        .route("/hls/:id/:name", get(hls::file))
+        .with_state(state)
 }
 
 async fn serve(
-    TypedHeader(accept): TypedHeader<Accept>,
+    accept: Option<TypedHeader<Accept>>,
+    request_headers: HeaderMap,
     Query(params): Query<PlayParams>,
     State(state): State<AppState>,
-) -> impl IntoResponse {
+) -> Response {
     log::info!(
         "Serve {}, action={:?}",
         params.source,
         params.action.as_ref().unwrap_or(&PlayAction::Passthrough)
     );
 
+    let accept = accept.map(|TypedHeader(accept)| accept).unwrap_or_default();
     log::info!("Client accepts: {accept}");
-    log::info!("Server serves: {OUTPUT_MIME}");
 
-    let headers = [
-        (header::CONTENT_TYPE, OUTPUT_MIME),
-        (header::TRANSFER_ENCODING, "chunked"),
-    ];
+    let Some(output_codec) = OutputCodec::negotiate(&accept) else {
+        log::info!("No acceptable output format");
+        return (
+            StatusCode::NOT_ACCEPTABLE,
+            format!(
+                "Supported formats: {}",
+                OutputCodec::ALL.map(OutputCodec::mime_str).join(", ")
+            ),
+        )
+            .into_response();
+    };
+
+    log::info!("Server serves: {}", output_codec.mime_str());
 
-    (headers, get_stream(params, state))
+    let icy = request_headers
+        .get(ICY_METADATA_HEADER)
+        .is_some_and(|value| value == "1");
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static(output_codec.mime_str()),
+    );
+    headers.insert(
+        header::TRANSFER_ENCODING,
+        HeaderValue::from_static("chunked"),
+    );
+    if icy {
+        log::info!("Client accepts ICY metadata");
+        headers.insert(HeaderName::from_static(ICY_METAINT_HEADER), METAINT.into());
+    }
+
+    (headers, get_stream(params, output_codec, icy, state)).into_response()
 }
 
 fn get_stream(
     params: PlayParams,
+    output_codec: OutputCodec,
+    icy: bool,
     state: AppState,
 ) -> StreamBody<impl Stream<Item = anyhow::Result<Vec<u8>>>> {
     stream! {
@@ -79,7 +120,13 @@ fn get_stream(
             let state= state.clone();
             std::thread::spawn(move || {
                 tokio::runtime::Runtime::new()?.block_on(async move {
-                    analyze(params, writer, &state).await
+                    let title = IcyTitle::default();
+                    if icy {
+                        let writer = IcyWriter::new(writer, METAINT, title.clone());
+                        analyze(params, output_codec, writer, title, &state).await
+                    } else {
+                        analyze(params, output_codec, writer, title, &state).await
+                    }
                 })})
         };
 
@@ -116,84 +163,110 @@ fn get_stream(
     .into()
 }
 
-const CROSS_FADE_DURATION: Duration = Duration::from_millis(1_500);
+/// Longest a cut waits for a quiet point or beat.
+const TRANSITION_TOLERANCE: Duration = Duration::from_millis(750);
 
 async fn analyze<W: Write + Send>(
     params: PlayParams,
+    output_codec: OutputCodec,
     writer: W,
+    title: IcyTitle,
     state: &AppState,
 ) -> anyhow::Result<()> {
-    let input = unstreamer::Unstreamer::open(&params.source)?;
-
-    let mut decoder = Decoder::try_from(input)?;
-    let first_frame = decoder.next().ok_or_else(|| anyhow!("No audio frame"))??;
-
-    let codec_params = decoder
-        .codec_params()
-        .with_samples_per_frame(first_frame.samples());
-
-    log::info!("Input media info {codec_params:?}");
-
-    let mut encoder = Encoder::aac(codec_params, writer)?;
+    let mut subscription = state
+        .sources
+        .subscribe(
+            &params.source,
+            params.analyzer_config(&state.args.analyzer),
+            state,
+        )
+        .with_title(title);
+    let SourceInfo {
+        codec_params,
+        frame_duration,
+    } = subscription.info()?;
+
+    let mut encoder = output_codec.encoder(codec_params, writer)?;
     log::info!("Output media info {:?}", encoder.codec_params());
 
     let mut stream_saver = StreamSaver::new(state.args.is_recording_enabled(), codec_params)?;
+    let mut meter = state.levels.create(&params.source, codec_params);
 
-    let mut analyzer = BufferedAnalyzer::new(
-        LabelSmoother::new(
-            Duration::from_millis(state.args.smooth_behind),
-            Duration::from_millis(state.args.smooth_ahead),
-        ),
-        state.args.clone().into(),
-    );
+    let cross_fades = params.cross_fade_config(&state.args.cross_fades);
+    let into_ad = cross_fades.fader_into_ad(frame_duration);
+    let out_of_ad = cross_fades.fader_out_of_ad(frame_duration);
 
-    let cross_fader =
-        CrossFader::new::<ParabolicCrossFade>(CROSS_FADE_DURATION, first_frame.duration());
+    let entry = cross_fades.entry_fader(frame_duration);
 
-    let entry = CrossFader::new::<LinearCrossFade>(CROSS_FADE_DURATION, first_frame.duration());
+    let transitions = TransitionPlanner::new(TRANSITION_TOLERANCE);
 
     let action = params.action.unwrap_or(PlayAction::Passthrough);
     let mut mixer: Box<dyn Mixer> = match action {
         PlayAction::Passthrough => Box::new(PassthroughMixer::new()),
-        PlayAction::Silence => Box::new(SilenceMixer::new(cross_fader)),
-        PlayAction::Replace => Box::new(AdsMixer::new(
-            AdsPlanner::new(state.ads_provider.clone(), codec_params).await?,
-            encoder.pts()?,
-            cross_fader,
-        )),
+        PlayAction::Silence => {
+            Box::new(SilenceMixer::new(into_ad, out_of_ad).with_transitions(transitions))
+        }
+        PlayAction::Replace => Box::new(
+            AdsMixer::new(
+                AdsPlanner::new(state.ads_provider.clone(), codec_params, meter.handle()).await?,
+                encoder.pts()?,
+                into_ad,
+                out_of_ad,
+            )
+            .with_transitions(transitions),
+        ),
     };
 
-    for frame in decoder {
+    let mut gap_filler = GapFiller::new(state.args.gap_filler.clone(), codec_params);
+    let mut fallback = state
+        .args
+        .dead_air_fallback
+        .clone()
+        .map(|track| GapFiller::new(Some(track), codec_params));
+    let mut last_frame = None;
+
+    for item in subscription {
         if state.terminator.is_terminated() {
             break;
         }
 
-        let frame = frame?;
+        let (signal, frame) = match item? {
+            SourceItem::Frame(signal, frame) => {
+                last_frame = Some(frame.clone());
+                (signal, frame)
+            }
+            SourceItem::Gap => {
+                // Nothing to take the format from before the first frame.
+                let Some(last) = &last_frame else {
+                    continue;
+                };
+                (
+                    Signal::Content(analyzer::ContentKind::Unknown),
+                    gap_filler.next(last),
+                )
+            }
+        };
+        meter.push(Destination::Original, &frame);
         stream_saver.push(Destination::Original, frame.clone());
 
-        analyzer.push(frame)?;
+        let frame = match (signal, fallback.as_mut()) {
+            (Signal::DeadAir, Some(fallback)) => fallback.next(&frame),
+            _ => frame,
+        };
 
-        for (kind, frame) in analyzer.pop()? {
-            if state.terminator.is_terminated() {
-                break;
+        let signal = match signal {
+            Signal::Content(_) if state.args.advert => {
+                Signal::Content(analyzer::ContentKind::Advertisement)
             }
+            signal => signal,
+        };
+        let frame = mixer.push(signal, &frame).await;
+        let frame = entry.apply(&codec::silence_frame(&frame), &frame);
 
-            let frame = mixer
-                .push(
-                    if state.args.advert {
-                        analyzer::ContentKind::Advertisement
-                    } else {
-                        kind
-                    },
-                    &frame,
-                )
-                .await;
-            let frame = entry.apply(&codec::silence_frame(&frame), &frame);
-
-            stream_saver.push(Destination::Processed, frame.clone());
+        meter.push(Destination::Processed, &frame);
+        stream_saver.push(Destination::Processed, frame.clone());
 
-            encoder.push(frame)?;
-        }
+        encoder.push(frame)?;
     }
 
     stream_saver.terminate();
@@ -0,0 +1,53 @@
+use std::path::PathBuf;
+
+use codec::{AudioFrame, CodecParams, Decoder};
+
+/// Plays to listeners instead of the source, e.g. while it reconnects: the track in a loop, or silence.
+pub struct GapFiller {
+    track: Option<PathBuf>,
+    codec_params: CodecParams,
+    frames: Option<Vec<AudioFrame>>,
+    position: usize,
+}
+
+impl GapFiller {
+    pub const fn new(track: Option<PathBuf>, codec_params: CodecParams) -> Self {
+        Self {
+            track,
+            codec_params,
+            frames: None,
+            position: 0,
+        }
+    }
+
+    /// Next frame of the filler, `last` is the last source frame.
+    pub fn next(&mut self, last: &AudioFrame) -> AudioFrame {
+        // The track is loaded on the first gap, most listeners never need it.
+        if self.frames.is_none() {
+            self.frames = Some(self.load().unwrap_or_else(|err| {
+                log::error!("Failed to load gap filler, use silence: {err:#}");
+                vec![]
+            }));
+        }
+
+        match self.frames.as_deref() {
+            Some(frames) if !frames.is_empty() => {
+                let frame = frames[self.position % frames.len()].clone();
+                self.position += 1;
+                frame
+            }
+            _ => codec::silence_frame(last),
+        }
+    }
+
+    fn load(&self) -> anyhow::Result<Vec<AudioFrame>> {
+        let Some(path) = &self.track else {
+            return Ok(vec![]);
+        };
+
+        log::info!("Load gap filler {}", path.display());
+        Decoder::try_from(std::fs::File::open(path)?)?
+            .resample(self.codec_params)
+            .collect()
+    }
+}
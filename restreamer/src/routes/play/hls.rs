@@ -0,0 +1,104 @@
+use std::time::{Duration, Instant};
+
+use axum::{
+    extract::{OriginalUri, Path, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+};
+use unstreamer::icy::IcyTitle;
+use uuid::Uuid;
+
+use crate::{
+    hls::{HlsSession, Segmenter, MIME_HLS, PLAYLIST_NAME, SEGMENT_DURATION},
+    state::AppState,
+};
+
+use super::{analyze, OutputCodec, PlayAction, PlayParams};
+
+// How long the first playlist request waits for the first segment, less than the session idle timeout.
+const FIRST_SEGMENT_TIMEOUT: Duration = Duration::from_secs(20);
+const FIRST_SEGMENT_POLL: Duration = Duration::from_millis(200);
+
+pub async fn start(
+    OriginalUri(uri): OriginalUri,
+    Query(params): Query<PlayParams>,
+    State(state): State<AppState>,
+) -> Response {
+    let (id, session) = state.hls_sessions.create();
+
+    log::info!(
+        "Serve HLS {} as {id}, action={:?}",
+        params.source,
+        params.action.as_ref().unwrap_or(&PlayAction::Passthrough)
+    );
+
+    std::thread::spawn(move || {
+        let writer = Segmenter::new(session.clone(), SEGMENT_DURATION);
+        let result = tokio::runtime::Runtime::new()
+            .map_err(Into::into)
+            .and_then(|runtime| {
+                runtime.block_on(analyze(
+                    params,
+                    OutputCodec::Aac,
+                    writer,
+                    IcyTitle::default(),
+                    &state,
+                ))
+            });
+
+        match result {
+            Ok(()) => log::info!("HLS session {id} finished"),
+            Err(_) if session.is_expired() => log::info!("HLS session {id} closed"),
+            Err(err) => log::error!("HLS session {id} failed: {err:?}"),
+        }
+    });
+
+    Redirect::temporary(&format!(
+        "{}/{id}/{PLAYLIST_NAME}",
+        uri.path().trim_end_matches('/')
+    ))
+    .into_response()
+}
+
+pub async fn file(
+    Path((id, name)): Path<(Uuid, String)>,
+    State(state): State<AppState>,
+) -> Response {
+    let Some(session) = state.hls_sessions.get(&id) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    if name == PLAYLIST_NAME {
+        playlist(&session).await
+    } else {
+        session.segment(&name).map_or_else(
+            || StatusCode::NOT_FOUND.into_response(),
+            |data| ([(header::CONTENT_TYPE, OutputCodec::Aac.mime_str())], data).into_response(),
+        )
+    }
+}
+
+async fn playlist(session: &HlsSession) -> Response {
+    let started = Instant::now();
+    while !session.is_ready() {
+        if started.elapsed() > FIRST_SEGMENT_TIMEOUT {
+            return StatusCode::SERVICE_UNAVAILABLE.into_response();
+        }
+        tokio::time::sleep(FIRST_SEGMENT_POLL).await;
+    }
+
+    match session.playlist() {
+        Ok(playlist) => (
+            [
+                (header::CONTENT_TYPE, MIME_HLS),
+                (header::CACHE_CONTROL, "no-cache"),
+            ],
+            playlist,
+        )
+            .into_response(),
+        Err(err) => {
+            log::error!("Failed to build HLS playlist: {err:#}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
@@ -1,7 +1,8 @@
-use analyzer::ContentKind;
 use axum::async_trait;
 use codec::AudioFrame;
 
+use crate::signal::Signal;
+
 mod ads;
 mod passthrough;
 mod silence;
@@ -12,7 +13,7 @@ pub use silence::SilenceMixer;
 
 #[async_trait]
 pub trait Mixer: Send {
-    async fn push(&mut self, kind: ContentKind, frame: &AudioFrame) -> AudioFrame;
+    async fn push(&mut self, signal: Signal, frame: &AudioFrame) -> AudioFrame;
 }
 
 #[cfg(test)]
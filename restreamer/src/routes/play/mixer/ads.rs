@@ -1,13 +1,22 @@
 use std::collections::VecDeque;
+use std::time::Duration;
 
 use axum::async_trait;
-use codec::dsp::CrossFader;
-use codec::{AudioFrame, Pts};
+use codec::dsp::{apply_gain, db_to_gain, CrossFader, Limiter, LoudnessMeter, TransitionPlanner};
+use codec::{AudioFrame, FrameDuration, Pts};
 
-use crate::ads_management::AdsPlanner;
+use crate::ads_management::{AdsPlanner, PlannedAd};
+use crate::signal::Signal;
 
 use super::Mixer;
 
+// Ads are matched to the program of the last half a minute.
+const PROGRAM_LOUDNESS_WINDOW: Duration = Duration::from_secs(30);
+const MAX_GAIN_DB: f64 = 12.0;
+// Of sample peaks, ads measured to peak under it between samples too are not limited.
+const PEAK_CEILING_DB: f64 = -1.0;
+const LIMITER_RELEASE: Duration = Duration::from_millis(200);
+
 #[derive(Debug, Eq, PartialEq)]
 enum Track {
     Main,
@@ -16,54 +25,129 @@ enum Track {
 
 pub struct AdsMixer {
     ads_planner: AdsPlanner,
-    cross_fader: CrossFader,
+    into_ad: CrossFader,
+    out_of_ad: CrossFader,
     pts: Pts,
     main_track: VecDeque<AudioFrame>,
     side_track: VecDeque<AudioFrame>,
     side_buffer: VecDeque<AudioFrame>,
     active_track: Track,
+    program_loudness: Option<LoudnessMeter>,
+    transitions: Option<TransitionPlanner>,
 }
 
 #[async_trait]
 impl Mixer for AdsMixer {
-    async fn push(&mut self, kind: analyzer::ContentKind, frame: &AudioFrame) -> AudioFrame {
+    async fn push(&mut self, signal: Signal, frame: &AudioFrame) -> AudioFrame {
         self.pts.update(frame);
-        match kind {
-            analyzer::ContentKind::Advertisement => self.advertisement(frame).await,
-            analyzer::ContentKind::Music
-            | analyzer::ContentKind::Talk
-            | analyzer::ContentKind::Unknown => self.content(frame).await,
+        if let Some(transitions) = &mut self.transitions {
+            transitions.push(frame);
+        }
+        match signal {
+            Signal::Content(analyzer::ContentKind::Advertisement) => {
+                self.advertisement(frame).await
+            }
+            Signal::Content(
+                analyzer::ContentKind::Music
+                | analyzer::ContentKind::Talk
+                | analyzer::ContentKind::Unknown,
+            ) => {
+                self.measure_program(frame);
+                self.content(frame).await
+            }
+            // Neither silence nor a fallback over it is the program ads are matched to.
+            Signal::DeadAir => self.content(frame).await,
         }
     }
 }
 
 impl AdsMixer {
-    pub fn new(ads_planner: AdsPlanner, pts: Pts, cross_fader: CrossFader) -> Self {
-        cross_fader.drain();
+    pub fn new(
+        ads_planner: AdsPlanner,
+        pts: Pts,
+        into_ad: CrossFader,
+        out_of_ad: CrossFader,
+    ) -> Self {
+        into_ad.drain();
+        out_of_ad.drain();
         Self {
             ads_planner,
-            cross_fader,
+            into_ad,
+            out_of_ad,
             main_track: VecDeque::new(),
             side_track: VecDeque::new(),
             side_buffer: VecDeque::new(),
             pts,
             active_track: Track::Main,
+            program_loudness: None,
+            transitions: None,
         }
     }
 
+    /// Cuts into ads at a quiet point or beat of the original, with a fade fitting it.
+    pub fn with_transitions(mut self, transitions: TransitionPlanner) -> Self {
+        self.transitions = Some(transitions);
+        self
+    }
+
     fn pts(&mut self, frame: AudioFrame) -> AudioFrame {
         frame.with_pts(self.pts.next())
     }
 
+    /// Gain of the ad to sound as loud as the program, limited if it would peak over the ceiling.
+    fn match_loudness(&self, ad: PlannedAd) -> Vec<AudioFrame> {
+        let program = self
+            .program_loudness
+            .as_ref()
+            .map_or(f64::NEG_INFINITY, LoudnessMeter::integrated);
+        let Some(loudness) = ad.loudness else {
+            return ad.frames;
+        };
+        if !program.is_finite() || !loudness.integrated.is_finite() {
+            return ad.frames;
+        }
+
+        let gain_db = (program - loudness.integrated).clamp(-MAX_GAIN_DB, MAX_GAIN_DB);
+        log::debug!(
+            "Ad gain {gain_db:.1}dB, program {program:.1}LUFS, ad {:.1}LUFS",
+            loudness.integrated
+        );
+
+        let gain = db_to_gain(gain_db);
+        if loudness.true_peak + gain_db <= PEAK_CEILING_DB {
+            ad.frames
+                .iter()
+                .map(|frame| apply_gain(frame, gain))
+                .collect()
+        } else {
+            let mut limiter = Limiter::new(PEAK_CEILING_DB, LIMITER_RELEASE);
+            ad.frames
+                .iter()
+                .map(|frame| limiter.apply(frame, gain))
+                .collect()
+        }
+    }
+
+    fn measure_program(&mut self, frame: &AudioFrame) {
+        self.program_loudness
+            .get_or_insert_with(|| {
+                LoudnessMeter::new(frame.sample_rate()).with_window(PROGRAM_LOUDNESS_WINDOW)
+            })
+            .push(frame);
+    }
+
     async fn content(&mut self, frame: &AudioFrame) -> AudioFrame {
         self.main_track.push_back(frame.clone());
         self.side_buffer.clear();
+        if let Some(transitions) = &mut self.transitions {
+            transitions.cancel();
+        }
 
-        let output = if self.side_track.len() > self.cross_fader.len() {
+        let output = if self.side_track.len() > self.out_of_ad.len() {
             self.side_track.pop_front().unwrap()
         } else {
             if self.active_track == Track::Side {
-                self.cross_fader.reset();
+                self.out_of_ad.reset();
                 self.active_track = Track::Main;
             }
             let ad = self
@@ -75,7 +159,7 @@ impl AdsMixer {
             if self.side_track.is_empty() {
                 self.ads_planner.finished().await;
             }
-            self.cross_fader.apply(&ad, &content)
+            self.out_of_ad.apply(&ad, &content)
         };
         self.pts(output)
     }
@@ -85,14 +169,31 @@ impl AdsMixer {
 
         let output = if self.main_track.is_empty() {
             if self.active_track == Track::Main {
-                self.cross_fader.reset();
+                let length = self.into_ad.base_duration(frame.duration());
+                match self
+                    .transitions
+                    .as_mut()
+                    .map(|transitions| transitions.transition(length))
+                {
+                    // The original plays on until a point to cut at.
+                    Some(None) => {
+                        let original = self.side_buffer.pop_front().unwrap();
+                        return self.pts(original);
+                    }
+                    Some(Some(transition)) => {
+                        log::debug!("Cut into ads, {transition:?}");
+                        self.into_ad.reset_to(transition.length, frame.duration());
+                    }
+                    None => self.into_ad.reset(),
+                }
                 self.active_track = Track::Side;
             }
 
             if self.side_track.is_empty() {
                 self.ads_planner.finished().await;
-                self.side_track
-                    .extend(self.ads_planner.next().await.unwrap());
+                let ad = self.ads_planner.next().await.unwrap();
+                let frames = self.match_loudness(ad);
+                self.side_track.extend(frames);
             }
 
             let content = self
@@ -100,7 +201,7 @@ impl AdsMixer {
                 .pop_front()
                 .unwrap_or_else(|| codec::silence_frame(frame));
             let ad = self.side_track.pop_front().unwrap();
-            self.cross_fader.apply(&content, &ad)
+            self.into_ad.apply(&content, &ad)
         } else {
             self.main_track.pop_front().unwrap()
         };
@@ -111,19 +212,23 @@ impl AdsMixer {
 #[cfg(test)]
 #[allow(clippy::float_cmp)]
 mod tests {
+    use std::f32::consts::PI;
     use std::time::Duration;
 
+    use ac_ffmpeg::codec::audio::{AudioFrameMut, ChannelLayout};
     use analyzer::ContentKind;
-    use codec::dsp::{CrossFader, ParabolicCrossFade};
-    use codec::{AudioFrame, Pts, Timestamp};
+    use codec::dsp::{db_to_gain, CrossFader, Loudness, ParabolicCrossFade};
+    use codec::{AudioFrame, FrameSamples, FrameSamplesMut, Pts, SampleFormat, Timestamp};
     use nearly::assert_nearly_eq;
 
-    use crate::ads_management::AdsPlanner;
+    use crate::ads_management::{AdsPlanner, PlannedAd};
     use crate::routes::play::mixer::tests::{create_frames, pts_seq, SamplesAsVec};
+    use crate::signal::Signal;
 
-    use super::{AdsMixer, Mixer};
+    use super::{AdsMixer, Mixer, PEAK_CEILING_DB};
 
     const PTS: Pts = Pts::const_new(Duration::from_secs(1));
+    const MUSIC: Signal = Signal::Content(ContentKind::Music);
 
     #[tokio::test]
     async fn test_one_ads_block_short_buffer() {
@@ -131,6 +236,7 @@ mod tests {
             AdsPlanner::testing(create_frames(10, 0.5)).await,
             PTS,
             CrossFader::exact::<ParabolicCrossFade>(4),
+            CrossFader::exact::<ParabolicCrossFade>(4),
         ));
         player
             .content(5)
@@ -186,6 +292,7 @@ mod tests {
             AdsPlanner::testing(create_frames(10, 0.5)).await,
             PTS,
             CrossFader::exact::<ParabolicCrossFade>(4),
+            CrossFader::exact::<ParabolicCrossFade>(4),
         ));
 
         player
@@ -254,6 +361,7 @@ mod tests {
             AdsPlanner::testing(create_frames(10, 0.5)).await,
             PTS,
             CrossFader::exact::<ParabolicCrossFade>(2),
+            CrossFader::exact::<ParabolicCrossFade>(2),
         ));
 
         player
@@ -279,6 +387,81 @@ mod tests {
         assert_eq!(player.timestamps(), pts_seq(20));
     }
 
+    // Three seconds of a 1kHz tone at 48kHz.
+    fn tone(amplitude: f32) -> Vec<AudioFrame> {
+        let samples = (0..3 * 48_000)
+            .map(|n| amplitude * (2.0 * PI * n as f32 / 48.0).sin())
+            .collect::<Vec<_>>();
+        samples
+            .chunks(1_024)
+            .map(|chunk| {
+                let mut frame = AudioFrameMut::silence(
+                    ChannelLayout::from_channels(1).unwrap().as_ref(),
+                    SampleFormat::Flt.into(),
+                    48_000,
+                    chunk.len(),
+                );
+                frame.set_channel_samples(&[chunk.to_vec()]);
+                frame.freeze()
+            })
+            .collect()
+    }
+
+    /// Loudness of the program and of the matched ad, and the sample peak of the matched ad.
+    async fn match_loudness(program: f32, ad: f32) -> (f64, f64, f32) {
+        let mut mixer = AdsMixer::new(
+            AdsPlanner::testing(create_frames(1, 0.5)).await,
+            PTS,
+            CrossFader::exact::<ParabolicCrossFade>(2),
+            CrossFader::exact::<ParabolicCrossFade>(2),
+        );
+        let program = tone(program);
+        for frame in &program {
+            mixer.measure_program(frame);
+        }
+
+        let frames = tone(ad);
+        let loudness = Loudness::measure(&frames);
+        let matched = mixer.match_loudness(PlannedAd { frames, loudness });
+
+        let peak = matched
+            .iter()
+            .flat_map(|frame| frame.channel_samples::<f32>().concat())
+            .fold(0.0_f32, |peak, sample| peak.max(sample.abs()));
+        (
+            Loudness::measure(&program).unwrap().integrated,
+            Loudness::measure(&matched).unwrap().integrated,
+            peak,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_quieter_ad() {
+        let (program, ad, peak) = match_loudness(0.5, 0.25).await;
+        assert_nearly_eq!(ad, program, eps = 0.01);
+        assert_nearly_eq!(peak, 0.5, eps = 1e-3);
+    }
+
+    #[tokio::test]
+    async fn test_louder_ad() {
+        let (program, ad, peak) = match_loudness(0.125, 0.5).await;
+        assert_nearly_eq!(ad, program, eps = 0.01);
+        assert_nearly_eq!(peak, 0.125, eps = 1e-3);
+
+        // The gain is at most 12dB.
+        let (program, ad, peak) = match_loudness(0.01, 0.5).await;
+        assert!(ad > program + 6.0);
+        assert_nearly_eq!(peak, 0.125, eps = 1e-3);
+    }
+
+    #[tokio::test]
+    async fn test_limited_ad() {
+        // The gain brings the ad over the ceiling.
+        let (program, ad, peak) = match_loudness(0.9, 0.3).await;
+        assert!(peak <= db_to_gain(PEAK_CEILING_DB) + 1e-4);
+        assert!(ad < program && ad > program - 0.5);
+    }
+
     struct Player {
         mixer: AdsMixer,
         frame: AudioFrame,
@@ -297,8 +480,7 @@ mod tests {
 
         async fn content(&mut self, length: usize) -> &mut Self {
             for _ in 0..length {
-                self.output
-                    .push(self.mixer.push(ContentKind::Music, &self.frame).await);
+                self.output.push(self.mixer.push(MUSIC, &self.frame).await);
             }
             self
         }
@@ -307,7 +489,7 @@ mod tests {
             for _ in 0..length {
                 self.output.push(
                     self.mixer
-                        .push(ContentKind::Advertisement, &self.frame)
+                        .push(Signal::Content(ContentKind::Advertisement), &self.frame)
                         .await,
                 );
             }
@@ -316,8 +498,7 @@ mod tests {
 
         async fn silence(&mut self, length: usize) -> &mut Self {
             for frame in create_frames(length, 0.0) {
-                self.output
-                    .push(self.mixer.push(ContentKind::Music, &frame).await);
+                self.output.push(self.mixer.push(MUSIC, &frame).await);
             }
             self
         }
@@ -1,6 +1,8 @@
 use axum::async_trait;
 use codec::{AudioFrame, Pts};
 
+use crate::signal::Signal;
+
 use super::Mixer;
 
 pub struct PassthroughMixer(Pts);
@@ -13,7 +15,7 @@ impl PassthroughMixer {
 
 #[async_trait]
 impl Mixer for PassthroughMixer {
-    async fn push(&mut self, _kind: analyzer::ContentKind, frame: &AudioFrame) -> AudioFrame {
+    async fn push(&mut self, _signal: Signal, frame: &AudioFrame) -> AudioFrame {
         self.0.update(frame);
         frame.clone().with_pts(self.0.next())
     }
@@ -1,72 +1,102 @@
 use axum::async_trait;
-use codec::{dsp::CrossFader, AudioFrame, Pts};
+use codec::{
+    dsp::{CrossFader, TransitionPlanner},
+    AudioFrame, FrameDuration, Pts,
+};
+
+use crate::signal::Signal;
 
 use super::Mixer;
 
 pub struct SilenceMixer {
-    cross_fader: CrossFader,
+    into_ad: CrossFader,
+    out_of_ad: CrossFader,
     ad_segment: bool,
     pts: Pts,
+    transitions: Option<TransitionPlanner>,
 }
 
 impl SilenceMixer {
-    pub fn new(cross_fader: CrossFader) -> Self {
+    pub fn new(into_ad: CrossFader, out_of_ad: CrossFader) -> Self {
         Self {
-            cross_fader,
+            into_ad,
+            out_of_ad,
             ad_segment: false,
             pts: Pts::new(2_048, 48_000),
+            transitions: None,
         }
     }
 
-    fn start_ad_segment(&mut self) {
-        if !self.ad_segment {
-            self.cross_fader.reset();
-            self.ad_segment = true;
-        }
+    /// Mutes and unmutes at quiet points or beats, with a fade fitting them.
+    pub fn with_transitions(mut self, transitions: TransitionPlanner) -> Self {
+        self.transitions = Some(transitions);
+        self
     }
 
-    fn stop_ad_segment(&mut self) {
-        if self.ad_segment {
-            self.cross_fader.reset();
-            self.ad_segment = false;
+    fn switch(&mut self, ad_segment: bool, frame: &AudioFrame) {
+        let cross_fader = if ad_segment {
+            &mut self.into_ad
+        } else {
+            &mut self.out_of_ad
+        };
+        let Some(transitions) = &mut self.transitions else {
+            if self.ad_segment != ad_segment {
+                cross_fader.reset();
+                self.ad_segment = ad_segment;
+            }
+            return;
+        };
+
+        transitions.push(frame);
+        if self.ad_segment == ad_segment {
+            transitions.cancel();
+        } else if let Some(transition) =
+            transitions.transition(cross_fader.base_duration(frame.duration()))
+        {
+            log::debug!("Ad segment {ad_segment}, {transition:?}");
+            cross_fader.reset_to(transition.length, frame.duration());
+            self.ad_segment = ad_segment;
         }
     }
 }
 
 #[async_trait]
 impl Mixer for SilenceMixer {
-    async fn push(&mut self, kind: analyzer::ContentKind, frame: &AudioFrame) -> AudioFrame {
+    async fn push(&mut self, signal: Signal, frame: &AudioFrame) -> AudioFrame {
         self.pts.update(frame);
         let silence = codec::silence_frame(frame);
 
-        let (fade_out, fade_in) = match kind {
-            analyzer::ContentKind::Advertisement => {
-                self.start_ad_segment();
-
-                (frame, &silence)
-            }
-            analyzer::ContentKind::Music
-            | analyzer::ContentKind::Talk
-            | analyzer::ContentKind::Unknown => {
-                self.stop_ad_segment();
-
-                (&silence, frame)
-            }
+        let ad_segment = match signal {
+            Signal::Content(analyzer::ContentKind::Advertisement) => true,
+            Signal::Content(
+                analyzer::ContentKind::Music
+                | analyzer::ContentKind::Talk
+                | analyzer::ContentKind::Unknown,
+            )
+            | Signal::DeadAir => false,
         };
+        self.switch(ad_segment, frame);
 
-        self.cross_fader
-            .apply(fade_out, fade_in)
-            .with_pts(self.pts.next())
+        let output = if self.ad_segment {
+            self.into_ad.apply(frame, &silence)
+        } else {
+            self.out_of_ad.apply(&silence, frame)
+        };
+        output.with_pts(self.pts.next())
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::time::Duration;
+
     use analyzer::ContentKind;
-    use codec::dsp::ParabolicCrossFade;
+    use codec::dsp::{LinearCrossFade, ParabolicCrossFade, TransitionPlanner};
+    use nearly::assert_nearly_eq;
 
     use crate::routes::play::mixer::silence::CrossFader;
     use crate::routes::play::mixer::tests::{create_frames, pts_seq, SamplesAsVec};
+    use crate::signal::Signal;
 
     use super::Mixer;
     use super::SilenceMixer;
@@ -75,20 +105,26 @@ mod tests {
     async fn test_music_to_advertisement() {
         let music = create_frames(20, 1.0);
 
-        let mut sut = SilenceMixer::new(CrossFader::exact::<ParabolicCrossFade>(3));
+        let mut sut = SilenceMixer::new(
+            CrossFader::exact::<ParabolicCrossFade>(3),
+            CrossFader::exact::<ParabolicCrossFade>(3),
+        );
 
         let mut output = vec![];
 
         for frame in music.iter().take(5) {
-            output.push(sut.push(ContentKind::Music, frame).await);
+            output.push(sut.push(Signal::Content(ContentKind::Music), frame).await);
         }
 
         for frame in music.iter().skip(5).take(10) {
-            output.push(sut.push(ContentKind::Advertisement, frame).await);
+            output.push(
+                sut.push(Signal::Content(ContentKind::Advertisement), frame)
+                    .await,
+            );
         }
 
         for frame in music.iter().skip(15) {
-            output.push(sut.push(ContentKind::Music, frame).await);
+            output.push(sut.push(Signal::Content(ContentKind::Music), frame).await);
         }
 
         let samples = output
@@ -111,4 +147,51 @@ mod tests {
 
         assert_eq!(timestamps, pts_seq(20));
     }
+
+    #[tokio::test]
+    async fn test_planned_transitions() {
+        let music = create_frames(10, 1.0);
+        let pause = create_frames(1, 0.0);
+
+        let mut sut = SilenceMixer::new(
+            CrossFader::exact::<LinearCrossFade>(4),
+            CrossFader::exact::<LinearCrossFade>(4),
+        )
+        .with_transitions(TransitionPlanner::new(Duration::from_secs(2)));
+
+        let mut output = vec![];
+
+        for frame in music.iter().take(5) {
+            output.push(sut.push(Signal::Content(ContentKind::Music), frame).await);
+        }
+
+        // Nothing to cut at, muted after the tolerance with the full fade.
+        for frame in music.iter().skip(5) {
+            output.push(
+                sut.push(Signal::Content(ContentKind::Advertisement), frame)
+                    .await,
+            );
+        }
+
+        // Unmuted right at the pause with a short fade.
+        for frame in pause.iter().chain(music.iter().take(2)) {
+            output.push(sut.push(Signal::Content(ContentKind::Music), frame).await);
+        }
+
+        let samples = output
+            .iter()
+            .flat_map(|frame| frame.samples_as_vec().into_iter())
+            .collect::<Vec<f32>>();
+
+        #[rustfmt::skip]
+        assert_nearly_eq!(
+            samples,
+            [
+                0.0, 0.333, 0.667, 1.0, 1.0,
+                1.0, 1.0, 1.0, 0.667, 0.333,
+                0.0, 1.0, 1.0
+            ],
+            eps = 1e-3
+        );
+    }
 }
@@ -0,0 +1,77 @@
+use std::io::Write;
+
+use codec::{CodecParams, Encoder};
+use mime::Mime;
+
+use crate::accept_header::Accept;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputCodec {
+    /// AAC in ADTS.
+    Aac,
+    /// Opus in Ogg.
+    Opus,
+    Mp3,
+}
+
+impl OutputCodec {
+    /// Supported codecs in order of server preference.
+    pub const ALL: [Self; 3] = [Self::Aac, Self::Opus, Self::Mp3];
+
+    pub const fn mime_str(self) -> &'static str {
+        match self {
+            Self::Aac => "audio/aac",
+            Self::Opus => "audio/ogg",
+            Self::Mp3 => "audio/mpeg",
+        }
+    }
+
+    pub fn mime(self) -> Mime {
+        self.mime_str().parse().expect("Valid mime")
+    }
+
+    pub fn negotiate(accept: &Accept) -> Option<Self> {
+        accept.negotiate(Self::ALL, |codec| codec.mime())
+    }
+
+    pub fn encoder<W: Write>(self, params: CodecParams, output: W) -> anyhow::Result<Encoder<W>> {
+        match self {
+            Self::Aac => Encoder::aac(params, output),
+            Self::Opus => Encoder::opus(params, output),
+            Self::Mp3 => Encoder::mp3(params, output),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn accept(mimes: &[&str]) -> Accept {
+        Accept::from(
+            mimes
+                .iter()
+                .map(|s| Mime::from_str(s).unwrap())
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    #[test]
+    fn test_negotiate() {
+        assert_eq!(
+            OutputCodec::negotiate(&Accept::default()),
+            Some(OutputCodec::Aac)
+        );
+        assert_eq!(
+            OutputCodec::negotiate(&accept(&["audio/ogg", "audio/*;q=0.5"])),
+            Some(OutputCodec::Opus)
+        );
+        assert_eq!(
+            OutputCodec::negotiate(&accept(&["audio/aac;q=0.2", "audio/mpeg;q=0.9"])),
+            Some(OutputCodec::Mp3)
+        );
+        assert_eq!(OutputCodec::negotiate(&accept(&["video/mp4"])), None);
+    }
+}
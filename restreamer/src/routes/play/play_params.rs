@@ -1,9 +1,68 @@
-use serde::Deserialize;
+use std::{fmt::Display, str::FromStr};
+
+use analyzer::{Amplification, AnalyzerConfig, ClassifyModel, MAX_REPEAT_SAMPLE};
+use codec::dsp::CrossFadeCurve;
+use serde::{de::Error, Deserialize, Deserializer};
+
+use crate::cross_fades::{CrossFadeConfig, MAX_CROSS_FADE_DURATION};
 
 #[derive(Debug, Deserialize)]
 pub struct PlayParams {
     pub source: String,
     pub action: Option<PlayAction>,
+    /// Overrides analyzer model.
+    #[serde(default, deserialize_with = "from_str")]
+    pub model: Option<ClassifyModel>,
+    /// Overrides per-class amplification, e.g. `1,5,5`.
+    #[serde(default, deserialize_with = "from_str")]
+    pub amplification: Option<Amplification>,
+    /// Overrides how many times samples are repeated.
+    #[serde(default, deserialize_with = "repeat_sample")]
+    pub repeat_sample: Option<usize>,
+    /// Overrides the entry fade curve, e.g. `equal-power`.
+    #[serde(default, deserialize_with = "from_str")]
+    pub entry_curve: Option<CrossFadeCurve>,
+    /// Overrides the entry fade duration in ms.
+    #[serde(default, deserialize_with = "cross_fade_duration")]
+    pub entry_duration: Option<u64>,
+    /// Overrides the curve of the cross-fade into ads.
+    #[serde(default, deserialize_with = "from_str")]
+    pub into_ad_curve: Option<CrossFadeCurve>,
+    /// Overrides the duration of the cross-fade into ads in ms.
+    #[serde(default, deserialize_with = "cross_fade_duration")]
+    pub into_ad_duration: Option<u64>,
+    /// Overrides the curve of the cross-fade out of ads.
+    #[serde(default, deserialize_with = "from_str")]
+    pub out_of_ad_curve: Option<CrossFadeCurve>,
+    /// Overrides the duration of the cross-fade out of ads in ms.
+    #[serde(default, deserialize_with = "cross_fade_duration")]
+    pub out_of_ad_duration: Option<u64>,
+}
+
+impl PlayParams {
+    /// Analyzer settings with request overrides applied on top of the defaults.
+    pub fn analyzer_config(&self, defaults: &AnalyzerConfig) -> AnalyzerConfig {
+        AnalyzerConfig {
+            model: self.model.unwrap_or(defaults.model),
+            models_dir: defaults.models_dir.clone(),
+            amplification: self.amplification.unwrap_or(defaults.amplification),
+            repeat_sample: self.repeat_sample.unwrap_or(defaults.repeat_sample),
+        }
+    }
+
+    /// Fades with request overrides applied on top of the defaults.
+    pub fn cross_fade_config(&self, defaults: &CrossFadeConfig) -> CrossFadeConfig {
+        CrossFadeConfig {
+            entry_curve: self.entry_curve.unwrap_or(defaults.entry_curve),
+            entry_duration: self.entry_duration.unwrap_or(defaults.entry_duration),
+            into_ad_curve: self.into_ad_curve.unwrap_or(defaults.into_ad_curve),
+            into_ad_duration: self.into_ad_duration.unwrap_or(defaults.into_ad_duration),
+            out_of_ad_curve: self.out_of_ad_curve.unwrap_or(defaults.out_of_ad_curve),
+            out_of_ad_duration: self
+                .out_of_ad_duration
+                .unwrap_or(defaults.out_of_ad_duration),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, PartialEq, Eq)]
@@ -13,3 +72,115 @@ pub enum PlayAction {
     Silence,
     Replace,
 }
+
+fn from_str<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|s| s.parse().map_err(D::Error::custom))
+        .transpose()
+}
+
+fn repeat_sample<'de, D>(deserializer: D) -> Result<Option<usize>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = from_str::<D, usize>(deserializer)?;
+    if value.is_some_and(|value| !(1..=MAX_REPEAT_SAMPLE).contains(&value)) {
+        return Err(D::Error::custom(format!(
+            "repeat_sample must be in 1..={MAX_REPEAT_SAMPLE}"
+        )));
+    }
+    Ok(value)
+}
+
+fn cross_fade_duration<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = from_str::<D, u64>(deserializer)?;
+    if value.is_some_and(|value| value > MAX_CROSS_FADE_DURATION) {
+        return Err(D::Error::custom(format!(
+            "Cross-fade duration must be in 0..={MAX_CROSS_FADE_DURATION}ms"
+        )));
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        extract::Query,
+        http::{StatusCode, Uri},
+        response::IntoResponse,
+    };
+
+    use super::*;
+
+    fn parse(query: &str) -> Result<PlayParams, String> {
+        let uri = format!("/play?{query}").parse::<Uri>().unwrap();
+        Query::<PlayParams>::try_from_uri(&uri)
+            .map(|Query(params)| params)
+            .map_err(|err| err.to_string())
+    }
+
+    #[test]
+    fn test_analyzer_config() {
+        let defaults = AnalyzerConfig::default();
+
+        let params = parse("source=http://radio").unwrap();
+        assert_eq!(params.analyzer_config(&defaults), defaults);
+
+        let params =
+            parse("source=http://radio&model=moat&amplification=1,2,3&repeat_sample=2").unwrap();
+        let config = params.analyzer_config(&defaults);
+        assert_eq!(config.model, ClassifyModel::MOAT);
+        assert_eq!(config.amplification, Amplification([1., 2., 3.]));
+        assert_eq!(config.repeat_sample, 2);
+        assert_eq!(config.models_dir, defaults.models_dir);
+    }
+
+    #[test]
+    fn test_invalid_analyzer_config() {
+        assert!(parse("source=http://radio&model=xyz").is_err());
+        assert!(parse("source=http://radio&amplification=1,2").is_err());
+        assert!(parse("source=http://radio&repeat_sample=0").is_err());
+        assert!(parse("source=http://radio&repeat_sample=100").is_err());
+    }
+
+    #[test]
+    fn test_cross_fade_config() {
+        let defaults = CrossFadeConfig::default();
+
+        let params = parse("source=http://radio").unwrap();
+        assert_eq!(params.cross_fade_config(&defaults), defaults);
+
+        let params = parse(
+            "source=http://radio&entry_curve=equal-power&into_ad_duration=500&out_of_ad_curve=cossin&out_of_ad_duration=0",
+        )
+        .unwrap();
+        let config = params.cross_fade_config(&defaults);
+        assert_eq!(config.entry_curve, CrossFadeCurve::EqualPower);
+        assert_eq!(config.entry_duration, defaults.entry_duration);
+        assert_eq!(config.into_ad_curve, defaults.into_ad_curve);
+        assert_eq!(config.into_ad_duration, 500);
+        assert_eq!(config.out_of_ad_curve, CrossFadeCurve::Cossin);
+        assert_eq!(config.out_of_ad_duration, 0);
+    }
+
+    #[test]
+    fn test_invalid_cross_fade_config() {
+        assert!(parse("source=http://radio&entry_curve=cubic").is_err());
+        assert!(parse("source=http://radio&into_ad_duration=-1").is_err());
+        assert!(parse("source=http://radio&out_of_ad_duration=10001").is_err());
+
+        let uri = "/play?source=http://radio&into_ad_curve=xyz"
+            .parse::<Uri>()
+            .unwrap();
+        let rejection = Query::<PlayParams>::try_from_uri(&uri).unwrap_err();
+        assert_eq!(rejection.into_response().status(), StatusCode::BAD_REQUEST);
+    }
+}
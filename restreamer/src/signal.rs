@@ -0,0 +1,10 @@
+use analyzer::ContentKind;
+
+/// What source frames carry for the mixers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signal {
+    /// Classified by the analyzer.
+    Content(ContentKind),
+    /// The source has been silent for too long.
+    DeadAir,
+}
@@ -0,0 +1,720 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::anyhow;
+use chrono::{DateTime, Utc};
+use flume::{RecvTimeoutError, TrySendError};
+
+use analyzer::{AnalyzerConfig, BufferedAnalyzer, ContentKind, LabelSmoother};
+use codec::dsp::{SilenceDetector, SilenceEvent};
+use codec::{AudioFrame, CodecParams, Decoder, FrameDuration};
+use unstreamer::{icy::IcyTitle, ConnectionStatus, LiveLag, OpenOptions, Unstreamer};
+
+use crate::{ads_management::AdsProvider, signal::Signal, state::AppState};
+
+// Classified frames buffered per listener, about 6s of AAC at 44.1kHz.
+// A listener falling behind further is disconnected, so it does not stall others.
+const SUBSCRIBER_CAPACITY: usize = 256;
+
+// How often a subscriber checks the source connection until the frame duration is known.
+const GAP_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+// How far a file or a finished playlist is decoded ahead of realtime, well within the subscriber queues.
+const PACING_LEAD: Duration = Duration::from_secs(2);
+
+// Listeners share a pipeline when they play the same URL with the same analyzer settings.
+type SourceKey = (String, AnalyzerConfig);
+
+/// Registry of running sources.
+///
+/// Every source is decoded and analyzed once, classified frames are fanned out to all subscribers.
+/// A source stops when its last subscriber goes away.
+#[derive(Clone, Default)]
+pub struct Sources(Arc<Mutex<HashMap<SourceKey, Arc<Source>>>>);
+
+impl Sources {
+    pub fn subscribe(&self, url: &str, config: AnalyzerConfig, state: &AppState) -> Subscription {
+        let (sender, receiver) = flume::bounded(SUBSCRIBER_CAPACITY);
+
+        let mut sources = self.0.lock().unwrap();
+        let source = sources
+            .entry((url.to_string(), config))
+            .or_insert_with_key(|(url, config)| {
+                let source = Arc::new(Source::new(url, config.clone()));
+                log::info!("Start source {url}, {config:?}");
+                spawn(source.clone(), state.clone());
+                source
+            })
+            .clone();
+        source.add(sender);
+        drop(sources);
+
+        log::info!("Source {url} has {} subscriber(s)", source.subscribers());
+
+        Subscription::new(receiver, source.connection.clone())
+    }
+
+    /// Removes the source if nobody listens to it anymore.
+    fn remove_idle(&self, source: &Arc<Source>) -> bool {
+        let mut sources = self.0.lock().unwrap();
+        let idle = source.subscribers() == 0;
+        if idle {
+            Self::remove_locked(&mut sources, source);
+        }
+        drop(sources);
+        idle
+    }
+
+    /// Running sources by URL.
+    pub fn snapshots(&self) -> Vec<SourceSnapshot> {
+        self.0
+            .lock()
+            .unwrap()
+            .values()
+            .map(|source| source.snapshot())
+            .collect()
+    }
+
+    fn remove(&self, source: &Arc<Source>) {
+        Self::remove_locked(&mut self.0.lock().unwrap(), source);
+    }
+
+    fn remove_locked(sources: &mut HashMap<SourceKey, Arc<Source>>, source: &Arc<Source>) {
+        let key = source.key();
+        if sources
+            .get(&key)
+            .is_some_and(|current| Arc::ptr_eq(current, source))
+        {
+            sources.remove(&key);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SourceInfo {
+    pub codec_params: CodecParams,
+    pub frame_duration: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub struct SourceSnapshot {
+    pub url: String,
+    pub subscribers: usize,
+    /// How far behind the live edge a live playlist or manifest is played.
+    pub live_lag: Option<Duration>,
+}
+
+#[derive(Clone)]
+enum SourceEvent {
+    Started(SourceInfo),
+    Frame(Signal, AudioFrame),
+    /// ICY stream title, sent before the first frame it applies to.
+    Title(String),
+    Failed(String),
+}
+
+struct Source {
+    url: String,
+    config: AnalyzerConfig,
+    connection: ConnectionStatus,
+    inner: Mutex<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    info: Option<SourceInfo>,
+    title: Option<String>,
+    lag: Option<LiveLag>,
+    senders: Vec<flume::Sender<SourceEvent>>,
+}
+
+impl Source {
+    fn new(url: &str, config: AnalyzerConfig) -> Self {
+        Self {
+            url: url.to_string(),
+            config,
+            connection: ConnectionStatus::default(),
+            inner: Mutex::new(Inner::default()),
+        }
+    }
+
+    fn key(&self) -> SourceKey {
+        (self.url.clone(), self.config.clone())
+    }
+
+    fn add(&self, sender: flume::Sender<SourceEvent>) {
+        let mut inner = self.inner.lock().unwrap();
+        // Late subscribers get the stream info right away.
+        if let Some(info) = inner.info {
+            _ = sender.try_send(SourceEvent::Started(info));
+        }
+        if let Some(title) = &inner.title {
+            _ = sender.try_send(SourceEvent::Title(title.clone()));
+        }
+        inner.senders.push(sender);
+        drop(inner);
+    }
+
+    fn subscribers(&self) -> usize {
+        self.inner.lock().unwrap().senders.len()
+    }
+
+    fn set_lag(&self, lag: Option<LiveLag>) {
+        self.inner.lock().unwrap().lag = lag;
+    }
+
+    fn snapshot(&self) -> SourceSnapshot {
+        let inner = self.inner.lock().unwrap();
+        SourceSnapshot {
+            url: self.url.clone(),
+            subscribers: inner.senders.len(),
+            live_lag: inner
+                .lag
+                .as_ref()
+                .filter(|lag| lag.is_live())
+                .map(LiveLag::get),
+        }
+    }
+
+    fn start(&self, info: SourceInfo) {
+        let mut inner = self.inner.lock().unwrap();
+        // Under the same lock as `add`, so nobody gets the info twice.
+        inner.info = Some(info);
+        self.send(&mut inner.senders, &SourceEvent::Started(info));
+        drop(inner);
+    }
+
+    fn set_title(&self, title: String) {
+        let mut inner = self.inner.lock().unwrap();
+        self.send(&mut inner.senders, &SourceEvent::Title(title.clone()));
+        inner.title = Some(title);
+        drop(inner);
+    }
+
+    fn broadcast(&self, event: &SourceEvent) {
+        self.send(&mut self.inner.lock().unwrap().senders, event);
+    }
+
+    fn send(&self, senders: &mut Vec<flume::Sender<SourceEvent>>, event: &SourceEvent) {
+        senders.retain(|sender| match sender.try_send(event.clone()) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => {
+                log::warn!("Subscriber of {} is too slow, disconnecting", self.url);
+                false
+            }
+            Err(TrySendError::Disconnected(_)) => false,
+        });
+    }
+
+    fn close(&self) {
+        self.inner.lock().unwrap().senders.clear();
+    }
+}
+
+pub enum SourceItem {
+    Frame(Signal, AudioFrame),
+    /// No frame for a frame duration while the source is reconnecting, the gap is to be filled.
+    Gap,
+}
+
+/// Receiving end of a source, disconnects from the source when dropped.
+pub struct Subscription {
+    receiver: flume::Receiver<SourceEvent>,
+    title: IcyTitle,
+    connection: ConnectionStatus,
+    gap_check_interval: Duration,
+}
+
+impl Subscription {
+    fn new(receiver: flume::Receiver<SourceEvent>, connection: ConnectionStatus) -> Self {
+        Self {
+            receiver,
+            title: IcyTitle::default(),
+            connection,
+            gap_check_interval: GAP_CHECK_INTERVAL,
+        }
+    }
+
+    /// Waits until the source is opened.
+    pub fn info(&mut self) -> anyhow::Result<SourceInfo> {
+        match self.receiver.recv() {
+            Ok(SourceEvent::Started(info)) => {
+                // Gaps are filled frame by frame.
+                self.gap_check_interval = info.frame_duration;
+                Ok(info)
+            }
+            Ok(SourceEvent::Failed(err)) => Err(anyhow!(err)),
+            Ok(SourceEvent::Frame(..) | SourceEvent::Title(_)) => {
+                unreachable!("Frame before the source is started")
+            }
+            Err(_) => Err(anyhow!("Source closed")),
+        }
+    }
+
+    /// Keeps the title up to date with received frames, if the source has one.
+    #[allow(clippy::missing_const_for_fn)]
+    pub fn with_title(self, title: IcyTitle) -> Self {
+        Self { title, ..self }
+    }
+}
+
+impl Iterator for Subscription {
+    type Item = anyhow::Result<SourceItem>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let event = match self.receiver.recv_timeout(self.gap_check_interval) {
+                Ok(event) => event,
+                Err(RecvTimeoutError::Timeout) => {
+                    if self.connection.is_reconnecting() {
+                        return Some(Ok(SourceItem::Gap));
+                    }
+                    continue;
+                }
+                Err(RecvTimeoutError::Disconnected) => return None,
+            };
+
+            match event {
+                SourceEvent::Frame(signal, frame) => {
+                    return Some(Ok(SourceItem::Frame(signal, frame)))
+                }
+                SourceEvent::Title(title) => self.title.set(&title),
+                SourceEvent::Failed(err) => return Some(Err(anyhow!(err))),
+                SourceEvent::Started(_) => unreachable!("Source is started twice"),
+            }
+        }
+    }
+}
+
+fn spawn(source: Arc<Source>, state: AppState) {
+    std::thread::spawn(move || {
+        match run(&source, &state) {
+            Ok(()) => log::info!("Source {} finished", source.url),
+            Err(err) => {
+                log::error!("Source {} failed: {err:?}", source.url);
+                source.broadcast(&SourceEvent::Failed(format!("{err:#}")));
+            }
+        }
+        state.sources.remove(&source);
+        source.close();
+    });
+}
+
+fn run(source: &Arc<Source>, state: &AppState) -> anyhow::Result<()> {
+    let input = Unstreamer::open_with(
+        &source.url,
+        &OpenOptions {
+            status: source.connection.clone(),
+            segments: state.args.segments_config(),
+        },
+    )?;
+    let titles = input.titles();
+    let boundaries = input.boundaries();
+    source.set_lag(input.lag());
+    // Anything but a live stream would be decoded as fast as possible and overflow the listeners.
+    let mut pacer = (!input.is_live()).then(|| Pacer::new(PACING_LEAD));
+
+    let mut decoder = Decoder::try_from(input)?;
+    let first_frame = decoder.next().ok_or_else(|| anyhow!("No audio frame"))??;
+
+    let codec_params = decoder
+        .codec_params()
+        .with_samples_per_frame(first_frame.samples());
+
+    log::info!("Input media info {codec_params:?}");
+
+    source.start(SourceInfo {
+        codec_params,
+        frame_duration: first_frame.duration(),
+    });
+
+    let mut analyzer = BufferedAnalyzer::new(
+        state.classifiers.get(&source.config)?,
+        &source.config,
+        LabelSmoother::new(
+            Duration::from_millis(state.args.smooth_behind),
+            Duration::from_millis(state.args.smooth_ahead),
+        )
+        .with_min_durations(state.args.min_durations()),
+        state.args.clone().into(),
+    );
+
+    analyzer.push(first_frame)?;
+
+    let mut dead_air = DeadAir::new(&source.url, state)?;
+
+    // Titles wait for the analyzer delay, keyed by the number of frames pushed before them.
+    let mut pending_titles = VecDeque::<(usize, String)>::new();
+    let mut pushed = 1;
+    let mut sent = 0;
+
+    for frame in decoder {
+        if state.terminator.is_terminated() {
+            break;
+        }
+
+        analyzer.push(frame?)?;
+        pushed += 1;
+
+        for () in boundaries.iter().flat_map(flume::Receiver::try_iter) {
+            log::info!("Source {} signals a boundary", source.url);
+            analyzer.hint_boundary()?;
+        }
+
+        for title in titles.iter().flat_map(flume::Receiver::try_iter) {
+            log::info!("Source {} title: {title}", source.url);
+            // A new song or an ad block is likely to start here.
+            analyzer.hint_boundary()?;
+            pending_titles.push_back((pushed, title));
+        }
+
+        for (kind, frame) in analyzer.pop()? {
+            while pending_titles.front().is_some_and(|(at, _)| *at <= sent) {
+                if let Some((_, title)) = pending_titles.pop_front() {
+                    source.set_title(title);
+                }
+            }
+            let signal = dead_air.signal(kind, &frame);
+            if let Some(pacer) = &mut pacer {
+                pacer.wait(frame.duration());
+            }
+            source.broadcast(&SourceEvent::Frame(signal, frame));
+            sent += 1;
+        }
+
+        if state.sources.remove_idle(source) {
+            log::info!("Source {} has no subscribers", source.url);
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Holds a source that can be read at once to the pace of playback.
+struct Pacer {
+    lead: Duration,
+    started: Option<Instant>,
+    played: Duration,
+}
+
+impl Pacer {
+    const fn new(lead: Duration) -> Self {
+        Self {
+            lead,
+            started: None,
+            played: Duration::ZERO,
+        }
+    }
+
+    /// Waits until the next frame is due, at most the lead ahead of realtime.
+    fn wait(&mut self, frame_duration: Duration) {
+        let started = *self.started.get_or_insert_with(Instant::now);
+        if let Some(ahead) = self.played.checked_sub(started.elapsed() + self.lead) {
+            std::thread::sleep(ahead);
+        }
+        self.played += frame_duration;
+    }
+}
+
+/// Turns classified frames into dead air while the source is silent and records it.
+struct DeadAir {
+    url: String,
+    detector: SilenceDetector,
+    // Recorded by a worker, so the database does not hold up the source.
+    events: flume::Sender<DeadAirEvent>,
+}
+
+enum DeadAirEvent {
+    Started(DateTime<Utc>),
+    Finished(DateTime<Utc>),
+}
+
+impl DeadAir {
+    fn new(url: &str, state: &AppState) -> anyhow::Result<Self> {
+        Ok(Self {
+            url: url.to_string(),
+            detector: state.args.silence_detector(),
+            events: start_dead_air_worker(url.to_string(), state.ads_provider.clone())?,
+        })
+    }
+
+    fn signal(&mut self, kind: ContentKind, frame: &AudioFrame) -> Signal {
+        match self.detector.push(frame) {
+            Some(SilenceEvent::Started(silent_for)) => {
+                log::error!("Source {} is silent for {silent_for:?}", self.url);
+                let started =
+                    Utc::now() - chrono::Duration::from_std(silent_for).unwrap_or_default();
+                _ = self.events.send(DeadAirEvent::Started(started));
+            }
+            Some(SilenceEvent::Ended(lasted)) => {
+                log::warn!("Source {} is back after {lasted:?} of dead air", self.url);
+                _ = self.events.send(DeadAirEvent::Finished(Utc::now()));
+            }
+            None => {}
+        }
+
+        if self.detector.is_silent() {
+            Signal::DeadAir
+        } else {
+            Signal::Content(kind)
+        }
+    }
+}
+
+// Records dead air of the source until its `DeadAir` is dropped.
+fn start_dead_air_worker(
+    url: String,
+    ads_provider: Arc<AdsProvider>,
+) -> anyhow::Result<flume::Sender<DeadAirEvent>> {
+    let (sender, events) = flume::unbounded();
+
+    // Source threads have no runtime of their own for the database.
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+
+    std::thread::spawn(move || {
+        runtime.block_on(async move {
+            let mut started = None;
+            while let Ok(event) = events.recv_async().await {
+                match event {
+                    DeadAirEvent::Started(at) => {
+                        match ads_provider.report_dead_air_started(&url, at).await {
+                            Ok(id) => started = Some(id),
+                            Err(err) => log::error!("Failed to record dead air of {url}: {err:#}"),
+                        }
+                    }
+                    DeadAirEvent::Finished(at) => {
+                        finish_dead_air(&ads_provider, &url, started.take(), at).await;
+                    }
+                }
+            }
+
+            // A source stopped during dead air ends it.
+            finish_dead_air(&ads_provider, &url, started, Utc::now()).await;
+        });
+    });
+
+    Ok(sender)
+}
+
+async fn finish_dead_air(
+    ads_provider: &AdsProvider,
+    url: &str,
+    started: Option<i64>,
+    at: DateTime<Utc>,
+) {
+    if let Some(id) = started {
+        if let Err(err) = ads_provider.report_dead_air_finished(id, at).await {
+            log::error!("Failed to record end of dead air of {url}: {err:#}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+
+    use codec::SampleFormat;
+
+    use super::*;
+
+    fn insert(sources: &Sources, url: &str) -> Arc<Source> {
+        let source = Arc::new(Source::new(url, AnalyzerConfig::default()));
+        sources
+            .0
+            .lock()
+            .unwrap()
+            .insert(source.key(), source.clone());
+        source
+    }
+
+    fn subscribe(source: &Source) -> Subscription {
+        let (sender, receiver) = flume::bounded(SUBSCRIBER_CAPACITY);
+        source.add(sender);
+        Subscription::new(receiver, source.connection.clone())
+    }
+
+    #[test]
+    fn test_fan_out() {
+        let sources = Sources::default();
+        let source = insert(&sources, "source");
+
+        let info = SourceInfo {
+            codec_params: CodecParams::new(44_100, SampleFormat::FltPlanar, 2),
+            frame_duration: Duration::from_millis(23),
+        };
+
+        let mut first = subscribe(&source);
+        source.start(info);
+        let mut second = subscribe(&source);
+
+        assert_eq!(first.info().unwrap().codec_params, info.codec_params);
+        assert_eq!(second.info().unwrap().codec_params, info.codec_params);
+        assert_eq!(source.subscribers(), 2);
+
+        drop(first);
+        source.broadcast(&SourceEvent::Failed("Test".to_string()));
+        assert_eq!(source.subscribers(), 1);
+        assert!(!sources.remove_idle(&source));
+
+        drop(second);
+        source.broadcast(&SourceEvent::Failed("Test".to_string()));
+        assert!(sources.remove_idle(&source));
+        assert!(sources.0.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_snapshots() {
+        let sources = Sources::default();
+        let source = insert(&sources, "source");
+        let _subscription = subscribe(&source);
+
+        let snapshots = sources.snapshots();
+        assert_eq!(snapshots.len(), 1);
+        assert_eq!(snapshots[0].url, "source");
+        assert_eq!(snapshots[0].subscribers, 1);
+        assert_eq!(snapshots[0].live_lag, None);
+
+        // A finished playlist has nothing to lag behind.
+        source.set_lag(Some(LiveLag::default()));
+        assert_eq!(sources.snapshots()[0].live_lag, None);
+
+        let input = Unstreamer::open(&serve_live_playlist()).unwrap();
+        source.set_lag(input.lag());
+        assert!(sources.snapshots()[0].live_lag.is_some());
+    }
+
+    /// Serves a live HLS playlist of silent segments, returns its URL.
+    fn serve_live_playlist() -> String {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/live.m3u8", listener.local_addr().unwrap());
+
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut request = [0; 1024];
+                let read = stream.read(&mut request).unwrap();
+                let (content_type, body) =
+                    if String::from_utf8_lossy(&request[..read]).contains(".m3u8") {
+                        (
+                            "application/vnd.apple.mpegurl",
+                            "#EXTM3U\n#EXT-X-TARGETDURATION:6\n#EXT-X-MEDIA-SEQUENCE:1\n\
+                             #EXTINF:6.0,\n1.aac\n#EXTINF:6.0,\n2.aac\n#EXTINF:6.0,\n3.aac\n",
+                        )
+                    } else {
+                        ("audio/aac", "")
+                    };
+                _ = write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Type: {content_type}\r\n\
+                     Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+            }
+        });
+        url
+    }
+
+    #[test]
+    fn test_slow_subscriber() {
+        let source = Source::new("source", AnalyzerConfig::default());
+        let mut subscription = subscribe(&source);
+
+        for _ in 0..=SUBSCRIBER_CAPACITY {
+            source.broadcast(&SourceEvent::Failed("Test".to_string()));
+        }
+        assert_eq!(source.subscribers(), 0);
+
+        assert!(subscription
+            .by_ref()
+            .take(SUBSCRIBER_CAPACITY)
+            .all(|e| e.is_err()));
+        assert!(subscription.next().is_none());
+    }
+
+    #[test]
+    fn test_paced_source() {
+        let source = Source::new("source", AnalyzerConfig::default());
+        let subscription = subscribe(&source);
+        let frame_duration = Duration::from_millis(2);
+        let frames = 2 * SUBSCRIBER_CAPACITY;
+
+        // The listener plays in realtime.
+        let listener = std::thread::spawn(move || {
+            for _ in subscription.receiver.iter().take(frames) {
+                std::thread::sleep(frame_duration);
+            }
+        });
+
+        // The source has all frames at once.
+        let started = Instant::now();
+        let mut pacer = Pacer::new(frame_duration * 4);
+        for _ in 0..frames {
+            pacer.wait(frame_duration);
+            source.broadcast(&SourceEvent::Title("Test".to_string()));
+        }
+
+        assert!(started.elapsed() >= frame_duration * (frames as u32 - 5));
+        assert_eq!(source.subscribers(), 1);
+        listener.join().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_dead_air_worker() {
+        let database = std::env::temp_dir().join(format!("{}.sqlite", uuid::Uuid::new_v4()));
+        let ads_provider = Arc::new(AdsProvider::init(&database).await.unwrap());
+
+        let events =
+            start_dead_air_worker("http://radio".to_string(), ads_provider.clone()).unwrap();
+        events.send(DeadAirEvent::Started(Utc::now())).unwrap();
+        // The source stops during dead air.
+        drop(events);
+
+        let mut records = vec![];
+        for _ in 0..100 {
+            records = ads_provider.dead_air().await.unwrap();
+            if records.iter().any(|record| record.finished.is_some()) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        std::fs::remove_file(&database).unwrap();
+
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].source, "http://radio");
+        assert!(records[0].finished.is_some());
+    }
+
+    #[test]
+    fn test_restart_after_close() {
+        let sources = Sources::default();
+        let old = insert(&sources, "source");
+        let new = insert(&sources, "source");
+
+        // The stale source must not remove its replacement.
+        sources.remove(&old);
+        assert!(Arc::ptr_eq(&sources.0.lock().unwrap()[&new.key()], &new));
+    }
+
+    #[test]
+    fn test_gap() {
+        let source = Source::new("source", AnalyzerConfig::default());
+        let mut subscription = subscribe(&source);
+        subscription.gap_check_interval = Duration::from_millis(1);
+
+        source.connection.set_reconnecting(true);
+        assert!(matches!(subscription.next(), Some(Ok(SourceItem::Gap))));
+
+        source.connection.set_reconnecting(false);
+        source.broadcast(&SourceEvent::Failed("Test".to_string()));
+        assert!(matches!(subscription.next(), Some(Err(_))));
+
+        source.close();
+        assert!(subscription.next().is_none());
+    }
+}
@@ -1,10 +1,17 @@
 use std::sync::Arc;
 
-use crate::{ads_management::AdsProvider, args::Args, terminate::Terminator};
+use crate::{
+    ads_management::AdsProvider, args::Args, classifiers::Classifiers, hls::HlsSessions,
+    levels::StreamLevels, sources::Sources, terminate::Terminator,
+};
 
 #[derive(Clone)]
 pub struct AppState {
     pub terminator: Terminator,
     pub ads_provider: Arc<AdsProvider>,
+    pub classifiers: Classifiers,
     pub args: Args,
+    pub hls_sessions: HlsSessions,
+    pub levels: StreamLevels,
+    pub sources: Sources,
 }
@@ -0,0 +1,265 @@
+mod manifest;
+
+use std::io::Read;
+use std::time::Duration;
+
+use flume::Sender;
+use url::Url;
+
+use crate::segments::{
+    self, fetch, fetch_playlist, Fetcher, LiveLag, Progress, SegmentSender, SegmentsConfig,
+};
+use crate::ConnectionStatus;
+use manifest::{extract_segments, is_live, SegmentInfo};
+
+pub static MIME_DASH: &str = "application/dash+xml";
+
+// How often a live manifest is checked, if it has no segments yet.
+const UPDATE_PERIOD: Duration = Duration::from_secs(1);
+
+pub fn is_dash(content_type: &str) -> bool {
+    content_type.eq_ignore_ascii_case(MIME_DASH)
+}
+
+pub struct DASHUnstreamer;
+
+impl DASHUnstreamer {
+    pub(crate) fn open(
+        source: Url,
+        status: ConnectionStatus,
+        boundaries: Sender<()>,
+        config: SegmentsConfig,
+        lag: LiveLag,
+    ) -> anyhow::Result<Box<dyn Read + Send>> {
+        // Fail early on a broken manifest, the fetcher only retries.
+        let mpd = fetch_manifest(&source, config.max_playlist_size)?;
+        extract_segments(&mpd, &source, chrono::Utc::now(), config.max_lag)?;
+        lag.set_live(is_live(&mpd));
+
+        let (sender, reader) = segments::channel(boundaries, config, lag);
+        segments::spawn(DASHFetcher::new(source, sender), status);
+
+        Ok(Box::new(reader))
+    }
+}
+
+struct DASHFetcher {
+    source: Url,
+    // Start of the last fetched segment.
+    last_fetched: Option<Duration>,
+    // Initialization segment the decoder has got last.
+    init: Option<Url>,
+    period_start: Option<Duration>,
+    sender: SegmentSender,
+}
+
+impl Fetcher for DASHFetcher {
+    fn fetch_new_segments(&mut self, _resume: bool) -> anyhow::Result<Progress> {
+        let mpd = fetch_manifest(&self.source, self.sender.config.max_playlist_size)?;
+        let segments = extract_segments(
+            &mpd,
+            &self.source,
+            chrono::Utc::now(),
+            self.sender.config.max_lag,
+        )?;
+
+        // Missed segments need no care, the window of a live manifest moves on by itself.
+        if let (Some(last_fetched), Some(last)) = (self.last_fetched, segments.last()) {
+            // The server may restart the presentation.
+            if last.start < last_fetched {
+                log::info!("DASH presentation restarted");
+                self.last_fetched = None;
+            }
+        }
+
+        let live = is_live(&mpd);
+        self.sender.set_live(live);
+        self.unfetched(&segments, live);
+        if live && self.sender.is_too_far_behind() {
+            self.skip_to_live_edge(&segments);
+        }
+        let mut unfetched = self.unfetched(&segments, live);
+
+        let new_segments = segments
+            .iter()
+            .filter(|segment| self.is_new(segment))
+            .collect::<Vec<_>>();
+
+        for segment in new_segments {
+            if self.sender.is_consumer_gone() {
+                break;
+            }
+
+            if self.sender.is_full() {
+                log::debug!("DASH consumer is behind, wait");
+                return Ok(Progress::Wait(segment.duration / 2));
+            }
+
+            if self
+                .period_start
+                .is_some_and(|period_start| period_start != segment.period_start)
+            {
+                log::info!("DASH period starts at {:?}", segment.period_start);
+                self.sender.boundary();
+            }
+
+            // Download the whole segment here, so a dropped connection is retried as well.
+            let mut data = vec![];
+            let init = segment
+                .init
+                .as_ref()
+                .filter(|init| self.init.as_ref() != Some(init));
+            if let Some(init) = init {
+                data = self.download(init)?;
+            }
+            data.extend(self.download(&segment.source)?);
+
+            if init.is_some() {
+                self.init = segment.init.clone();
+            }
+            self.period_start = Some(segment.period_start);
+            self.last_fetched = Some(segment.start);
+
+            if live {
+                unfetched = unfetched.saturating_sub(segment.duration);
+                self.sender.set_unfetched(unfetched);
+            }
+            self.sender.send(data, segment.duration);
+
+            log::debug!(
+                "Fetched {} at {:?}, {}ms behind live",
+                segment.source,
+                segment.start,
+                self.sender.lag().as_millis()
+            );
+        }
+
+        if !live {
+            return Ok(Progress::Ended);
+        }
+
+        // New segments appear every segment duration, the manifest may not change at all.
+        let wait = segments
+            .last()
+            .map_or(UPDATE_PERIOD, |segment| segment.duration / 2);
+        Ok(Progress::Wait(
+            mpd.minimumUpdatePeriod
+                .filter(|period| !period.is_zero())
+                .map_or(wait, |period| period.min(wait)),
+        ))
+    }
+
+    fn sender(&self) -> &SegmentSender {
+        &self.sender
+    }
+}
+
+impl DASHFetcher {
+    const fn new(source: Url, sender: SegmentSender) -> Self {
+        Self {
+            source,
+            last_fetched: None,
+            init: None,
+            period_start: None,
+            sender,
+        }
+    }
+
+    fn is_new(&self, segment: &SegmentInfo) -> bool {
+        self.last_fetched < Some(segment.start)
+    }
+
+    fn unfetched(&self, segments: &[SegmentInfo], live: bool) -> Duration {
+        let unfetched = if live {
+            segments
+                .iter()
+                .filter(|segment| self.is_new(segment))
+                .map(|segment| segment.duration)
+                .sum()
+        } else {
+            Duration::ZERO
+        };
+        self.sender.set_unfetched(unfetched);
+        unfetched
+    }
+
+    fn skip_to_live_edge(&mut self, segments: &[SegmentInfo]) {
+        // The content jumps, unless nothing has been fetched yet.
+        if self.last_fetched.is_some() {
+            self.sender.boundary();
+        }
+
+        let durations = segments
+            .iter()
+            .map(|segment| segment.duration)
+            .collect::<Vec<_>>();
+        let first = self.sender.skip_to_live_edge(&durations);
+        self.last_fetched = first
+            .checked_sub(1)
+            .and_then(|last| segments.get(last))
+            .map(|last| last.start);
+
+        // Initialization segment may have been dropped with the unread audio.
+        self.init = None;
+    }
+
+    fn download(&self, source: &Url) -> anyhow::Result<Vec<u8>> {
+        fetch(source, self.sender.config.max_segment_size)
+    }
+}
+
+fn fetch_manifest(source: &Url, max_size: u64) -> anyhow::Result<dash_mpd::MPD> {
+    let content = fetch_playlist(source, is_dash, max_size)?;
+    Ok(dash_mpd::parse(&content)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::segments::tests::serve;
+
+    #[test]
+    fn test_vod() {
+        let manifest = r#"<?xml version="1.0"?>
+<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" type="static">
+  <Period duration="PT4S">
+    <AdaptationSet mimeType="audio/mp4">
+      <SegmentTemplate timescale="1" duration="2" initialization="init-$RepresentationID$.mp4"
+        media="$RepresentationID$-$Number$.m4s"/>
+      <Representation id="a" bandwidth="96000"/>
+    </AdaptationSet>
+  </Period>
+  <Period duration="PT2S">
+    <AdaptationSet mimeType="audio/mp4">
+      <SegmentTemplate timescale="1" duration="2" initialization="init-$RepresentationID$.mp4"
+        media="$RepresentationID$-$Number$.m4s"/>
+      <Representation id="ad" bandwidth="96000"/>
+    </AdaptationSet>
+  </Period>
+</MPD>"#;
+
+        let url = serve(vec![
+            ("/manifest.mpd", MIME_DASH, manifest.as_bytes().to_vec()),
+            ("/init-a.mp4", "video/mp4", b"[a]".to_vec()),
+            ("/a-1.m4s", "video/mp4", b"one".to_vec()),
+            ("/a-2.m4s", "video/mp4", b"two".to_vec()),
+            ("/init-ad.mp4", "video/mp4", b"[ad]".to_vec()),
+            ("/ad-1.m4s", "video/mp4", b"ad".to_vec()),
+        ]);
+
+        let (boundaries_tx, boundaries) = flume::unbounded();
+        let mut reader = DASHUnstreamer::open(
+            url.join("manifest.mpd").unwrap(),
+            ConnectionStatus::default(),
+            boundaries_tx,
+            SegmentsConfig::default(),
+            LiveLag::default(),
+        )
+        .unwrap();
+
+        let mut data = vec![];
+        reader.read_to_end(&mut data).unwrap();
+        assert_eq!(data, b"[a]onetwo[ad]ad");
+        assert_eq!(boundaries.try_iter().count(), 1);
+    }
+}
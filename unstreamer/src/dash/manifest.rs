@@ -0,0 +1,605 @@
+use std::{fmt::Write, time::Duration};
+
+use anyhow::{anyhow, bail, ensure};
+use chrono::{DateTime, Utc};
+use dash_mpd::{is_audio_adaptation, AdaptationSet, BaseURL, Period, Representation, MPD};
+use url::Url;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SegmentInfo {
+    /// Presentation time of the segment, orders segments across manifest updates.
+    pub start: Duration,
+    pub duration: Duration,
+    pub source: Url,
+    /// Initialization segment, e.g. for fragmented MP4.
+    pub init: Option<Url>,
+    /// Periods are separate pieces of content, e.g. an ad break.
+    pub period_start: Duration,
+}
+
+#[must_use]
+pub fn is_live(mpd: &MPD) -> bool {
+    mpd.mpdtype.as_deref() == Some("dynamic")
+}
+
+/// Segments of the best audio representation of every period.
+///
+/// Segments of a live manifest without a timeline are generated from the clock,
+/// up to `window` back from the live edge at `now`.
+pub fn extract_segments(
+    mpd: &MPD,
+    mpd_url: &Url,
+    now: DateTime<Utc>,
+    window: Duration,
+) -> anyhow::Result<Vec<SegmentInfo>> {
+    let base = join_base(mpd_url, &mpd.base_url)?;
+
+    let mut segments = vec![];
+    let mut period_start = Duration::ZERO;
+    for (index, period) in mpd.periods.iter().enumerate() {
+        period_start = period.start.unwrap_or(period_start);
+        let period_duration = period.duration.or_else(|| {
+            let end = mpd
+                .periods
+                .get(index + 1)
+                .and_then(|next| next.start)
+                .or(mpd.mediaPresentationDuration)?;
+            end.checked_sub(period_start)
+        });
+
+        let context = Context {
+            mpd,
+            period,
+            period_start,
+            period_duration,
+            now,
+            window,
+        };
+        segments.extend(context.segments(&join_base(&base, &period.BaseURL)?)?);
+
+        if let Some(duration) = period_duration {
+            period_start += duration;
+        }
+    }
+
+    Ok(segments)
+}
+
+struct Context<'m> {
+    mpd: &'m MPD,
+    period: &'m Period,
+    period_start: Duration,
+    period_duration: Option<Duration>,
+    now: DateTime<Utc>,
+    window: Duration,
+}
+
+impl Context<'_> {
+    fn segments(&self, base: &Url) -> anyhow::Result<Vec<SegmentInfo>> {
+        let (adaptation, representation) = select_representation(self.period)?;
+        let base = join_base(base, &adaptation.BaseURL)?;
+        let base = join_base(&base, &representation.BaseURL)?;
+
+        // The closest element wins, parts of a template are not merged.
+        let template = representation
+            .SegmentTemplate
+            .as_ref()
+            .or(adaptation.SegmentTemplate.as_ref())
+            .or(self.period.SegmentTemplate.as_ref());
+        let list = representation
+            .SegmentList
+            .as_ref()
+            .or(adaptation.SegmentList.as_ref());
+
+        if let Some(template) = template {
+            self.template_segments(template, representation, &base)
+        } else if let Some(list) = list {
+            self.list_segments(list, &base)
+        } else {
+            // The whole period is a single file.
+            ensure!(
+                !is_live(self.mpd),
+                "Live manifest without segment template or list"
+            );
+            Ok(vec![self.segment(
+                Duration::ZERO,
+                self.period_duration.unwrap_or_default(),
+                base,
+                None,
+            )])
+        }
+    }
+
+    fn segment(
+        &self,
+        start: Duration,
+        duration: Duration,
+        source: Url,
+        init: Option<Url>,
+    ) -> SegmentInfo {
+        SegmentInfo {
+            start: self.period_start + start,
+            duration,
+            source,
+            init,
+            period_start: self.period_start,
+        }
+    }
+
+    fn template_segments(
+        &self,
+        template: &dash_mpd::SegmentTemplate,
+        representation: &Representation,
+        base: &Url,
+    ) -> anyhow::Result<Vec<SegmentInfo>> {
+        let timescale = template.timescale.unwrap_or(1).max(1);
+        let offset = template.presentationTimeOffset.unwrap_or(0);
+        let start_number = template.startNumber.unwrap_or(1);
+        let media = template
+            .media
+            .as_deref()
+            .ok_or_else(|| anyhow!("Segment template without media"))?;
+
+        let init = template
+            .initialization
+            .as_deref()
+            .map(|init| base.join(&expand(init, representation, 0, 0)))
+            .transpose()?;
+
+        // Index from the start number, start time and duration in the timescale units.
+        let times = if let Some(timeline) = &template.SegmentTimeline {
+            self.timeline(&timeline.segments, timescale, offset)
+        } else if let Some(duration) = template.duration {
+            self.numbered(duration, timescale, offset)
+        } else {
+            bail!("Segment template without timeline or duration");
+        };
+
+        times
+            .into_iter()
+            .map(|(index, time, duration)| {
+                let number = start_number + index;
+                let source = base.join(&expand(media, representation, number, time))?;
+                Ok(self.segment(
+                    ticks(time.saturating_sub(offset), timescale),
+                    ticks(duration, timescale),
+                    source,
+                    init.clone(),
+                ))
+            })
+            .collect()
+    }
+
+    fn timeline(
+        &self,
+        timeline: &[dash_mpd::S],
+        timescale: u64,
+        offset: u64,
+    ) -> Vec<(u64, u64, u64)> {
+        // Start time, duration and repeat count of every element.
+        let mut runs = vec![];
+        let mut time = offset;
+        for (index, s) in timeline.iter().enumerate() {
+            if let Some(t) = s.t {
+                time = u64::try_from(t).unwrap_or_default();
+            }
+            let Ok(duration) = u64::try_from(s.d) else {
+                continue;
+            };
+            if duration == 0 {
+                continue;
+            }
+
+            // Negative repeat count lasts until the next element, the period end or now.
+            let end = timeline
+                .get(index + 1)
+                .and_then(|next| next.t)
+                .and_then(|t| u64::try_from(t).ok())
+                .or_else(|| {
+                    let end = if is_live(self.mpd) {
+                        self.elapsed()?
+                    } else {
+                        self.period_duration?
+                    };
+                    Some(offset + to_ticks(end, timescale))
+                });
+            let count = match s.r {
+                Some(r) if r < 0 => end.map_or(1, |end| end.saturating_sub(time) / duration),
+                r => u64::try_from(r.unwrap_or(0)).unwrap_or_default() + 1,
+            };
+
+            runs.push((time, duration, count));
+            time = time.saturating_add(duration.saturating_mul(count));
+        }
+
+        // Clamped like `numbered`, repeat counts of a broken manifest must not produce an endless list.
+        let range = if is_live(self.mpd) {
+            time.saturating_sub(to_ticks(self.window, timescale))..time
+        } else {
+            let end = self.period_duration.map_or(time, |period| {
+                time.min(offset.saturating_add(to_ticks(period, timescale)))
+            });
+            0..end
+        };
+
+        let mut times = vec![];
+        let mut index = 0;
+        for (start, duration, count) in runs {
+            // Repeats ending after the range start and starting before its end.
+            let first = range.start.saturating_sub(start) / duration;
+            let last = range
+                .end
+                .saturating_sub(start)
+                .div_ceil(duration)
+                .min(count);
+            for repeat in first..last {
+                times.push((index + repeat, start + repeat * duration, duration));
+            }
+            index += count;
+        }
+        times
+    }
+
+    fn numbered(&self, duration: f64, timescale: u64, offset: u64) -> Vec<(u64, u64, u64)> {
+        // Clamped, a broken manifest must not produce an endless list.
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let duration = duration.round().max(1.0) as u64;
+        let segment = ticks(duration, timescale);
+
+        let range = if is_live(self.mpd) {
+            // A segment is available once it is complete.
+            let available = self
+                .elapsed()
+                .map_or(0, |elapsed| to_ticks(elapsed, timescale) / duration);
+            let window = to_ticks(self.window, timescale) / duration + 1;
+            available.saturating_sub(window)..available
+        } else {
+            let count = self.period_duration.map_or(0, |period| {
+                let period = period.as_secs_f64() / segment.as_secs_f64();
+                #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+                let count = period.ceil() as u64;
+                count
+            });
+            0..count
+        };
+
+        range
+            .map(|index| (index, offset + index * duration, duration))
+            .collect()
+    }
+
+    // Time since the period start for a live manifest.
+    fn elapsed(&self) -> Option<Duration> {
+        let start = self.mpd.availabilityStartTime?;
+        (self.now - start)
+            .to_std()
+            .ok()?
+            .checked_sub(self.period_start)
+    }
+
+    fn list_segments(
+        &self,
+        list: &dash_mpd::SegmentList,
+        base: &Url,
+    ) -> anyhow::Result<Vec<SegmentInfo>> {
+        let timescale = list.timescale.unwrap_or(1).max(1);
+        let duration = ticks(list.duration.unwrap_or(0), timescale);
+        let init = list
+            .Initialization
+            .as_ref()
+            .and_then(|init| init.sourceURL.as_deref())
+            .map(|init| base.join(init))
+            .transpose()?;
+
+        list.segment_urls
+            .iter()
+            .filter_map(|segment| segment.media.as_deref())
+            .zip(0u32..)
+            .map(|(media, index)| {
+                Ok(self.segment(duration * index, duration, base.join(media)?, init.clone()))
+            })
+            .collect()
+    }
+}
+
+/// Picks the audio representation to ingest.
+///
+/// The main audio adaptation set is preferred, then the one with the highest bandwidth.
+/// Protected content can not be decrypted, it is skipped.
+fn select_representation(period: &Period) -> anyhow::Result<(&AdaptationSet, &Representation)> {
+    let audio = period
+        .adaptations
+        .iter()
+        .filter(is_audio_adaptation)
+        .collect::<Vec<_>>();
+    ensure!(!audio.is_empty(), "Period does not contain audio");
+
+    audio
+        .into_iter()
+        .filter(|adaptation| adaptation.ContentProtection.is_empty())
+        .filter_map(|adaptation| {
+            let representation = adaptation
+                .representations
+                .iter()
+                .filter(|representation| representation.ContentProtection.is_empty())
+                .max_by_key(|representation| representation.bandwidth)?;
+            Some((adaptation, representation))
+        })
+        .max_by_key(|(adaptation, representation)| {
+            let main = adaptation
+                .Role
+                .iter()
+                .any(|role| role.value.as_deref() == Some("main"));
+            (main, representation.bandwidth)
+        })
+        .ok_or_else(|| anyhow!("DRM protected audio is not supported"))
+}
+
+fn join_base(base: &Url, urls: &[BaseURL]) -> anyhow::Result<Url> {
+    match urls.first() {
+        Some(url) => Ok(base.join(url.base.trim())?),
+        None => Ok(base.clone()),
+    }
+}
+
+/// Substitutes `$RepresentationID$`, `$Number$`, `$Time$` and `$Bandwidth$` identifiers,
+/// with an optional width like `$Number%05d$`.
+fn expand(template: &str, representation: &Representation, number: u64, time: u64) -> String {
+    // Identifiers are between odd and even `$`.
+    let mut parts = template.split('$');
+    let mut result = parts.next().unwrap_or_default().to_string();
+
+    while let Some(identifier) = parts.next() {
+        let (name, format) = identifier.split_once('%').unwrap_or((identifier, ""));
+        let width = format
+            .trim_end_matches('d')
+            .trim_start_matches('0')
+            .parse::<usize>()
+            .unwrap_or(0);
+
+        match name {
+            "" => result.push('$'),
+            "RepresentationID" => result.push_str(representation.id.as_deref().unwrap_or_default()),
+            "Number" => _ = write!(result, "{number:0width$}"),
+            "Time" => _ = write!(result, "{time:0width$}"),
+            "Bandwidth" => {
+                let bandwidth = representation.bandwidth.unwrap_or_default();
+                _ = write!(result, "{bandwidth:0width$}");
+            }
+            _ => {
+                log::warn!("Unknown template identifier ${identifier}$");
+                _ = write!(result, "${identifier}$");
+            }
+        }
+
+        result.push_str(parts.next().unwrap_or_default());
+    }
+
+    result
+}
+
+fn ticks(value: u64, timescale: u64) -> Duration {
+    let nanos = u128::from(value) * 1_000_000_000 / u128::from(timescale);
+    Duration::from_nanos(u64::try_from(nanos).unwrap_or(u64::MAX))
+}
+
+fn to_ticks(duration: Duration, timescale: u64) -> u64 {
+    let ticks = duration.as_nanos() * u128::from(timescale) / 1_000_000_000;
+    u64::try_from(ticks).unwrap_or(u64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(path: &str) -> Url {
+        Url::parse("http://localhost/live/")
+            .unwrap()
+            .join(path)
+            .unwrap()
+    }
+
+    fn extract(manifest: &str, now: &str) -> Vec<SegmentInfo> {
+        extract_segments(
+            &dash_mpd::parse(manifest).unwrap(),
+            &url("manifest.mpd"),
+            now.parse().unwrap(),
+            Duration::from_secs(10),
+        )
+        .unwrap()
+    }
+
+    fn sources(segments: &[SegmentInfo]) -> Vec<&str> {
+        segments
+            .iter()
+            .map(|segment| segment.source.path())
+            .collect()
+    }
+
+    #[test]
+    fn test_expand() {
+        let representation = Representation {
+            id: Some("audio=128000".to_string()),
+            bandwidth: Some(128_000),
+            ..Representation::default()
+        };
+
+        assert_eq!(
+            expand("$RepresentationID$/$Number%05d$.m4s", &representation, 7, 0),
+            "audio=128000/00007.m4s"
+        );
+        assert_eq!(
+            expand("t$Time$-$Bandwidth$$$.m4s", &representation, 0, 9000),
+            "t9000-128000$.m4s"
+        );
+    }
+
+    #[test]
+    fn test_timeline() {
+        let segments = extract(
+            r#"<?xml version="1.0"?>
+<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" type="static" mediaPresentationDuration="PT10S">
+  <Period>
+    <AdaptationSet contentType="video" mimeType="video/mp4">
+      <Representation id="video" bandwidth="2000000"/>
+    </AdaptationSet>
+    <AdaptationSet contentType="audio" mimeType="audio/mp4" lang="fr">
+      <Representation id="fr" bandwidth="256000"/>
+    </AdaptationSet>
+    <AdaptationSet contentType="audio" mimeType="audio/mp4">
+      <Role schemeIdUri="urn:mpeg:dash:role:2011" value="main"/>
+      <BaseURL>audio/</BaseURL>
+      <SegmentTemplate timescale="1000" initialization="$RepresentationID$/init.mp4"
+        media="$RepresentationID$/$Time$.m4s">
+        <SegmentTimeline>
+          <S t="0" d="4000" r="1"/>
+          <S d="2000"/>
+        </SegmentTimeline>
+      </SegmentTemplate>
+      <Representation id="low" bandwidth="64000"/>
+      <Representation id="high" bandwidth="128000"/>
+    </AdaptationSet>
+  </Period>
+</MPD>"#,
+            "2024-01-01T00:00:00Z",
+        );
+
+        assert_eq!(
+            sources(&segments),
+            vec![
+                "/live/audio/high/0.m4s",
+                "/live/audio/high/4000.m4s",
+                "/live/audio/high/8000.m4s"
+            ]
+        );
+        assert_eq!(segments[2].start, Duration::from_secs(8));
+        assert_eq!(segments[2].duration, Duration::from_secs(2));
+        assert_eq!(segments[0].init, Some(url("audio/high/init.mp4")));
+    }
+
+    #[test]
+    fn test_timeline_repeats() {
+        let manifest = |mpd_type: &str, repeat: &str| {
+            format!(
+                r#"<?xml version="1.0"?>
+<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" type="{mpd_type}" mediaPresentationDuration="PT10S"
+  availabilityStartTime="2024-01-01T00:00:00Z">
+  <Period start="PT0S">
+    <AdaptationSet mimeType="audio/mp4">
+      <SegmentTemplate timescale="1000" media="a-$Number$.m4s">
+        <SegmentTimeline>
+          <S t="0" d="2000" r="{repeat}"/>
+        </SegmentTimeline>
+      </SegmentTemplate>
+      <Representation id="a" bandwidth="96000"/>
+    </AdaptationSet>
+  </Period>
+</MPD>"#
+            )
+        };
+
+        // Up to the period end.
+        let segments = extract(&manifest("static", "1000000000"), "2024-01-01T00:00:00Z");
+        assert_eq!(sources(&segments).len(), 5);
+
+        // Up to the window back from the end, numbered from the first repeat.
+        let segments = extract(&manifest("dynamic", "1000000000"), "2024-01-01T00:00:00Z");
+        assert_eq!(
+            sources(&segments),
+            vec![
+                "/live/a-999999997.m4s",
+                "/live/a-999999998.m4s",
+                "/live/a-999999999.m4s",
+                "/live/a-1000000000.m4s",
+                "/live/a-1000000001.m4s"
+            ]
+        );
+
+        // An old start repeated until now.
+        let segments = extract(&manifest("dynamic", "-1"), "2025-01-01T00:00:00Z");
+        assert_eq!(segments.len(), 5);
+        assert_eq!(segments[4].start, Duration::from_secs(366 * 24 * 3600 - 2));
+    }
+
+    #[test]
+    fn test_live_number() {
+        let manifest = r#"<?xml version="1.0"?>
+<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" type="dynamic"
+  availabilityStartTime="2024-01-01T00:00:00Z">
+  <Period start="PT0S">
+    <AdaptationSet mimeType="audio/mp4">
+      <SegmentTemplate timescale="48000" duration="192000" startNumber="10"
+        media="a-$Number$.m4s"/>
+      <Representation id="a" bandwidth="96000"/>
+    </AdaptationSet>
+  </Period>
+</MPD>"#;
+
+        // 4s segments, 10s window back from the last complete segment.
+        let segments = extract(manifest, "2024-01-01T00:01:02Z");
+        assert_eq!(
+            sources(&segments),
+            vec!["/live/a-22.m4s", "/live/a-23.m4s", "/live/a-24.m4s"]
+        );
+        assert_eq!(segments[2].start, Duration::from_secs(56));
+
+        assert!(extract(manifest, "2023-12-31T00:00:00Z").is_empty());
+    }
+
+    #[test]
+    fn test_periods() {
+        let segments = extract(
+            r#"<?xml version="1.0"?>
+<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" type="static">
+  <BaseURL>http://cdn.example.com/show/</BaseURL>
+  <Period duration="PT6S">
+    <AdaptationSet mimeType="audio/mp4">
+      <Representation id="a" bandwidth="96000">
+        <SegmentList timescale="10" duration="30">
+          <Initialization sourceURL="init.mp4"/>
+          <SegmentURL media="1.m4s"/>
+          <SegmentURL media="2.m4s"/>
+        </SegmentList>
+      </Representation>
+    </AdaptationSet>
+  </Period>
+  <Period duration="PT15S">
+    <AdaptationSet mimeType="audio/mpeg">
+      <Representation id="ad" bandwidth="128000">
+        <BaseURL>ads/break.mp3</BaseURL>
+      </Representation>
+    </AdaptationSet>
+  </Period>
+</MPD>"#,
+            "2024-01-01T00:00:00Z",
+        );
+
+        assert_eq!(
+            sources(&segments),
+            vec!["/show/1.m4s", "/show/2.m4s", "/show/ads/break.mp3"]
+        );
+        assert_eq!(segments[1].start, Duration::from_secs(3));
+        assert_eq!(segments[2].start, Duration::from_secs(6));
+        assert_eq!(segments[2].period_start, Duration::from_secs(6));
+        assert_eq!(segments[2].duration, Duration::from_secs(15));
+        assert_eq!(segments[2].init, None);
+    }
+
+    #[test]
+    fn test_protected() {
+        let mpd = dash_mpd::parse(
+            r#"<?xml version="1.0"?>
+<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" type="static">
+  <Period>
+    <AdaptationSet mimeType="audio/mp4">
+      <ContentProtection schemeIdUri="urn:mpeg:dash:mp4protection:2011" value="cenc"/>
+      <Representation id="a" bandwidth="96000"/>
+    </AdaptationSet>
+  </Period>
+</MPD>"#,
+        )
+        .unwrap();
+
+        assert!(extract_segments(&mpd, &url("manifest.mpd"), Utc::now(), Duration::ZERO).is_err());
+    }
+}
@@ -1,230 +1,412 @@
-use std::borrow::Cow;
-use std::collections::VecDeque;
+mod playlist;
+
+use std::collections::HashMap;
 use std::io::Read;
 use std::str::FromStr;
 use std::time::Duration;
 
-use anyhow::{anyhow, ensure};
-use flume::{Receiver, TryRecvError};
-use hls_m3u8::tags::VariantStream;
-use hls_m3u8::{MasterPlaylist, MediaPlaylist, MediaSegment};
+use aes::cipher::{block_padding::Pkcs7, BlockDecryptMut, KeyIvInit};
+use anyhow::anyhow;
+use flume::Sender;
+use hls_m3u8::{MasterPlaylist, MediaPlaylist};
 use url::Url;
 
+use crate::segments::{
+    self, fetch, fetch_playlist, Fetcher, LiveLag, Progress, SegmentSender, SegmentsConfig,
+};
+use crate::ConnectionStatus;
+use playlist::{extract_segments, select_media_playlist, SegmentInfo, SegmentKey};
+
 pub static MIME_HLS: &str = "application/vnd.apple.mpegurl";
+// Legacy type, still used by some servers.
+static MIME_HLS_LEGACY: &str = "application/x-mpegurl";
+
+type Aes128CbcDec = cbc::Decryptor<aes::Aes128>;
 
-pub struct HLSUnstreamer {
-    data_rx: Receiver<Box<(dyn Read + Send + Sync)>>,
-    error_rx: Receiver<anyhow::Error>,
-    readers: VecDeque<Box<(dyn Read + Send + Sync)>>,
+pub fn is_hls(content_type: &str) -> bool {
+    content_type.eq_ignore_ascii_case(MIME_HLS)
+        || content_type.eq_ignore_ascii_case(MIME_HLS_LEGACY)
 }
 
-impl HLSUnstreamer {
-    pub(crate) fn open(source: Url) -> anyhow::Result<Box<dyn Read + Send>> {
-        let resp = ureq::get(source.as_ref()).call()?;
+// Some servers send HLS playlists with a plain M3U type.
+fn is_playlist(content_type: &str) -> bool {
+    is_hls(content_type) || crate::playlist_file::is_m3u(content_type)
+}
 
-        ensure!(
-            resp.content_type() == MIME_HLS,
-            "Invalid content type: {}",
-            resp.content_type()
-        );
+pub struct HLSUnstreamer;
 
-        // TODO check for content size to avoid overflows.
+impl HLSUnstreamer {
+    pub(crate) fn open(
+        source: Url,
+        status: ConnectionStatus,
+        boundaries: Sender<()>,
+        config: SegmentsConfig,
+        lag: LiveLag,
+    ) -> anyhow::Result<Box<dyn Read + Send>> {
         // The content can be either master playlist or media playlist.
+        let content = fetch_playlist(&source, is_playlist, config.max_playlist_size)?;
 
-        let content = resp.into_string()?;
+        let (source, media) = match MasterPlaylist::try_from(content.as_ref()) {
+            Ok(master) => {
+                let source = select_media_playlist(&master, &source)?;
+                let media = fetch_media_playlist(&source, config.max_playlist_size)?;
+                (source, media)
+            }
+            Err(_) => (
+                source,
+                MediaPlaylist::from_str(content.as_ref())?.into_owned(),
+            ),
+        };
+        lag.set_live(!media.has_end_list);
 
-        let source = MasterPlaylist::try_from(content.as_ref())
-            .map_err(Into::into)
-            .and_then(get_best_media_playlist_url)
-            .or_else(|_| anyhow::Ok(source))?;
+        let (sender, reader) = segments::channel(boundaries, config, lag);
+        segments::spawn(HLSFetcher::new(source, sender), status);
 
-        let (data_tx, data_rx) = flume::unbounded();
-        let (error_tx, error_rx) = flume::bounded::<anyhow::Error>(1);
+        Ok(Box::new(reader))
+    }
+}
 
-        let mut last_fetched = 0;
+struct HLSFetcher {
+    source: Url,
+    last_fetched: Option<usize>,
+    keys: HashMap<Url, [u8; 16]>,
+    // Initialization section the decoder has got last.
+    map: Option<Url>,
+    sender: SegmentSender,
+}
+
+impl Fetcher for HLSFetcher {
+    fn fetch_new_segments(&mut self, resume: bool) -> anyhow::Result<Progress> {
+        let playlist = fetch_media_playlist(&self.source, self.sender.config.max_playlist_size)?;
+        let segments = extract_segments(&playlist, &self.source)?;
+
+        if let (Some(last_fetched), Some(first), Some(last)) =
+            (self.last_fetched, segments.first(), segments.last())
+        {
+            let missed = resume && last_fetched + 1 < first.sequence_number;
+            // The server may restart numbering as well.
+            if missed || last.sequence_number < last_fetched {
+                log::info!("Resume at the live edge #{}", last.sequence_number);
+                self.last_fetched = last.sequence_number.checked_sub(1);
+            }
+        }
 
-        std::thread::spawn(move || loop {
-            if data_tx.is_disconnected() {
-                log::error!("Data is disconected, exit");
+        // A finished playlist has no live edge to keep up with.
+        let live = !playlist.has_end_list;
+        self.sender.set_live(live);
+        self.unfetched(&segments, live);
+        if live && self.sender.is_too_far_behind() {
+            self.skip_to_live_edge(&segments);
+        }
+        let mut unfetched = self.unfetched(&segments, live);
+
+        let new_segments = segments
+            .into_iter()
+            .filter(|segment| self.is_new(segment))
+            .collect::<Vec<_>>();
+
+        for segment in new_segments {
+            if self.sender.is_consumer_gone() {
                 break;
             }
 
-            let playlist = match fetch_media_playlist(&source) {
-                Ok(playlist) => playlist,
-                Err(error) => {
-                    let _ = error_tx.send(error);
-                    break;
-                }
-            };
-
-            match extract_segments(&playlist) {
-                Ok(segments) => {
-                    for segment in segments {
-                        if segment.sequence_number <= last_fetched {
-                            continue;
-                        }
-
-                        last_fetched = segment.sequence_number;
-
-                        if data_tx.is_disconnected() {
-                            log::error!("Data is disconected, exit");
-                            break;
-                        }
-
-                        if let Err(error) = ureq::get(segment.source.as_ref()).call().map(|resp| {
-                            log::debug!(
-                                "Fetched #{}: {}",
-                                segment.sequence_number,
-                                segment.title.unwrap_or_default()
-                            );
-                            data_tx.send(resp.into_reader())
-                        }) {
-                            let _ = error_tx.send(error.into());
-                            break;
-                        }
-                    }
-                }
-                Err(error) => {
-                    let _ = error_tx.send(error);
-                    break;
-                }
+            if self.sender.is_full() {
+                log::debug!("HLS consumer is behind, wait");
+                return Ok(Progress::Wait(playlist.target_duration / 2));
             }
 
-            std::thread::sleep(playlist.duration() / 2);
-        });
+            if segment.discontinuity && self.last_fetched.is_some() {
+                log::info!("Discontinuity at #{}", segment.sequence_number);
+                self.sender.boundary();
+                // New encoding may come with a new initialization section, send it again anyway.
+                self.map = None;
+            }
 
-        Ok(Box::new(Self {
-            data_rx,
-            error_rx,
-            readers: VecDeque::new(),
-        }))
+            // Download the whole segment here, so a dropped connection is retried as well.
+            let mut data = vec![];
+            let map = segment.map.filter(|map| self.map.as_ref() != Some(map));
+            if let Some(map) = &map {
+                // Initialization section is encrypted with the same key.
+                data = self.download(map, segment.key.as_ref())?;
+            }
+            data.extend(self.download(&segment.source, segment.key.as_ref())?);
+
+            if map.is_some() {
+                self.map = map;
+            }
+            self.last_fetched = Some(segment.sequence_number);
+
+            if live {
+                unfetched = unfetched.saturating_sub(segment.duration);
+                self.sender.set_unfetched(unfetched);
+            }
+            self.sender.send(data, segment.duration);
+
+            log::debug!(
+                "Fetched #{}: {}, {}ms behind live",
+                segment.sequence_number,
+                segment.title.unwrap_or_default(),
+                self.sender.lag().as_millis()
+            );
+        }
+
+        if playlist.has_end_list {
+            Ok(Progress::Ended)
+        } else {
+            Ok(Progress::Wait(playlist.duration() / 2))
+        }
     }
-}
 
-#[derive(Debug, Clone)]
-#[allow(dead_code)]
-struct SegmentInfo<'t> {
-    sequence_number: usize,
-    duration: Duration,
-    source: Url,
-    title: Option<Cow<'t, str>>,
+    fn sender(&self) -> &SegmentSender {
+        &self.sender
+    }
 }
 
-impl<'s> TryFrom<&MediaSegment<'s>> for SegmentInfo<'s> {
-    type Error = anyhow::Error;
+impl HLSFetcher {
+    fn new(source: Url, sender: SegmentSender) -> Self {
+        Self {
+            source,
+            last_fetched: None,
+            keys: HashMap::new(),
+            map: None,
+            sender,
+        }
+    }
 
-    fn try_from(segment: &MediaSegment<'s>) -> Result<Self, Self::Error> {
-        Ok(Self {
-            sequence_number: segment.number(),
-            duration: segment.duration.duration(),
-            source: Url::from_str(segment.uri().as_ref())?,
-            title: segment.duration.title().clone(),
-        })
+    fn is_new(&self, segment: &SegmentInfo) -> bool {
+        self.last_fetched < Some(segment.sequence_number)
     }
-}
 
-fn extract_segments<'p>(playlist: &MediaPlaylist<'p>) -> anyhow::Result<Vec<SegmentInfo<'p>>> {
-    playlist
-        .segments
-        .iter()
-        .map(|(_, segment)| segment.try_into())
-        .collect()
-}
+    fn unfetched(&self, segments: &[SegmentInfo], live: bool) -> Duration {
+        let unfetched = if live {
+            segments
+                .iter()
+                .filter(|segment| self.is_new(segment))
+                .map(|segment| segment.duration)
+                .sum()
+        } else {
+            Duration::ZERO
+        };
+        self.sender.set_unfetched(unfetched);
+        unfetched
+    }
+
+    fn skip_to_live_edge(&mut self, segments: &[SegmentInfo]) {
+        // The content jumps, unless nothing has been fetched yet.
+        if self.last_fetched.is_some() {
+            self.sender.boundary();
+        }
 
-#[allow(clippy::needless_pass_by_value)]
-fn get_best_media_playlist_url(master: MasterPlaylist) -> anyhow::Result<Url> {
-    master
-        .variant_streams
-        .iter()
-        .filter_map(|vs| match vs {
-            VariantStream::ExtXIFrame { .. } => None,
-            VariantStream::ExtXStreamInf {
-                uri, stream_data, ..
-            } => Some((uri, stream_data.bandwidth())),
-        })
-        .max_by_key(|(_, bandwidth)| *bandwidth)
-        .map(|(uri, _)| Url::try_from(uri.as_ref()))
-        .transpose()?
-        .map(Url::from)
-        .ok_or_else(|| anyhow!("Master playlist does not contain any media streams:\n{master:#?}"))
+        let durations = segments
+            .iter()
+            .map(|segment| segment.duration)
+            .collect::<Vec<_>>();
+        let first = segments.get(self.sender.skip_to_live_edge(&durations));
+        self.last_fetched = first.and_then(|first| first.sequence_number.checked_sub(1));
+
+        // Initialization section may have been dropped with the unread audio.
+        self.map = None;
+    }
+
+    fn download(&mut self, source: &Url, key: Option<&SegmentKey>) -> anyhow::Result<Vec<u8>> {
+        let mut data = fetch(source, self.sender.config.max_segment_size)?;
+
+        let Some(key) = key else {
+            return Ok(data);
+        };
+
+        let iv = key.iv;
+        let key = self.key(&key.uri)?;
+        let len = Aes128CbcDec::new(&key.into(), &iv.into())
+            .decrypt_padded_mut::<Pkcs7>(&mut data)
+            .map_err(|_| anyhow!("Failed to decrypt {source}"))?
+            .len();
+        data.truncate(len);
+
+        Ok(data)
+    }
+
+    // Keys are cached, they are usually shared by many segments.
+    fn key(&mut self, uri: &Url) -> anyhow::Result<[u8; 16]> {
+        if let Some(key) = self.keys.get(uri) {
+            return Ok(*key);
+        }
+
+        let key: [u8; 16] = fetch(uri, 16)?
+            .try_into()
+            .map_err(|_| anyhow!("Invalid key {uri}"))?;
+
+        self.keys.insert(uri.clone(), key);
+        Ok(key)
+    }
 }
 
-fn fetch_media_playlist(source: &Url) -> anyhow::Result<MediaPlaylist> {
-    let resp = ureq::get(source.as_ref()).call()?;
-    ensure!(
-        resp.content_type() == MIME_HLS,
-        "Invalid content type: {}",
-        resp.content_type()
-    );
-    let content = resp.into_string()?;
+fn fetch_media_playlist(source: &Url, max_size: u64) -> anyhow::Result<MediaPlaylist<'static>> {
+    let content = fetch_playlist(source, is_playlist, max_size)?;
     let playlist = MediaPlaylist::from_str(content.as_ref())?;
     Ok(playlist.into_owned())
 }
 
-impl Read for HLSUnstreamer {
-    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        let mut total_read = 0usize;
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
 
-        while !self.readers.is_empty() {
-            if total_read == buf.len() {
-                break;
-            }
+    use aes::cipher::BlockEncryptMut;
+    use flume::Receiver;
 
-            let reader = self
-                .readers
-                .front_mut()
-                .expect("Readers queue is not empty");
-            let read = reader.read(&mut buf[total_read..])?;
-            total_read += read;
+    use super::*;
+    use crate::segments::tests::serve;
 
-            if read == 0 {
-                self.readers.pop_front();
-            } else {
-                break;
-            }
-        }
+    type Aes128CbcEnc = cbc::Encryptor<aes::Aes128>;
 
-        match self.error_rx.try_recv() {
-            Ok(error) => Err(std::io::Error::new(std::io::ErrorKind::Other, error)),
-            Err(TryRecvError::Empty) => Ok(()),
-            Err(TryRecvError::Disconnected) => Err(std::io::Error::new(
-                std::io::ErrorKind::NotConnected,
-                TryRecvError::Disconnected,
-            )),
-        }?;
-
-        match self.data_rx.try_recv() {
-            Ok(reader) => {
-                self.readers.push_back(reader);
-                Ok(())
-            }
-            Err(TryRecvError::Empty) => Ok(()),
-            Err(TryRecvError::Disconnected) => Err(std::io::Error::new(
-                std::io::ErrorKind::NotConnected,
-                TryRecvError::Disconnected,
-            )),
-        }?;
-
-        while !self.readers.is_empty() {
-            if total_read == buf.len() {
-                break;
-            }
+    fn playlist(content: &str) -> (&'static str, Vec<u8>) {
+        (MIME_HLS, content.as_bytes().to_vec())
+    }
 
-            let reader = self
-                .readers
-                .front_mut()
-                .expect("Readers queue is not empty");
-            let read = reader.read(&mut buf[total_read..])?;
+    fn open(url: Url, config: SegmentsConfig) -> (Box<dyn Read + Send>, Receiver<()>, LiveLag) {
+        let (boundaries_tx, boundaries) = flume::unbounded();
+        let lag = LiveLag::default();
+        let reader = HLSUnstreamer::open(
+            url,
+            ConnectionStatus::default(),
+            boundaries_tx,
+            config,
+            lag.clone(),
+        )
+        .unwrap();
+        (reader, boundaries, lag)
+    }
 
-            total_read += read;
+    fn read_all(url: Url) -> (Vec<u8>, usize) {
+        let (mut reader, boundaries, _) = open(url, SegmentsConfig::default());
 
-            if read == 0 {
-                self.readers.pop_front();
-            } else {
-                break;
-            }
-        }
+        let mut data = vec![];
+        reader.read_to_end(&mut data).unwrap();
+        (data, boundaries.try_iter().count())
+    }
+
+    #[test]
+    fn test_vod() {
+        let (content_type, master) = playlist(
+            "#EXTM3U
+#EXT-X-STREAM-INF:BANDWIDTH=2000000,CODECS=\"avc1.4d401f,mp4a.40.2\",RESOLUTION=1280x720
+video/index.m3u8
+#EXT-X-STREAM-INF:BANDWIDTH=128000,CODECS=\"mp4a.40.2\"
+audio/index.m3u8
+",
+        );
+        let (_, media) = playlist(
+            "#EXTM3U
+#EXT-X-TARGETDURATION:6
+#EXTINF:6.0,
+first.aac
+#EXTINF:6.0,
+/audio/second.aac
+#EXT-X-ENDLIST
+",
+        );
+
+        let url = serve(vec![
+            ("/master.m3u8", content_type, master),
+            ("/audio/index.m3u8", content_type, media),
+            ("/audio/first.aac", "audio/aac", b"first ".to_vec()),
+            ("/audio/second.aac", "audio/aac", b"second".to_vec()),
+        ]);
+
+        // Ends instead of polling the playlist forever.
+        let (mut reader, boundaries, lag) =
+            open(url.join("master.m3u8").unwrap(), SegmentsConfig::default());
+        assert!(!lag.is_live());
+
+        let mut data = vec![];
+        reader.read_to_end(&mut data).unwrap();
+        assert_eq!(data, b"first second");
+        assert_eq!(boundaries.try_iter().count(), 0);
+    }
+
+    #[test]
+    fn test_encrypted_fmp4() {
+        let key = [7u8; 16];
+        let iv = 1u128.to_be_bytes();
+
+        let encrypt = |data: &[u8], iv: [u8; 16]| {
+            let mut buf = data.to_vec();
+            buf.resize(data.len() + 16, 0);
+            let len = Aes128CbcEnc::new(&key.into(), &iv.into())
+                .encrypt_padded_mut::<Pkcs7>(&mut buf, data.len())
+                .unwrap()
+                .len();
+            buf.truncate(len);
+            buf
+        };
+
+        let (content_type, media) = playlist(
+            "#EXTM3U
+#EXT-X-TARGETDURATION:6
+#EXT-X-MEDIA-SEQUENCE:1
+#EXT-X-KEY:METHOD=AES-128,URI=\"key\"
+#EXT-X-MAP:URI=\"init.mp4\"
+#EXTINF:6.0,
+1.m4s
+#EXTINF:6.0,
+2.m4s
+#EXT-X-DISCONTINUITY
+#EXT-X-KEY:METHOD=NONE
+#EXT-X-MAP:URI=\"init2.mp4\"
+#EXTINF:6.0,
+3.m4s
+#EXT-X-ENDLIST
+",
+        );
+
+        let url = serve(vec![
+            ("/index.m3u8", content_type, media),
+            ("/key", "application/octet-stream", key.to_vec()),
+            ("/init.mp4", "video/mp4", encrypt(b"[init]", iv)),
+            ("/1.m4s", "video/mp4", encrypt(b"one", iv)),
+            ("/2.m4s", "video/mp4", encrypt(b"two", 2u128.to_be_bytes())),
+            ("/init2.mp4", "video/mp4", b"[init2]".to_vec()),
+            ("/3.m4s", "video/mp4", b"three".to_vec()),
+        ]);
+
+        let (data, boundaries) = read_all(url.join("index.m3u8").unwrap());
+        assert_eq!(data, b"[init]onetwo[init2]three");
+        assert_eq!(boundaries, 1);
+    }
+
+    #[test]
+    fn test_live_edge() {
+        let (content_type, media) = playlist(
+            "#EXTM3U
+#EXT-X-TARGETDURATION:6
+#EXTINF:6.0,
+1.aac
+#EXTINF:6.0,
+2.aac
+#EXTINF:6.0,
+3.aac
+#EXTINF:6.0,
+4.aac
+",
+        );
 
-        Ok(total_read)
+        let url = serve(vec![
+            ("/index.m3u8", content_type, media),
+            ("/3.aac", "audio/aac", b"three".to_vec()),
+            ("/4.aac", "audio/aac", b"four".to_vec()),
+        ]);
+
+        let config = SegmentsConfig {
+            max_lag: Duration::from_secs(12),
+            ..SegmentsConfig::default()
+        };
+        let (mut reader, boundaries, lag) = open(url.join("index.m3u8").unwrap(), config);
+
+        // 24s of the playlist is too far behind, starts 6s before the live edge.
+        let mut data = [0u8; 4];
+        reader.read_exact(&mut data).unwrap();
+        assert_eq!(&data, b"four");
+        assert_eq!(lag.get(), Duration::ZERO);
+        assert_eq!(boundaries.try_iter().count(), 0);
     }
 }
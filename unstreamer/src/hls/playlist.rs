@@ -0,0 +1,303 @@
+use std::{borrow::Cow, time::Duration};
+
+use anyhow::{anyhow, bail};
+use hls_m3u8::{
+    tags::VariantStream,
+    types::{EncryptionMethod, KeyFormat, MediaType},
+    MasterPlaylist, MediaPlaylist, MediaSegment,
+};
+use url::Url;
+
+// Codecs which carry audio, a variant with only these is audio only.
+const AUDIO_CODECS: [&str; 6] = ["mp4a", "ac-3", "ec-3", "opus", "mp3", "flac"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SegmentKey {
+    pub uri: Url,
+    pub iv: [u8; 16],
+}
+
+#[derive(Debug, Clone)]
+#[allow(dead_code)]
+pub struct SegmentInfo<'t> {
+    pub sequence_number: usize,
+    pub duration: Duration,
+    pub source: Url,
+    pub title: Option<Cow<'t, str>>,
+    /// AES-128 key, if the segment is encrypted.
+    pub key: Option<SegmentKey>,
+    /// Media initialization section, e.g. for fragmented MP4.
+    pub map: Option<Url>,
+    pub discontinuity: bool,
+}
+
+impl<'s> SegmentInfo<'s> {
+    fn new(
+        segment: &MediaSegment<'s>,
+        playlist_url: &Url,
+        map: Option<Url>,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            sequence_number: segment.number(),
+            duration: segment.duration.duration(),
+            source: playlist_url.join(segment.uri())?,
+            title: segment.duration.title().clone(),
+            key: segment_key(segment, playlist_url)?,
+            map,
+            discontinuity: segment.has_discontinuity,
+        })
+    }
+}
+
+/// Segments of the playlist, URIs are resolved against the playlist URL.
+pub fn extract_segments<'p>(
+    playlist: &MediaPlaylist<'p>,
+    playlist_url: &Url,
+) -> anyhow::Result<Vec<SegmentInfo<'p>>> {
+    // `EXT-X-MAP` applies to all following segments, until the next one.
+    let mut map = None;
+
+    playlist
+        .segments
+        .iter()
+        .map(|(_, segment)| {
+            if let Some(segment_map) = &segment.map {
+                map = Some(playlist_url.join(segment_map.uri())?);
+            }
+            SegmentInfo::new(segment, playlist_url, map.clone())
+        })
+        .collect()
+}
+
+fn segment_key(segment: &MediaSegment, playlist_url: &Url) -> anyhow::Result<Option<SegmentKey>> {
+    // Other key formats are DRM systems, the identity one is a plain key.
+    let Some(key) = segment
+        .keys
+        .iter()
+        .filter_map(|key| key.as_ref())
+        .find(|key| key.format.is_none() || key.format == Some(KeyFormat::Identity))
+    else {
+        return Ok(None);
+    };
+
+    match key.method {
+        EncryptionMethod::Aes128 => Ok(Some(SegmentKey {
+            uri: playlist_url.join(key.uri())?,
+            // The playlist parser puts the sequence number in, if the IV is missing.
+            iv: key
+                .iv
+                .to_slice()
+                .unwrap_or_else(|| (segment.number() as u128).to_be_bytes()),
+        })),
+        method => bail!("{method} encryption is not supported"),
+    }
+}
+
+/// Picks the media playlist to ingest, only audio is needed.
+///
+/// Audio only variant is preferred, then an alternative audio rendition,
+/// then the variant with the highest bandwidth.
+pub fn select_media_playlist(master: &MasterPlaylist, master_url: &Url) -> anyhow::Result<Url> {
+    let variants = master
+        .variant_streams
+        .iter()
+        .filter(|vs| matches!(vs, VariantStream::ExtXStreamInf { .. }))
+        .collect::<Vec<_>>();
+
+    let audio_only = variants
+        .iter()
+        .filter(|vs| is_audio_only(vs))
+        .max_by_key(|vs| bandwidth(vs));
+
+    let rendition = || {
+        master
+            .media
+            .iter()
+            .filter(|media| media.media_type == MediaType::Audio && media.uri().is_some())
+            .filter(|media| variants.iter().any(|vs| vs.is_associated(media)))
+            .max_by_key(|media| (media.is_default, media.is_autoselect))
+            .and_then(|media| media.uri().map(AsRef::as_ref))
+    };
+
+    let best = || {
+        variants
+            .iter()
+            .max_by_key(|vs| bandwidth(vs))
+            .map(|vs| uri(vs))
+    };
+
+    let uri = audio_only
+        .map(|vs| uri(vs))
+        .or_else(rendition)
+        .or_else(best)
+        .ok_or_else(|| {
+            anyhow!("Master playlist does not contain any media streams:\n{master:#?}")
+        })?;
+
+    log::info!("Selected media playlist {uri}");
+
+    Ok(master_url.join(uri)?)
+}
+
+fn uri<'v>(variant: &'v VariantStream) -> &'v str {
+    match variant {
+        VariantStream::ExtXStreamInf { uri, .. } | VariantStream::ExtXIFrame { uri, .. } => uri,
+    }
+}
+
+fn bandwidth(variant: &VariantStream) -> u64 {
+    match variant {
+        VariantStream::ExtXStreamInf { stream_data, .. }
+        | VariantStream::ExtXIFrame { stream_data, .. } => stream_data.bandwidth(),
+    }
+}
+
+fn is_audio_only(variant: &VariantStream) -> bool {
+    match variant {
+        VariantStream::ExtXStreamInf { stream_data, .. } => {
+            stream_data.resolution().is_none()
+                && stream_data.codecs().is_some_and(|codecs| {
+                    !codecs.is_empty()
+                        && codecs
+                            .iter()
+                            .all(|codec| AUDIO_CODECS.iter().any(|audio| codec.starts_with(audio)))
+                })
+        }
+        VariantStream::ExtXIFrame { .. } => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn url(path: &str) -> Url {
+        Url::parse("http://localhost/live/")
+            .unwrap()
+            .join(path)
+            .unwrap()
+    }
+
+    fn select(master: &str) -> Url {
+        select_media_playlist(
+            &MasterPlaylist::try_from(master).unwrap(),
+            &url("master.m3u8"),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_segments() {
+        let playlist = MediaPlaylist::from_str(
+            "#EXTM3U
+#EXT-X-TARGETDURATION:6
+#EXT-X-MEDIA-SEQUENCE:10
+#EXT-X-MAP:URI=\"init.mp4\"
+#EXTINF:6.0,First
+a.m4s
+#EXT-X-KEY:METHOD=AES-128,URI=\"/keys/1\"
+#EXTINF:6.0,
+https://cdn.example.com/b.m4s
+#EXT-X-DISCONTINUITY
+#EXT-X-KEY:METHOD=AES-128,URI=\"key2\",IV=0x000102030405060708090a0b0c0d0e0f
+#EXT-X-MAP:URI=\"../init2.mp4\"
+#EXTINF:6.0,
+c.m4s
+",
+        )
+        .unwrap();
+
+        let segments = extract_segments(&playlist, &url("index.m3u8")).unwrap();
+
+        assert_eq!(
+            segments
+                .iter()
+                .map(|s| s.sequence_number)
+                .collect::<Vec<_>>(),
+            vec![10, 11, 12]
+        );
+        assert_eq!(segments[0].source, url("a.m4s"));
+        assert_eq!(segments[0].title.as_deref(), Some("First"));
+        assert_eq!(segments[0].key, None);
+        assert_eq!(segments[0].map, Some(url("init.mp4")));
+        assert!(!segments[0].discontinuity);
+
+        assert_eq!(segments[1].source.as_str(), "https://cdn.example.com/b.m4s");
+        assert_eq!(
+            segments[1].key,
+            Some(SegmentKey {
+                uri: Url::parse("http://localhost/keys/1").unwrap(),
+                iv: 11u128.to_be_bytes(),
+            })
+        );
+        assert_eq!(segments[1].map, Some(url("init.mp4")));
+
+        assert!(segments[2].discontinuity);
+        assert_eq!(
+            segments[2].key,
+            Some(SegmentKey {
+                uri: url("key2"),
+                iv: std::array::from_fn(|i| i as u8),
+            })
+        );
+        assert_eq!(
+            segments[2].map.as_ref().map(Url::as_str),
+            Some("http://localhost/init2.mp4")
+        );
+    }
+
+    #[test]
+    fn test_sample_aes() {
+        let playlist = MediaPlaylist::from_str(
+            "#EXTM3U
+#EXT-X-TARGETDURATION:6
+#EXT-X-KEY:METHOD=SAMPLE-AES,URI=\"key\"
+#EXTINF:6.0,
+a.ts
+",
+        )
+        .unwrap();
+
+        assert!(extract_segments(&playlist, &url("index.m3u8")).is_err());
+    }
+
+    #[test]
+    fn test_select_audio_only() {
+        let master = "#EXTM3U
+#EXT-X-STREAM-INF:BANDWIDTH=2000000,CODECS=\"avc1.4d401f,mp4a.40.2\",RESOLUTION=1280x720
+video.m3u8
+#EXT-X-STREAM-INF:BANDWIDTH=64000,CODECS=\"mp4a.40.5\"
+audio_low.m3u8
+#EXT-X-STREAM-INF:BANDWIDTH=128000,CODECS=\"mp4a.40.2\"
+audio_high.m3u8
+";
+        assert_eq!(select(master), url("audio_high.m3u8"));
+    }
+
+    #[test]
+    fn test_select_rendition() {
+        let master = "#EXTM3U
+#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"aac\",NAME=\"Commentary\",URI=\"commentary.m3u8\"
+#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"aac\",NAME=\"Main\",DEFAULT=YES,AUTOSELECT=YES,URI=\"main.m3u8\"
+#EXT-X-STREAM-INF:BANDWIDTH=2000000,CODECS=\"avc1.4d401f,mp4a.40.2\",AUDIO=\"aac\"
+video.m3u8
+";
+        assert_eq!(select(master), url("main.m3u8"));
+    }
+
+    #[test]
+    fn test_select_best_bandwidth() {
+        let master = "#EXTM3U
+#EXT-X-STREAM-INF:BANDWIDTH=800000
+low.m3u8
+#EXT-X-STREAM-INF:BANDWIDTH=2000000
+http://other.example.com/high.m3u8
+";
+        assert_eq!(
+            select(master).as_str(),
+            "http://other.example.com/high.m3u8"
+        );
+    }
+}
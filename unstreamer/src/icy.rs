@@ -0,0 +1,256 @@
+//! SHOUTcast/Icecast in-band metadata.
+//!
+//! When a client asks for it with `Icy-MetaData: 1`, the server sends `icy-metaint: N` header
+//! and puts a metadata block after every N bytes of audio.
+//! The block starts with a length byte, the length is in 16 bytes units,
+//! followed by text like `StreamTitle='Artist - Song';` padded with zeroes.
+
+use std::{
+    io::{self, Read, Write},
+    sync::{Arc, Mutex},
+};
+
+pub const ICY_METADATA_HEADER: &str = "Icy-MetaData";
+pub const ICY_METAINT_HEADER: &str = "icy-metaint";
+
+/// Metadata interval for our listeners.
+pub const METAINT: usize = 16_000;
+
+const BLOCK_UNIT: usize = 16;
+const MAX_BLOCK_SIZE: usize = u8::MAX as usize * BLOCK_UNIT;
+
+/// Strips metadata blocks from the stream, sends title changes to the channel.
+pub struct IcyReader<R> {
+    inner: R,
+    metaint: usize,
+    audio_left: usize,
+    title: Option<String>,
+    titles: flume::Sender<String>,
+}
+
+impl<R: Read> IcyReader<R> {
+    pub const fn new(inner: R, metaint: usize, titles: flume::Sender<String>) -> Self {
+        Self {
+            inner,
+            metaint,
+            audio_left: metaint,
+            title: None,
+            titles,
+        }
+    }
+
+    // Returns false on the end of stream.
+    fn read_metadata(&mut self) -> io::Result<bool> {
+        let mut length = [0u8];
+        if self.inner.read(&mut length)? == 0 {
+            return Ok(false);
+        }
+
+        let mut block = vec![0u8; usize::from(length[0]) * BLOCK_UNIT];
+        self.inner.read_exact(&mut block)?;
+
+        if let Some(title) = parse_stream_title(&block) {
+            if self.title.as_ref() != Some(&title) {
+                log::info!("ICY title: {title}");
+                // Nobody may listen for titles, that is fine.
+                _ = self.titles.send(title.clone());
+                self.title = Some(title);
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+impl<R: Read> Read for IcyReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.audio_left == 0 {
+            if !self.read_metadata()? {
+                return Ok(0);
+            }
+            self.audio_left = self.metaint;
+        }
+
+        let len = buf.len().min(self.audio_left);
+        let read = self.inner.read(&mut buf[..len])?;
+        self.audio_left -= read;
+
+        Ok(read)
+    }
+}
+
+/// Extracts `StreamTitle` from a metadata block.
+#[must_use]
+pub fn parse_stream_title(block: &[u8]) -> Option<String> {
+    const PREFIX: &str = "StreamTitle='";
+
+    let text = String::from_utf8_lossy(block);
+    let text = text.trim_end_matches('\0');
+
+    let start = text.find(PREFIX)? + PREFIX.len();
+    // Titles may contain quotes, the value ends with `';`.
+    let end = text[start..]
+        .find("';")
+        .or_else(|| text[start..].rfind('\''))?;
+
+    Some(text[start..start + end].to_string())
+}
+
+/// Encodes a metadata block with the title, an empty block if there is nothing to send.
+#[must_use]
+pub fn metadata_block(title: Option<&str>) -> Vec<u8> {
+    let Some(title) = title else {
+        return vec![0];
+    };
+
+    let mut text = format!("StreamTitle='{title}';").into_bytes();
+    if text.len() > MAX_BLOCK_SIZE {
+        log::warn!("ICY title is too long, truncated");
+        text.truncate(MAX_BLOCK_SIZE);
+    }
+
+    let units = text.len().div_ceil(BLOCK_UNIT);
+    text.resize(units * BLOCK_UNIT, 0);
+
+    let mut block = Vec::with_capacity(text.len() + 1);
+    block.push(units as u8);
+    block.extend(text);
+    block
+}
+
+/// Current title shared between a metadata source and `IcyWriter`.
+#[derive(Debug, Clone, Default)]
+pub struct IcyTitle(Arc<Mutex<Option<String>>>);
+
+impl IcyTitle {
+    pub fn set(&self, title: &str) {
+        let mut current = self.0.lock().unwrap();
+        if current.as_deref() != Some(title) {
+            *current = Some(title.to_string());
+        }
+    }
+
+    #[must_use]
+    pub fn get(&self) -> Option<String> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+/// Inserts metadata blocks into the stream, the title is sent when it changes.
+pub struct IcyWriter<W> {
+    inner: W,
+    metaint: usize,
+    audio_left: usize,
+    title: IcyTitle,
+    sent: Option<String>,
+}
+
+impl<W: Write> IcyWriter<W> {
+    pub const fn new(inner: W, metaint: usize, title: IcyTitle) -> Self {
+        Self {
+            inner,
+            metaint,
+            audio_left: metaint,
+            title,
+            sent: None,
+        }
+    }
+
+    fn write_metadata(&mut self) -> io::Result<()> {
+        let title = self.title.get();
+        let block = if title == self.sent {
+            metadata_block(None)
+        } else {
+            metadata_block(title.as_deref())
+        };
+        self.sent = title;
+        self.inner.write_all(&block)
+    }
+}
+
+impl<W: Write> Write for IcyWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = buf.len().min(self.audio_left);
+        let written = self.inner.write(&buf[..len])?;
+        self.audio_left -= written;
+
+        if self.audio_left == 0 {
+            self.write_metadata()?;
+            self.audio_left = self.metaint;
+        }
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_stream_title() {
+        assert_eq!(
+            parse_stream_title(b"StreamTitle='Artist - Song';StreamUrl='';\0\0\0"),
+            Some("Artist - Song".to_string())
+        );
+        assert_eq!(
+            parse_stream_title(b"StreamTitle='Don't Stop';\0"),
+            Some("Don't Stop".to_string())
+        );
+        assert_eq!(parse_stream_title(b"StreamTitle='';"), Some(String::new()));
+        assert_eq!(parse_stream_title(b"StreamUrl='x';"), None);
+        assert_eq!(parse_stream_title(b""), None);
+    }
+
+    #[test]
+    fn test_metadata_block() {
+        assert_eq!(metadata_block(None), vec![0]);
+
+        let block = metadata_block(Some("Song"));
+        assert_eq!(block.len(), 1 + 32);
+        assert_eq!(block[0], 2);
+        assert_eq!(parse_stream_title(&block[1..]), Some("Song".to_string()));
+
+        let block = metadata_block(Some(&"x".repeat(5_000)));
+        assert_eq!(block.len(), 1 + MAX_BLOCK_SIZE);
+        assert_eq!(block[0], u8::MAX);
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let audio = (0..100u8).collect::<Vec<_>>();
+        let title = IcyTitle::default();
+
+        let mut writer = IcyWriter::new(vec![], 16, title.clone());
+        title.set("First");
+        writer.write_all(&audio[..40]).unwrap();
+        title.set("Second");
+        writer.write_all(&audio[40..]).unwrap();
+
+        let stream = writer.inner;
+        // 6 blocks: "First", empty, "Second", then empty ones.
+        assert!(stream.len() > audio.len() + 6);
+
+        let (sender, titles) = flume::unbounded();
+        let mut reader = IcyReader::new(stream.as_slice(), 16, sender);
+        let mut output = vec![];
+        let mut buf = [0u8; 7];
+        loop {
+            let read = reader.read(&mut buf).unwrap();
+            if read == 0 {
+                break;
+            }
+            output.extend_from_slice(&buf[..read]);
+        }
+
+        assert_eq!(output, audio);
+        assert_eq!(
+            titles.try_iter().collect::<Vec<_>>(),
+            vec!["First".to_string(), "Second".to_string()]
+        );
+    }
+}
@@ -1,36 +1,429 @@
+mod dash;
 mod hls;
+pub mod icy;
+mod playlist_file;
+mod reconnect;
+mod segments;
 
 use std::io::Read;
+use std::path::Path;
 
-use anyhow::bail;
+use anyhow::{anyhow, bail, ensure};
 use url::Url;
 
+pub use reconnect::{Backoff, ConnectionStatus, ReconnectingReader};
+pub use segments::{LiveLag, SegmentsConfig};
+
 static MIME_AUDIO: &str = "audio/";
 
+// Playlist files may point at other playlist files.
+const MAX_PLAYLIST_DEPTH: usize = 3;
+
+/// Settings of `Unstreamer::open_with`.
+#[derive(Debug, Clone, Default)]
+pub struct OpenOptions {
+    /// Tells when a live source is reconnecting.
+    pub status: ConnectionStatus,
+    /// Limits of HLS and DASH sources.
+    pub segments: SegmentsConfig,
+}
+
 #[non_exhaustive]
-pub struct Unstreamer(Box<dyn Read + Send>);
+pub struct Unstreamer {
+    reader: Box<dyn Read + Send>,
+    titles: Option<flume::Receiver<String>>,
+    boundaries: Option<flume::Receiver<()>>,
+    lag: Option<LiveLag>,
+    live: bool,
+}
 
 impl Unstreamer {
     pub fn open(source: &str) -> anyhow::Result<Self> {
-        if let Ok(url) = Url::parse(source) {
-            let resp = ureq::get(url.as_ref()).call()?;
-            if resp.content_type() == hls::MIME_HLS {
-                Ok(Self(Box::new(hls::HLSUnstreamer::open(url)?)))
-            } else if resp.content_type().starts_with(MIME_AUDIO) {
-                Ok(Self(resp.into_reader()))
-            } else {
-                bail!("Unsupported content type: {}", resp.content_type());
+        Self::open_with(source, &OpenOptions::default())
+    }
+
+    pub fn open_with(source: &str, options: &OpenOptions) -> anyhow::Result<Self> {
+        Url::parse(source).map_or_else(
+            |_| Self::open_path(Path::new(source), options, 0),
+            |url| Self::open_url(url, options, 0),
+        )
+    }
+
+    // Entries of local playlists are relative to the playlist, like those of remote ones.
+    fn open_path(path: &Path, options: &OpenOptions, depth: usize) -> anyhow::Result<Self> {
+        let format = path.to_str().and_then(playlist_file::Format::from_path);
+
+        if let Some(format) = format {
+            ensure!(
+                depth < MAX_PLAYLIST_DEPTH,
+                "Playlist {} is nested too deep",
+                path.display()
+            );
+
+            let text = std::fs::read_to_string(path)?;
+            let url = Url::from_file_path(std::path::absolute(path)?)
+                .map_err(|()| anyhow!("Invalid playlist path {}", path.display()))?;
+            if format == playlist_file::Format::M3u && playlist_file::is_hls(&text) {
+                return Self::open_hls(url, options.status.clone(), options.segments);
             }
-        } else if let Ok(file) = std::fs::File::open(source) {
-            Ok(Self(Box::new(file)))
+
+            let entries = playlist_file::parse(format, &text)
+                .iter()
+                .filter_map(|entry| url.join(entry).ok())
+                .collect::<Vec<_>>();
+            Self::open_entries(&entries, options, depth + 1)
+        } else if let Ok(file) = std::fs::File::open(path) {
+            Ok(Self::new(Box::new(file)))
         } else {
-            bail!("Unsupported source: {}", source);
+            bail!("Unsupported source: {}", path.display());
+        }
+    }
+
+    fn open_url(url: Url, options: &OpenOptions, depth: usize) -> anyhow::Result<Self> {
+        if url.scheme() == "file" {
+            let path = url
+                .to_file_path()
+                .map_err(|()| anyhow!("Invalid file URL {url}"))?;
+            return Self::open_path(&path, options, depth);
         }
+
+        let OpenOptions {
+            status,
+            segments: config,
+        } = options.clone();
+
+        let resp = request(&url)?;
+        let content_type = resp.content_type().to_string();
+        let playlist_file = playlist_file::Format::detect(&content_type, url.path());
+
+        if hls::is_hls(&content_type) {
+            Self::open_hls(url, status, config)
+        } else if dash::is_dash(&content_type) {
+            let (sender, boundaries) = flume::unbounded();
+            let lag = LiveLag::default();
+            Ok(Self {
+                reader: dash::DASHUnstreamer::open(url, status, sender, config, lag.clone())?,
+                titles: None,
+                boundaries: Some(boundaries),
+                lag: Some(lag),
+                live: true,
+            })
+        } else if let Some(format) = playlist_file {
+            ensure!(
+                depth < MAX_PLAYLIST_DEPTH,
+                "Playlist {url} is nested too deep"
+            );
+
+            let text = String::from_utf8(segments::read_body(resp, config.max_playlist_size)?)?;
+            if format == playlist_file::Format::M3u && playlist_file::is_hls(&text) {
+                return Self::open_hls(url, status, config);
+            }
+
+            let entries = playlist_file::parse(format, &text)
+                .iter()
+                .filter_map(|entry| url.join(entry).ok())
+                .collect::<Vec<_>>();
+            Self::open_entries(&entries, options, depth + 1)
+        } else if content_type.starts_with(MIME_AUDIO) {
+            // A file of known length just ends, anything else may drop and reconnect.
+            let sized = resp.header("Content-Length").is_some();
+            let live = is_icy_stream(&resp);
+
+            let (sender, titles) = flume::unbounded();
+            let reader = audio_reader(resp, &sender);
+
+            let reader: Box<dyn Read + Send> = if sized {
+                reader
+            } else {
+                let reader = ReconnectingReader::new(
+                    reader,
+                    move || {
+                        let resp = request(&url)?;
+                        ensure!(
+                            resp.content_type().starts_with(MIME_AUDIO),
+                            "Unsupported content type: {}",
+                            resp.content_type()
+                        );
+                        Ok(audio_reader(resp, &sender))
+                    },
+                    status,
+                    Backoff::default(),
+                );
+                if live {
+                    Box::new(reader)
+                } else {
+                    Box::new(reader.ending_with_source())
+                }
+            };
+
+            Ok(Self {
+                reader,
+                titles: Some(titles),
+                boundaries: None,
+                lag: None,
+                live,
+            })
+        } else {
+            bail!("Unsupported content type: {content_type}");
+        }
+    }
+
+    fn open_hls(
+        url: Url,
+        status: ConnectionStatus,
+        config: SegmentsConfig,
+    ) -> anyhow::Result<Self> {
+        let (sender, boundaries) = flume::unbounded();
+        let lag = LiveLag::default();
+        Ok(Self {
+            reader: hls::HLSUnstreamer::open(url, status, sender, config, lag.clone())?,
+            titles: None,
+            boundaries: Some(boundaries),
+            lag: Some(lag),
+            live: true,
+        })
+    }
+
+    // Playlists list mirrors, the first reachable entry wins.
+    fn open_entries(entries: &[Url], options: &OpenOptions, depth: usize) -> anyhow::Result<Self> {
+        let mut last_error = anyhow!("Playlist has no entries");
+
+        for entry in entries {
+            match Self::open_url(entry.clone(), options, depth) {
+                Ok(unstreamer) => {
+                    log::info!("Open playlist entry {entry}");
+                    return Ok(unstreamer);
+                }
+                Err(err) => {
+                    log::warn!("Playlist entry {entry} failed: {err:#}");
+                    last_error = err;
+                }
+            }
+        }
+
+        Err(last_error)
+    }
+
+    fn new(reader: Box<dyn Read + Send>) -> Self {
+        Self {
+            reader,
+            titles: None,
+            boundaries: None,
+            lag: None,
+            live: false,
+        }
+    }
+
+    /// Stream title changes, if the source may send ICY metadata.
+    #[must_use]
+    pub fn titles(&self) -> Option<flume::Receiver<String>> {
+        self.titles.clone()
+    }
+
+    /// Points where the source signals a content change, e.g. HLS discontinuities or DASH periods.
+    #[must_use]
+    pub fn boundaries(&self) -> Option<flume::Receiver<()>> {
+        self.boundaries.clone()
+    }
+
+    /// The source plays in realtime, unlike files or finished playlists that can be read at once.
+    #[must_use]
+    pub fn is_live(&self) -> bool {
+        self.lag.as_ref().map_or(self.live, LiveLag::is_live)
+    }
+
+    /// How far behind the live edge the source is, if it is an HLS or DASH one.
+    #[must_use]
+    pub fn lag(&self) -> Option<LiveLag> {
+        self.lag.clone()
     }
 }
 
 impl Read for Unstreamer {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        self.0.read(buf)
+        self.reader.read(buf)
+    }
+}
+
+fn request(url: &Url) -> anyhow::Result<ureq::Response> {
+    Ok(ureq::get(url.as_ref())
+        .set(icy::ICY_METADATA_HEADER, "1")
+        .call()?)
+}
+
+// Shoutcast and Icecast streams announce themselves with ICY headers, e.g. `icy-name`.
+fn is_icy_stream(resp: &ureq::Response) -> bool {
+    resp.headers_names()
+        .iter()
+        .any(|name| name.to_ascii_lowercase().starts_with("icy-"))
+}
+
+// Strips ICY metadata, if the server sends it.
+fn audio_reader(resp: ureq::Response, titles: &flume::Sender<String>) -> Box<dyn Read + Send> {
+    let metaint = resp
+        .header(icy::ICY_METAINT_HEADER)
+        .and_then(|value| value.trim().parse::<usize>().ok())
+        .filter(|metaint| *metaint > 0);
+
+    match metaint {
+        Some(metaint) => Box::new(icy::IcyReader::new(
+            resp.into_reader(),
+            metaint,
+            titles.clone(),
+        )),
+        None => resp.into_reader(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+    };
+
+    use super::*;
+    use crate::segments::tests::serve;
+
+    // Serves the audio chunked, without a length, and counts requests.
+    fn serve_chunked(body: &'static [u8], headers: &'static str) -> (Url, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap();
+        let requests = Arc::new(AtomicUsize::default());
+
+        let counter = requests.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+                counter.fetch_add(1, Ordering::SeqCst);
+
+                write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Type: audio/mpeg\r\n{headers}\
+                     Transfer-Encoding: chunked\r\nConnection: close\r\n\r\n{:x}\r\n",
+                    body.len()
+                )
+                .unwrap();
+                stream.write_all(body).unwrap();
+                stream.write_all(b"\r\n0\r\n\r\n").unwrap();
+            }
+        });
+
+        (url, requests)
+    }
+
+    #[test]
+    fn test_chunked_file_ends() {
+        let (url, requests) = serve_chunked(b"audio", "");
+
+        let mut unstreamer = Unstreamer::open(url.join("song.mp3").unwrap().as_str()).unwrap();
+        let mut data = vec![];
+        unstreamer.read_to_end(&mut data).unwrap();
+
+        assert_eq!(data, b"audio");
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+        assert!(!unstreamer.is_live());
+    }
+
+    #[test]
+    fn test_icy_stream_reconnects() {
+        let (url, requests) = serve_chunked(b"audio", "icy-name: Radio\r\n");
+
+        let mut unstreamer = Unstreamer::open(url.join("live").unwrap().as_str()).unwrap();
+        let mut data = [0; 10];
+        unstreamer.read_exact(&mut data).unwrap();
+
+        assert_eq!(&data, b"audioaudio");
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+        assert!(unstreamer.is_live());
+    }
+
+    #[test]
+    fn test_playlist_fallback() {
+        let url = serve(vec![
+            (
+                "/radio.pls",
+                "audio/x-scpls",
+                b"[playlist]\nFile1=http://127.0.0.1:1/down\nFile2=mirror.m3u\n".to_vec(),
+            ),
+            ("/mirror.m3u", "text/plain", b"#EXTM3U\nsong.mp3\n".to_vec()),
+            ("/song.mp3", "audio/mpeg", b"audio".to_vec()),
+        ]);
+
+        let mut unstreamer = Unstreamer::open(url.join("radio.pls").unwrap().as_str()).unwrap();
+        let mut data = vec![];
+        unstreamer.read_to_end(&mut data).unwrap();
+        assert_eq!(data, b"audio");
+    }
+
+    // A fresh directory of the files, removed by the caller.
+    fn local_files(name: &str, files: &[(&str, &[u8])]) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("unstreamer-{}-{name}", std::process::id()));
+        for (file, content) in files {
+            let path = dir.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn test_local_playlist() {
+        let dir = local_files(
+            "playlist",
+            &[
+                (
+                    "radio.pls",
+                    b"[playlist]\nFile1=missing.mp3\nFile2=music/list.m3u\n",
+                ),
+                ("music/list.m3u", b"#EXTM3U\nsong.mp3\n"),
+                ("music/song.mp3", b"audio"),
+            ],
+        );
+
+        let result =
+            Unstreamer::open(dir.join("radio.pls").to_str().unwrap()).and_then(|mut unstreamer| {
+                let mut data = vec![];
+                unstreamer.read_to_end(&mut data)?;
+                Ok((data, unstreamer.is_live()))
+            });
+        std::fs::remove_dir_all(dir).unwrap();
+
+        assert_eq!(result.unwrap(), (b"audio".to_vec(), false));
+    }
+
+    #[test]
+    fn test_local_hls_playlist() {
+        let dir = local_files(
+            "hls",
+            &[
+                (
+                    "index.m3u",
+                    b"#EXTM3U\n#EXT-X-TARGETDURATION:6\n#EXTINF:6.0,\nfirst.aac\n\
+                      #EXTINF:6.0,\nsecond.aac\n#EXT-X-ENDLIST\n",
+                ),
+                ("first.aac", b"first "),
+                ("second.aac", b"second"),
+            ],
+        );
+
+        let result =
+            Unstreamer::open(dir.join("index.m3u").to_str().unwrap()).and_then(|mut unstreamer| {
+                let mut data = vec![];
+                unstreamer.read_to_end(&mut data)?;
+                Ok(data)
+            });
+        std::fs::remove_dir_all(dir).unwrap();
+
+        assert_eq!(result.unwrap(), b"first second");
     }
 }
@@ -0,0 +1,142 @@
+//! Station playlist files, `.pls` and plain `.m3u`, which point at the actual streams.
+
+static MIME_PLS: [&str; 2] = ["audio/x-scpls", "application/pls+xml"];
+static MIME_M3U: [&str; 2] = ["audio/x-mpegurl", "audio/mpegurl"];
+// Types of misconfigured servers, the extension tells the format then.
+static MIME_GENERIC: [&str; 3] = ["text/plain", "application/octet-stream", ""];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Pls,
+    M3u,
+}
+
+impl Format {
+    pub fn detect(content_type: &str, path: &str) -> Option<Self> {
+        if contains(&MIME_PLS, content_type) {
+            Some(Self::Pls)
+        } else if is_m3u(content_type) {
+            Some(Self::M3u)
+        } else if contains(&MIME_GENERIC, content_type) {
+            Self::from_path(path)
+        } else {
+            None
+        }
+    }
+
+    pub fn from_path(path: &str) -> Option<Self> {
+        let (_, extension) = path.rsplit_once('.')?;
+        if extension.eq_ignore_ascii_case("pls") {
+            Some(Self::Pls)
+        } else if extension.eq_ignore_ascii_case("m3u") {
+            Some(Self::M3u)
+        } else {
+            None
+        }
+    }
+}
+
+pub fn is_m3u(content_type: &str) -> bool {
+    contains(&MIME_M3U, content_type)
+}
+
+fn contains(types: &[&str], content_type: &str) -> bool {
+    types
+        .iter()
+        .any(|mime| mime.eq_ignore_ascii_case(content_type))
+}
+
+/// Stream URLs in the order of the playlist, they may be relative.
+pub fn parse(format: Format, text: &str) -> Vec<String> {
+    let lines = text
+        .trim_start_matches('\u{feff}')
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty());
+
+    match format {
+        // `File1=http://...`, entries are numbered, the order of lines does not matter.
+        Format::Pls => {
+            let mut entries = lines
+                .filter_map(|line| {
+                    let (key, value) = line.split_once('=')?;
+                    let number = key
+                        .get(..4)
+                        .filter(|prefix| prefix.eq_ignore_ascii_case("file"))
+                        .and_then(|_| key[4..].parse::<usize>().ok())?;
+                    Some((number, value.trim().to_string()))
+                })
+                .collect::<Vec<_>>();
+            entries.sort_by_key(|(number, _)| *number);
+            entries.into_iter().map(|(_, entry)| entry).collect()
+        }
+        Format::M3u => lines
+            .filter(|line| !line.starts_with('#'))
+            .map(str::to_string)
+            .collect(),
+    }
+}
+
+/// Tells an HLS playlist from a plain list of streams.
+pub fn is_hls(text: &str) -> bool {
+    text.lines()
+        .any(|line| line.trim_start().starts_with("#EXT-X-"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(
+            Format::detect("audio/x-scpls", "/listen"),
+            Some(Format::Pls)
+        );
+        assert_eq!(
+            Format::detect("audio/x-mpegurl", "/listen"),
+            Some(Format::M3u)
+        );
+        assert_eq!(
+            Format::detect("text/plain", "/radio.PLS"),
+            Some(Format::Pls)
+        );
+        assert_eq!(Format::detect("text/plain", "/radio.txt"), None);
+        assert_eq!(Format::detect("audio/mpeg", "/radio.m3u"), None);
+    }
+
+    #[test]
+    fn test_parse_pls() {
+        let text = "\u{feff}[playlist]\r
+NumberOfEntries=2\r
+File2=http://backup.example.com/stream\r
+Title2=Backup\r
+file1=http://main.example.com/stream\r
+Title1=Main\r
+Version=2\r
+";
+        assert_eq!(
+            parse(Format::Pls, text),
+            vec![
+                "http://main.example.com/stream",
+                "http://backup.example.com/stream"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_m3u() {
+        let text = "#EXTM3U
+#EXTINF:-1,Radio
+http://main.example.com/stream
+
+relative/stream.mp3
+";
+        assert_eq!(
+            parse(Format::M3u, text),
+            vec!["http://main.example.com/stream", "relative/stream.mp3"]
+        );
+        assert!(!is_hls(text));
+        assert!(is_hls("#EXTM3U\n#EXT-X-TARGETDURATION:6\n"));
+    }
+}
@@ -0,0 +1,257 @@
+use std::{
+    io::{self, Read},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+const INITIAL_DELAY: Duration = Duration::from_millis(500);
+const MAX_DELAY: Duration = Duration::from_secs(30);
+// About 3 minutes in total before giving up.
+const MAX_ATTEMPTS: u32 = 10;
+
+/// Exponentially growing delays between reconnect attempts, ends when out of attempts.
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    initial: Duration,
+    max: Duration,
+    max_attempts: u32,
+    attempts: u32,
+}
+
+impl Backoff {
+    #[must_use]
+    pub const fn new(initial: Duration, max: Duration, max_attempts: u32) -> Self {
+        Self {
+            initial,
+            max,
+            max_attempts,
+            attempts: 0,
+        }
+    }
+
+    /// Starts over after a successful connection.
+    pub const fn reset(&mut self) {
+        self.attempts = 0;
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self::new(INITIAL_DELAY, MAX_DELAY, MAX_ATTEMPTS)
+    }
+}
+
+impl Iterator for Backoff {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.attempts >= self.max_attempts {
+            return None;
+        }
+
+        let delay = self
+            .initial
+            .saturating_mul(2u32.saturating_pow(self.attempts))
+            .min(self.max);
+        self.attempts += 1;
+
+        Some(delay)
+    }
+}
+
+/// Tells whether a live source is reconnecting, shared with whoever waits for its data.
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionStatus(Arc<AtomicBool>);
+
+impl ConnectionStatus {
+    #[must_use]
+    pub fn is_reconnecting(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    pub fn set_reconnecting(&self, reconnecting: bool) {
+        self.0.store(reconnecting, Ordering::SeqCst);
+    }
+}
+
+/// Reopens the source when it fails or ends, so a live stream survives a dropped connection.
+///
+/// The new connection starts at the live edge, whatever was missed in between is lost.
+pub struct ReconnectingReader<F> {
+    reader: Box<dyn Read + Send>,
+    open: F,
+    status: ConnectionStatus,
+    backoff: Backoff,
+    reopen_at_end: bool,
+}
+
+impl<F> ReconnectingReader<F>
+where
+    F: FnMut() -> anyhow::Result<Box<dyn Read + Send>>,
+{
+    pub fn new(
+        reader: Box<dyn Read + Send>,
+        open: F,
+        status: ConnectionStatus,
+        backoff: Backoff,
+    ) -> Self {
+        Self {
+            reader,
+            open,
+            status,
+            backoff,
+            reopen_at_end: true,
+        }
+    }
+
+    /// Ends where the source ends, only failures reconnect.
+    #[must_use]
+    pub const fn ending_with_source(mut self) -> Self {
+        self.reopen_at_end = false;
+        self
+    }
+
+    fn reconnect(&mut self) -> io::Result<()> {
+        self.status.set_reconnecting(true);
+
+        let result = loop {
+            let Some(delay) = self.backoff.next() else {
+                break Err(io::Error::new(
+                    io::ErrorKind::NotConnected,
+                    "Source is gone, gave up reconnecting",
+                ));
+            };
+
+            log::info!("Reconnect in {}ms", delay.as_millis());
+            std::thread::sleep(delay);
+
+            match (self.open)() {
+                Ok(reader) => {
+                    log::info!("Reconnected");
+                    self.reader = reader;
+                    break Ok(());
+                }
+                Err(err) => log::warn!("Reconnect failed: {err:#}"),
+            }
+        };
+
+        self.status.set_reconnecting(false);
+        result
+    }
+}
+
+impl<F> Read for ReconnectingReader<F>
+where
+    F: FnMut() -> anyhow::Result<Box<dyn Read + Send>>,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            match self.reader.read(buf) {
+                Ok(0) if !self.reopen_at_end => return Ok(0),
+                Ok(0) => log::warn!("Live source ended"),
+                Ok(read) => {
+                    // Only data proves the connection is alive, connecting is not enough.
+                    self.backoff.reset();
+                    return Ok(read);
+                }
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => log::warn!("Live source failed: {err}"),
+            }
+
+            self.reconnect()?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::VecDeque, io::Cursor};
+
+    use super::*;
+
+    #[test]
+    fn test_backoff() {
+        let delays = Backoff::default()
+            .map(|d| d.as_millis())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            delays,
+            vec![500, 1_000, 2_000, 4_000, 8_000, 16_000, 30_000, 30_000, 30_000, 30_000]
+        );
+
+        let mut backoff = Backoff::new(INITIAL_DELAY, MAX_DELAY, 2);
+        backoff.by_ref().for_each(drop);
+        backoff.reset();
+        assert_eq!(backoff.next(), Some(INITIAL_DELAY));
+    }
+
+    fn reader(data: &'static [u8]) -> Box<dyn Read + Send> {
+        Box::new(Cursor::new(data))
+    }
+
+    #[test]
+    fn test_reconnect() {
+        let mut connections = VecDeque::from([
+            Err(anyhow::anyhow!("Refused")),
+            Ok(reader(b"cd")),
+            Err(anyhow::anyhow!("Refused")),
+            Ok(reader(b"ef")),
+        ]);
+        let status = ConnectionStatus::default();
+
+        let mut sut = ReconnectingReader::new(
+            reader(b"ab"),
+            move || {
+                connections
+                    .pop_front()
+                    .unwrap_or_else(|| anyhow::bail!("Gone"))
+            },
+            status.clone(),
+            Backoff::new(Duration::ZERO, Duration::ZERO, 2),
+        );
+
+        let mut data = vec![];
+        let err = sut.read_to_end(&mut data).unwrap_err();
+
+        assert_eq!(data, b"abcdef");
+        assert_eq!(err.kind(), io::ErrorKind::NotConnected);
+        assert!(!status.is_reconnecting());
+    }
+
+    struct Failing;
+
+    impl Read for Failing {
+        fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+            Err(io::ErrorKind::ConnectionReset.into())
+        }
+    }
+
+    #[test]
+    fn test_ending_with_source() {
+        let mut connections = VecDeque::from([Ok(reader(b"cd"))]);
+
+        let mut sut = ReconnectingReader::new(
+            Box::new(Failing),
+            move || {
+                connections
+                    .pop_front()
+                    .unwrap_or_else(|| anyhow::bail!("Replayed"))
+            },
+            ConnectionStatus::default(),
+            Backoff::new(Duration::ZERO, Duration::ZERO, 2),
+        )
+        .ending_with_source();
+
+        // Reconnects after the failure, but not at the end.
+        let mut data = vec![];
+        sut.read_to_end(&mut data).unwrap();
+        assert_eq!(data, b"cd");
+    }
+}
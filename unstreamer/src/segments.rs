@@ -0,0 +1,448 @@
+//! Input assembled from downloaded media segments, shared by HLS and DASH.
+
+use std::io::{Cursor, Read};
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Arc,
+};
+use std::time::Duration;
+
+use anyhow::{anyhow, ensure};
+use flume::{Receiver, Sender};
+
+use crate::{Backoff, ConnectionStatus};
+
+const MAX_LAG: Duration = Duration::from_secs(30);
+const MAX_PLAYLIST_SIZE: u64 = 1024 * 1024;
+const MAX_SEGMENT_SIZE: u64 = 16 * 1024 * 1024;
+
+/// Limits of the HLS and DASH input.
+#[derive(Debug, Clone, Copy)]
+pub struct SegmentsConfig {
+    /// Falling further behind the live edge skips to it, fetched but unread audio counts too.
+    pub max_lag: Duration,
+    /// Applies to HLS playlists and DASH manifests.
+    pub max_playlist_size: u64,
+    pub max_segment_size: u64,
+}
+
+impl Default for SegmentsConfig {
+    fn default() -> Self {
+        Self {
+            max_lag: MAX_LAG,
+            max_playlist_size: MAX_PLAYLIST_SIZE,
+            max_segment_size: MAX_SEGMENT_SIZE,
+        }
+    }
+}
+
+/// How far behind the live edge the input is.
+#[derive(Debug, Clone, Default)]
+pub struct LiveLag(Arc<LagCounters>);
+
+// In milliseconds.
+#[derive(Debug, Default)]
+struct LagCounters {
+    buffered: AtomicU64,
+    unfetched: AtomicU64,
+    live: AtomicBool,
+}
+
+impl LiveLag {
+    /// Audio fetched but not read yet plus audio in the playlist not fetched yet.
+    #[must_use]
+    pub fn get(&self) -> Duration {
+        self.buffered() + Duration::from_millis(self.0.unfetched.load(Ordering::SeqCst))
+    }
+
+    /// The playlist or manifest has a live edge, a finished or static one has none.
+    #[must_use]
+    pub fn is_live(&self) -> bool {
+        self.0.live.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn set_live(&self, live: bool) {
+        self.0.live.store(live, Ordering::SeqCst);
+    }
+
+    fn buffered(&self) -> Duration {
+        Duration::from_millis(self.0.buffered.load(Ordering::SeqCst))
+    }
+
+    fn add_buffered(&self, duration: Duration) {
+        self.0
+            .buffered
+            .fetch_add(millis(duration), Ordering::SeqCst);
+    }
+
+    fn remove_buffered(&self, duration: Duration) {
+        self.0
+            .buffered
+            .fetch_sub(millis(duration), Ordering::SeqCst);
+    }
+
+    fn set_unfetched(&self, duration: Duration) {
+        self.0.unfetched.store(millis(duration), Ordering::SeqCst);
+    }
+}
+
+fn millis(duration: Duration) -> u64 {
+    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+}
+
+pub enum Progress {
+    /// Wait that long for the playlist update.
+    Wait(Duration),
+    /// The playlist has ended, everything is fetched.
+    Ended,
+}
+
+struct Segment {
+    data: Vec<u8>,
+    duration: Duration,
+}
+
+/// Fetches segments of a playlist as they appear.
+pub trait Fetcher: Send + 'static {
+    /// Fetches segments newer than the last fetched one.
+    /// When resuming after an outage, missed segments are skipped.
+    fn fetch_new_segments(&mut self, resume: bool) -> anyhow::Result<Progress>;
+
+    fn sender(&self) -> &SegmentSender;
+}
+
+pub fn channel(
+    boundaries: Sender<()>,
+    config: SegmentsConfig,
+    lag: LiveLag,
+) -> (SegmentSender, SegmentReader) {
+    let (data_tx, data_rx) = flume::unbounded();
+    let (error_tx, error_rx) = flume::bounded::<anyhow::Error>(1);
+
+    let sender = SegmentSender {
+        data_tx,
+        data_rx: data_rx.clone(),
+        error_tx,
+        boundaries,
+        config,
+        lag: lag.clone(),
+    };
+    let reader = SegmentReader {
+        data_rx,
+        error_rx,
+        current: Cursor::default(),
+        lag,
+    };
+
+    (sender, reader)
+}
+
+/// Runs the fetcher until the playlist ends, the reader is dropped or the source is gone.
+pub fn spawn(mut fetcher: impl Fetcher, status: ConnectionStatus) {
+    let mut backoff = Backoff::default();
+
+    std::thread::spawn(move || loop {
+        if fetcher.sender().is_consumer_gone() {
+            log::error!("Data is disconected, exit");
+            break;
+        }
+
+        let resume = status.is_reconnecting();
+        let wait = match fetcher.fetch_new_segments(resume) {
+            Ok(Progress::Ended) => {
+                log::info!("Playlist ended");
+                status.set_reconnecting(false);
+                break;
+            }
+            Ok(Progress::Wait(wait)) => {
+                if status.is_reconnecting() {
+                    log::info!("Segmented source is back");
+                    status.set_reconnecting(false);
+                }
+                backoff.reset();
+                wait
+            }
+            Err(error) => {
+                let Some(delay) = backoff.next() else {
+                    status.set_reconnecting(false);
+                    let _ = fetcher.sender().error_tx.send(error);
+                    break;
+                };
+
+                log::warn!(
+                    "Segmented source failed, retry in {}ms: {error:#}",
+                    delay.as_millis()
+                );
+                status.set_reconnecting(true);
+                delay
+            }
+        };
+
+        std::thread::sleep(wait);
+    });
+}
+
+// Fetching stops while the consumer has `max_lag` of audio unread,
+// the playlist moves on meanwhile and the lag check skips to the live edge then.
+// So at most `max_lag` and a segment are buffered.
+pub struct SegmentSender {
+    data_tx: Sender<Segment>,
+    // Unread segments are dropped from here when skipping to the live edge.
+    data_rx: Receiver<Segment>,
+    error_tx: Sender<anyhow::Error>,
+    boundaries: Sender<()>,
+    pub config: SegmentsConfig,
+    lag: LiveLag,
+}
+
+impl SegmentSender {
+    pub fn send(&self, data: Vec<u8>, duration: Duration) {
+        self.lag.add_buffered(duration);
+        _ = self.data_tx.send(Segment { data, duration });
+    }
+
+    /// Content likely changes here, e.g. an ad is spliced in.
+    pub fn boundary(&self) {
+        _ = self.boundaries.send(());
+    }
+
+    // The sender holds a receiver itself.
+    pub fn is_consumer_gone(&self) -> bool {
+        self.data_tx.receiver_count() <= 1
+    }
+
+    /// The consumer is slow, fetching is to wait.
+    pub fn is_full(&self) -> bool {
+        self.lag.buffered() >= self.config.max_lag
+    }
+
+    pub fn is_too_far_behind(&self) -> bool {
+        self.lag.get() > self.config.max_lag
+    }
+
+    pub fn set_live(&self, live: bool) {
+        self.lag.set_live(live);
+    }
+
+    /// Duration of the live playlist not fetched yet.
+    pub fn set_unfetched(&self, duration: Duration) {
+        self.lag.set_unfetched(duration);
+    }
+
+    pub fn lag(&self) -> Duration {
+        self.lag.get()
+    }
+
+    /// Drops unread audio, returns the index of the first segment to fetch from the live edge.
+    ///
+    /// The newest segments up to half of the lag limit are kept,
+    /// so the next playlist update does not exceed the limit again.
+    pub fn skip_to_live_edge(&self, durations: &[Duration]) -> usize {
+        log::warn!(
+            "Input is {}ms behind live, skip to the live edge",
+            self.lag.get().as_millis()
+        );
+
+        for segment in self.data_rx.try_iter() {
+            self.lag.remove_buffered(segment.duration);
+        }
+
+        let mut total = Duration::ZERO;
+        let kept = durations
+            .iter()
+            .rev()
+            .take_while(|duration| {
+                total += **duration;
+                total <= self.config.max_lag / 2
+            })
+            .count()
+            .max(1);
+
+        durations.len().saturating_sub(kept)
+    }
+}
+
+pub struct SegmentReader {
+    data_rx: Receiver<Segment>,
+    error_rx: Receiver<anyhow::Error>,
+    current: Cursor<Vec<u8>>,
+    lag: LiveLag,
+}
+
+impl Read for SegmentReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            let read = self.current.read(buf)?;
+            if read > 0 {
+                return Ok(read);
+            }
+
+            // Wait for the next segment.
+            match self.data_rx.recv() {
+                Ok(segment) => {
+                    self.lag.remove_buffered(segment.duration);
+                    self.current = Cursor::new(segment.data);
+                }
+                // Fetching stopped, either the playlist has ended or the source is gone.
+                Err(_) => {
+                    return self
+                        .error_rx
+                        .try_recv()
+                        .map_or(Ok(0), |error| Err(std::io::Error::other(error)))
+                }
+            }
+        }
+    }
+}
+
+// Refuses bodies over the limit, so a broken server cannot exhaust the memory.
+pub fn read_body(resp: ureq::Response, limit: u64) -> anyhow::Result<Vec<u8>> {
+    let url = resp.get_url().to_string();
+    let length = resp
+        .header("Content-Length")
+        .and_then(|length| length.parse::<u64>().ok());
+    if let Some(length) = length {
+        ensure!(length <= limit, "{url} is too large: {length} bytes");
+    }
+
+    read_limited(resp.into_reader(), limit, &url)
+}
+
+/// Downloads a segment, or reads it if it is a local file.
+pub fn fetch(source: &url::Url, limit: u64) -> anyhow::Result<Vec<u8>> {
+    if source.scheme() != "file" {
+        return read_body(ureq::get(source.as_ref()).call()?, limit);
+    }
+
+    let path = source
+        .to_file_path()
+        .map_err(|()| anyhow!("Invalid file URL {source}"))?;
+    read_limited(std::fs::File::open(path)?, limit, source.as_str())
+}
+
+fn read_limited(reader: impl Read, limit: u64, source: &str) -> anyhow::Result<Vec<u8>> {
+    let mut data = vec![];
+    reader
+        .take(limit.saturating_add(1))
+        .read_to_end(&mut data)?;
+    ensure!(
+        data.len() as u64 <= limit,
+        "{source} is larger than {limit} bytes"
+    );
+
+    Ok(data)
+}
+
+/// Downloads a playlist or a manifest of the expected type.
+pub fn fetch_playlist(
+    source: &url::Url,
+    is_expected: fn(&str) -> bool,
+    max_size: u64,
+) -> anyhow::Result<String> {
+    // Local files have no content type to check.
+    if source.scheme() == "file" {
+        return Ok(String::from_utf8(fetch(source, max_size)?)?);
+    }
+
+    let resp = ureq::get(source.as_ref()).call()?;
+    ensure!(
+        is_expected(resp.content_type()),
+        "Invalid content type: {}",
+        resp.content_type()
+    );
+    Ok(String::from_utf8(read_body(resp, max_size)?)?)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+    };
+
+    use url::Url;
+
+    use super::*;
+
+    // Local stand-in for an HLS or DASH server, serves fixed files.
+    pub fn serve(files: Vec<(&'static str, &'static str, Vec<u8>)>) -> Url {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap();
+
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+                let mut request = String::new();
+                reader.read_line(&mut request).unwrap();
+                let mut header = String::new();
+                while reader.read_line(&mut header).unwrap() > 2 {
+                    header.clear();
+                }
+
+                let path = request.split_whitespace().nth(1).unwrap_or_default();
+                match files.iter().find(|(file, ..)| *file == path) {
+                    Some((_, content_type, body)) => {
+                        write!(
+                            stream,
+                            "HTTP/1.1 200 OK\r\nContent-Type: {content_type}\r\n\
+                             Content-Length: {}\r\nConnection: close\r\n\r\n",
+                            body.len()
+                        )
+                        .unwrap();
+                        stream.write_all(body).unwrap();
+                    }
+                    None => {
+                        write!(
+                            stream,
+                            "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                        )
+                        .unwrap();
+                    }
+                }
+            }
+        });
+
+        url
+    }
+
+    #[test]
+    fn test_size_limit() {
+        let resp = |body: &str| ureq::Response::new(200, "OK", body).unwrap();
+
+        assert_eq!(read_body(resp("1234"), 4).unwrap(), b"1234");
+        assert!(read_body(resp("12345"), 4).is_err());
+    }
+
+    #[test]
+    fn test_skip_to_live_edge() {
+        let (sender, mut reader) = channel(
+            flume::unbounded().0,
+            SegmentsConfig {
+                max_lag: Duration::from_secs(12),
+                ..SegmentsConfig::default()
+            },
+            LiveLag::default(),
+        );
+        let secs = Duration::from_secs;
+
+        sender.send(b"old".to_vec(), secs(6));
+        assert_eq!(sender.lag(), secs(6));
+
+        assert_eq!(sender.skip_to_live_edge(&[secs(4), secs(4), secs(4)]), 2);
+        assert_eq!(sender.lag(), Duration::ZERO);
+        assert_eq!(sender.skip_to_live_edge(&[secs(2), secs(2), secs(2)]), 0);
+        // Too long segments, the last one is kept anyway.
+        assert_eq!(sender.skip_to_live_edge(&[secs(10), secs(10)]), 1);
+
+        sender.send(b"new".to_vec(), secs(6));
+        let mut data = [0u8; 3];
+        reader.read_exact(&mut data).unwrap();
+        assert_eq!(&data, b"new");
+    }
+}